@@ -1,40 +1,132 @@
+use std::collections::HashMap;
 use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::{env, fs, io};
 
 use clap::Parser;
 use color_eyre::Help;
-use directories_next::ProjectDirs;
+use directories_next::{BaseDirs, ProjectDirs};
 
 use crate::errors::LostTheWay;
 use crate::utils::{get_default_copy_cmd, NAME};
 
 #[derive(Parser, Debug)]
 pub enum ConfigCommand {
-    /// Prints / writes the default configuration options.
+    /// Writes the default configuration options.
     /// Set the generated config file as default by setting the $THE_WAY_CONFIG environment variable
+    ///
+    /// Without a file, writes to the standard configuration location if there's no configuration there yet,
+    /// otherwise prints to stdout. An existing file is only overwritten with --force.
     Default {
         /// File to save the configuration to.
         file: Option<PathBuf>,
+        /// Overwrite an existing configuration file
+        #[clap(long, short)]
+        force: bool,
     },
     /// Prints location of currently set configuration file
+    /// (with --verbose, also the database and themes directories)
     Get,
 }
 
+/// Service snippets are synced to
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncBackend {
+    #[default]
+    Github,
+    Gitlab,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TheWayConfig {
     /// Selected theme
+    #[serde(default = "get_default_theme")]
     pub(crate) theme: String,
     /// Path to the directory containing the sled database files
+    #[serde(default = "get_default_db_dir")]
     pub(crate) db_dir: PathBuf,
     /// Path to the directory containing theme files
+    #[serde(default = "get_default_themes_dir")]
     pub(crate) themes_dir: PathBuf,
     #[serde(default = "get_default_copy_cmd")]
     pub(crate) copy_cmd: Option<String>,
+    /// Command used to render the search preview (snippet code is piped to its stdin).
+    /// Split on whitespace, so quoted arguments aren't supported
+    pub(crate) search_preview_cmd: Option<String>,
+    /// Use exact search instead of fuzzy by default
+    #[serde(default)]
+    pub(crate) search_exact: bool,
+    /// Store tags in lowercase
+    #[serde(default)]
+    pub(crate) lowercase_tags: bool,
+    /// Choose tags from the existing ones in a multi-select when adding or editing snippets
+    #[serde(default)]
+    pub(crate) pick_tags: bool,
+    /// Warn about (or refuse to import) snippets with more bytes of code than this
+    #[serde(default)]
+    pub(crate) max_code_bytes: Option<usize>,
+    /// Format of each snippet's line in the synced Gist's index.md file,
+    /// with {description}, {url}, {file} and {tags} placeholders
+    pub(crate) gist_index_line: Option<String>,
     /// Github token for the Gist API (i.e "gist" scope set)
     pub(crate) github_access_token: Option<String>,
+    /// Save the GitHub token to the OS keyring instead of this file, and look for it there
+    /// (needs the-way built with the "keyring" feature)
+    #[serde(default)]
+    pub(crate) use_keyring: bool,
+    /// GitHub Enterprise API URL (like https://github.mycorp.com/api/v3), https://api.github.com if not set.
+    /// $THE_WAY_GITHUB_API_URL takes precedence
+    pub(crate) github_api_url: Option<String>,
+    /// Create the synced Gist (or GitLab snippet) as public instead of secret.
+    /// Only used when creating it, delete the Gist and sync again to change an existing one
+    #[serde(default)]
+    pub(crate) gist_public: bool,
+    /// Where snippets are synced to, "github" (a Gist) or "gitlab" (a GitLab snippet)
+    #[serde(default)]
+    pub(crate) sync_backend: SyncBackend,
+    /// Base URL of a self-hosted GitLab instance to sync to, https://gitlab.com if not set
+    pub(crate) gitlab_url: Option<String>,
     /// ID of Gist used for sync
     pub gist_id: Option<String>,
+    /// URL of Gist used for sync
+    pub gist_url: Option<String>,
+    /// ID of GitLab snippet used for sync (with `sync_backend = "gitlab"`)
+    pub gitlab_snippet_id: Option<String>,
+    /// URL of GitLab snippet used for sync
+    pub gitlab_snippet_url: Option<String>,
+    /// Hex colors (like "#dea584") overriding the GitHub colors of languages' boxes, by language name.
+    /// Kept last since TOML tables have to come after plain values
+    #[serde(default)]
+    pub(crate) language_colors: HashMap<String, String>,
+    /// `db_dir` and `themes_dir` as written in the configuration file, before expanding them
+    #[serde(skip)]
+    pub(crate) unexpanded_dirs: Option<(PathBuf, PathBuf)>,
+    /// File the configuration was loaded from, and is stored to
+    #[serde(skip)]
+    file: Option<PathBuf>,
+}
+
+/// Expands a leading `~`, `$HOME` or `$XDG_DATA_HOME` (also as `${...}`) in a path,
+/// and resolves relative paths against `relative_to`. Absolute paths are kept as they are,
+/// and variables after the first component aren't expanded
+fn expand_path(path: &Path, base_dirs: &BaseDirs, relative_to: &Path) -> PathBuf {
+    let path = path.to_string_lossy();
+    let prefixes = [
+        ("~", base_dirs.home_dir()),
+        ("${HOME}", base_dirs.home_dir()),
+        ("$HOME", base_dirs.home_dir()),
+        ("${XDG_DATA_HOME}", base_dirs.data_dir()),
+        ("$XDG_DATA_HOME", base_dirs.data_dir()),
+    ];
+    for (prefix, dir) in prefixes {
+        if let Some(rest) = path.strip_prefix(prefix) {
+            if rest.is_empty() || rest.starts_with(std::path::is_separator) {
+                return relative_to.join(format!("{}{rest}", dir.to_string_lossy()));
+            }
+        }
+    }
+    relative_to.join(&*path)
 }
 
 /// Main project directory, cross-platform
@@ -42,28 +134,54 @@ fn get_project_dir() -> color_eyre::Result<ProjectDirs> {
     Ok(ProjectDirs::from("rs", "", NAME).ok_or(LostTheWay::Homeless)?)
 }
 
+/// Main project data directory, made if it doesn't exist
+fn get_data_dir() -> PathBuf {
+    let dir = get_project_dir().expect("Couldn't get project dir");
+    let data_dir = dir.data_dir();
+    if !data_dir.exists() {
+        fs::create_dir_all(data_dir).expect("Couldn't create data dir");
+    }
+    data_dir.to_owned()
+}
+
+fn get_default_theme() -> String {
+    String::from("base16-ocean.dark")
+}
+
+fn get_default_db_dir() -> PathBuf {
+    get_data_dir().join("the_way_db")
+}
+
+fn get_default_themes_dir() -> PathBuf {
+    get_data_dir().join("themes")
+}
+
 impl Default for TheWayConfig {
     fn default() -> Self {
-        let (db_dir, themes_dir, theme, copy_cmd) = {
-            let dir = get_project_dir().expect("Couldn't get project dir");
-            let data_dir = dir.data_dir();
-            if !data_dir.exists() {
-                fs::create_dir_all(data_dir).expect("Couldn't create data dir");
-            }
-            (
-                data_dir.join("the_way_db"),
-                data_dir.join("themes"),
-                String::from("base16-ocean.dark"),
-                get_default_copy_cmd(),
-            )
-        };
         let config = Self {
-            theme,
-            db_dir,
-            themes_dir,
-            copy_cmd,
+            theme: get_default_theme(),
+            db_dir: get_default_db_dir(),
+            themes_dir: get_default_themes_dir(),
+            copy_cmd: get_default_copy_cmd(),
+            search_preview_cmd: None,
+            search_exact: false,
+            lowercase_tags: false,
+            pick_tags: false,
+            max_code_bytes: None,
+            gist_index_line: None,
             github_access_token: None,
+            use_keyring: false,
+            github_api_url: None,
+            gist_public: false,
+            sync_backend: SyncBackend::default(),
+            gitlab_url: None,
             gist_id: None,
+            gist_url: None,
+            gitlab_snippet_id: None,
+            gitlab_snippet_url: None,
+            language_colors: HashMap::new(),
+            unexpanded_dirs: None,
+            file: None,
         };
         config.make_dirs().unwrap();
         config
@@ -72,25 +190,111 @@ impl Default for TheWayConfig {
 
 impl TheWayConfig {
     /// Write default configuration file
-    pub(crate) fn default_config(file: Option<&Path>) -> color_eyre::Result<()> {
+    pub(crate) fn default_config(file: Option<&Path>, force: bool) -> color_eyre::Result<()> {
         let writer: Box<dyn Write> = match file {
-            Some(file) => Box::new(fs::File::create(file)?),
-            None => Box::new(io::stdout()),
+            Some(file) => {
+                if file.exists() && !force {
+                    let error: color_eyre::Result<()> = Err(LostTheWay::DoingNothing.into());
+                    return error.suggestion(format!(
+                        "{} already exists, use --force to overwrite it",
+                        file.display()
+                    ));
+                }
+                Box::new(fs::File::create(file)?)
+            }
+            None => {
+                let default_file = Self::get_default_config_file()?;
+                if default_file.exists() && !force {
+                    Box::new(io::stdout())
+                } else {
+                    Self::write_default_config(&default_file)?;
+                    eprintln!(
+                        "Wrote default configuration to {}",
+                        default_file.to_string_lossy()
+                    );
+                    return Ok(());
+                }
+            }
         };
         let mut buffered = io::BufWriter::new(writer);
-        let copy_cmd = get_default_copy_cmd().ok_or(LostTheWay::NoDefaultCopyCommand)?;
-        let contents =
-            format!(
-                "theme = 'base16-ocean.dark'\ndb_dir = 'the_way_db'\nthemes_dir = 'the_way_themes'\ncopy_cmd = '{}'",
-                copy_cmd
-            );
-        write!(&mut buffered, "{contents}")?;
+        write!(
+            &mut buffered,
+            "{}",
+            Self::default_contents(Path::new("the_way_db"), Path::new("the_way_themes"))
+        )?;
+        buffered.flush()?;
+        Ok(())
+    }
+
+    /// Default configuration, commented for the user to fill in
+    fn default_contents(db_dir: &Path, themes_dir: &Path) -> String {
+        // Without a default for this OS, leave the example for the user to fill in
+        let copy_cmd = match get_default_copy_cmd() {
+            Some(copy_cmd) => format!("copy_cmd = '{copy_cmd}'"),
+            None => String::from("# copy_cmd = 'wl-copy'"),
+        };
+        format!(
+            "theme = 'base16-ocean.dark'\ndb_dir = '{}'\nthemes_dir = '{}'\n\
+            # Command that copies its stdin to the clipboard, e.g. 'wl-copy' on Wayland or 'pbcopy' on macOS\n\
+            {copy_cmd}",
+            db_dir.display(),
+            themes_dir.display()
+        )
+    }
+
+    /// Write the default configuration to the standard location,
+    /// with the standard data directories
+    fn write_default_config(default_file: &Path) -> color_eyre::Result<()> {
+        if let Some(config_dir) = default_file.parent() {
+            fs::create_dir_all(config_dir)?;
+        }
+        fs::write(
+            default_file,
+            Self::default_contents(&get_default_db_dir(), &get_default_themes_dir()),
+        )?;
+        Ok(())
+    }
+
+    /// ID of the Gist or GitLab snippet synced to, depending on `sync_backend`
+    pub(crate) fn synced_id(&self) -> Option<&str> {
+        match self.sync_backend {
+            SyncBackend::Github => self.gist_id.as_deref(),
+            SyncBackend::Gitlab => self.gitlab_snippet_id.as_deref(),
+        }
+    }
+
+    /// URL of the Gist or GitLab snippet synced to, depending on `sync_backend`
+    pub(crate) fn synced_url(&self) -> Option<&str> {
+        match self.sync_backend {
+            SyncBackend::Github => self.gist_url.as_deref(),
+            SyncBackend::Gitlab => self.gitlab_snippet_url.as_deref(),
+        }
+    }
+
+    /// Records the Gist or GitLab snippet synced to, depending on `sync_backend`.
+    /// The ID is kept if not given
+    pub(crate) fn set_synced(&mut self, id: Option<String>, url: String) {
+        let (synced_id, synced_url) = match self.sync_backend {
+            SyncBackend::Github => (&mut self.gist_id, &mut self.gist_url),
+            SyncBackend::Gitlab => (&mut self.gitlab_snippet_id, &mut self.gitlab_snippet_url),
+        };
+        if id.is_some() {
+            *synced_id = id;
+        }
+        *synced_url = Some(url);
+    }
+
+    /// Print the filename of the configuration file in use
+    pub(crate) fn print_config_location(&self) -> color_eyre::Result<()> {
+        println!("{}", self.get()?.to_string_lossy());
         Ok(())
     }
 
-    /// Print the filename of the currently set configuration file
-    pub(crate) fn print_config_location() -> color_eyre::Result<()> {
-        println!("{}", Self::get()?.to_string_lossy());
+    /// Print the configuration file and data directories in use
+    pub(crate) fn print_locations(&self) -> color_eyre::Result<()> {
+        println!("config file: {}", self.get()?.to_string_lossy());
+        println!("db dir: {}", self.db_dir.to_string_lossy());
+        println!("themes dir: {}", self.themes_dir.to_string_lossy());
         Ok(())
     }
 
@@ -117,68 +321,99 @@ impl TheWayConfig {
     }
 
     /// Gets the current config file location
-    fn get() -> color_eyre::Result<PathBuf> {
-        let config_file = env::var("THE_WAY_CONFIG").ok();
-        match config_file {
-            Some(file) => {
-                let path = Path::new(&file).to_owned();
-                if path.exists() {
-                    Ok(path)
-                } else {
-                    let error: color_eyre::Result<PathBuf> = Err(LostTheWay::ConfigError {
-                        message: format!("No such file {file}"),
-                    }
-                    .into());
-                    error.suggestion(format!(
-                        "Use `the-way config default {file}` to write out the default configuration",
-                    ))
-                }
-            }
+    fn get(&self) -> color_eyre::Result<PathBuf> {
+        match &self.file {
+            Some(file) => Ok(file.clone()),
             None => Self::get_default_config_file(),
         }
     }
 
-    /// Read config from default location
-    pub fn load() -> color_eyre::Result<Self> {
-        // Reads THE_WAY_CONFIG environment variable to get config file location
-        let config_file = env::var("THE_WAY_CONFIG").ok();
+    /// Read config from `config_file` (e.g. given with `--config`),
+    /// the THE_WAY_CONFIG environment variable or the default location, in that order
+    pub fn load(config_file: Option<&Path>) -> color_eyre::Result<Self> {
+        let config_file = config_file
+            .map(|file| file.to_string_lossy().into_owned())
+            .or_else(|| env::var("THE_WAY_CONFIG").ok());
         match config_file {
             Some(file) => {
                 let path = Path::new(&file).to_owned();
                 if path.exists() {
-                    let config: Self = confy::load_path(Path::new(&file))?;
-                    config.make_dirs()?;
-                    Ok(config)
+                    Self::load_from(&path)
                 } else {
                     let error: color_eyre::Result<Self> = Err(LostTheWay::ConfigError {
                         message: format!("No such file {file}"),
                     }
-                        .into());
+                    .into());
                     error.suggestion(format!(
                         "Use `the-way config default {file}` to write out the default configuration",
                     ))
                 }
             }
             None => {
-                Ok(confy::load(NAME, None).suggestion(LostTheWay::ConfigError {
-                    message: "Couldn't load from the default config location, maybe you don't have access? \
+                let default_file = Self::get_default_config_file()?;
+                if !default_file.exists() {
+                    log::debug!(
+                        "Writing the default configuration to {}",
+                        default_file.display()
+                    );
+                    Self::write_default_config(&default_file).suggestion(LostTheWay::ConfigError {
+                        message: "Couldn't write to the default config location, maybe you don't have access? \
                     Try running `the-way config default config_file.toml`, modify the generated file if necessary, \
                 then `export THE_WAY_CONFIG=<full/path/to/config_file.toml>`".into()
-                })?)
-            },
+                    })?;
+                }
+                Self::load_from(&default_file)
+            }
         }
     }
 
+    /// Read configuration from the given file, making data directories if needed
+    pub fn load_from(file: &Path) -> color_eyre::Result<Self> {
+        log::debug!("Loading configuration from {}", file.display());
+        let mut config = confy::load_path::<Self>(file)?.expand_dirs(file)?;
+        config.make_dirs()?;
+        config.file = Some(file.to_owned());
+        Ok(config)
+    }
+
+    /// Expand a leading `~` and `$HOME` or `$XDG_DATA_HOME` in the data directory paths.
+    /// A relative `db_dir` is resolved against the home directory and a relative `themes_dir`
+    /// against the directory of the configuration file, so themes can live next to it in dotfiles
+    fn expand_dirs(mut self, config_file: &Path) -> color_eyre::Result<Self> {
+        let base_dirs = BaseDirs::new().ok_or(LostTheWay::Homeless)?;
+        let config_dir = std::path::absolute(config_file)?
+            .parent()
+            .map_or_else(|| base_dirs.home_dir().to_owned(), Path::to_owned);
+        let db_dir = expand_path(&self.db_dir, &base_dirs, base_dirs.home_dir());
+        let themes_dir = expand_path(&self.themes_dir, &base_dirs, &config_dir);
+        if db_dir != self.db_dir || themes_dir != self.themes_dir {
+            log::debug!(
+                "Expanded data directories to {} and {}",
+                db_dir.display(),
+                themes_dir.display()
+            );
+            self.unexpanded_dirs = Some((
+                std::mem::replace(&mut self.db_dir, db_dir),
+                std::mem::replace(&mut self.themes_dir, themes_dir),
+            ));
+        }
+        Ok(self)
+    }
+
     /// Write possibly modified config
     pub(crate) fn store(&self) -> color_eyre::Result<()> {
-        // Reads THE_WAY_CONFIG environment variable to get config file location
-        let config_file = env::var("THE_WAY_CONFIG").ok();
-        match config_file {
-            Some(file) => confy::store_path(Path::new(&file), (*self).clone()).suggestion(LostTheWay::ConfigError {
+        // Keep data directories as the user wrote them
+        let mut config = self.clone();
+        if let Some((db_dir, themes_dir)) = config.unexpanded_dirs.take() {
+            config.db_dir = db_dir;
+            config.themes_dir = themes_dir;
+        }
+        match self.file.as_deref() {
+            Some(file) => confy::store_path(file, config).suggestion(LostTheWay::ConfigError {
                 message: "The current config_file location does not seem to have write access. \
                    Use `export THE_WAY_CONFIG=<full/path/to/config_file.toml>` to set a new location".into()
             })?,
-            None => confy::store(NAME, None, (*self).clone()).suggestion(LostTheWay::ConfigError {
+            None => confy::store(NAME, None, config).suggestion(LostTheWay::ConfigError {
                 message: "The current config_file location does not seem to have write access. \
                     Use `export THE_WAY_CONFIG=<full/path/to/config_file.toml>` to set a new location".into()
             })?,
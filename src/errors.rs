@@ -10,12 +10,24 @@ pub enum LostTheWay {
     /// Thrown when trying to access a nonexistent snippet index
     #[error("You haven't written that snippet: {index:?}.")]
     SnippetNotFound { index: usize },
+    /// Thrown when trying to move a snippet to an index that's already taken
+    #[error("You've already written a snippet at {index:?}.")]
+    SnippetExists { index: usize },
+    /// Thrown when no snippet has the given description
+    #[error("You haven't written a snippet described as {description:?}.")]
+    DescriptionNotFound { description: String },
+    /// Thrown when no snippet matches the given filters
+    #[error("You haven't written any snippets matching those filters.")]
+    NoMatchingSnippets,
     /// Thrown when trying to access an unrecorded tag
     #[error("You haven't tagged anything as {tag:?} yet.")]
     TagNotFound { tag: String },
-    /// Thrown when no text is returned from an external editor
-    #[error("EditorError")]
+    /// Thrown when the external editor is closed without saving
+    #[error("EditorError: Editor closed without saving, nothing changed")]
     EditorError,
+    /// Thrown when a snippet is saved without code
+    #[error("You can't record a snippet without code.")]
+    EmptyCode,
     /// Thrown when explicit Y not received from user for destructive things
     #[error("I'm a coward. Doing nothing.")]
     DoingNothing,
@@ -39,6 +51,22 @@ pub enum LostTheWay {
         Please add a supported command to your configuration file (as copy_cmd)"
     )]
     NoDefaultCopyCommand,
+    /// Thrown when a `--pattern` isn't a valid regular expression
+    #[error("InvalidPattern: {pattern:?} is not a valid regular expression\n{message}")]
+    InvalidPattern { pattern: String, message: String },
+    /// Thrown when a snippet's code is larger than the configured `max_code_bytes`
+    #[error("SnippetTooLarge: {description:?} has {size} bytes of code, more than max_code_bytes ({max})")]
+    SnippetTooLarge {
+        description: String,
+        size: usize,
+        max: usize,
+    },
+    /// Thrown when a stored snippet doesn't match its checksum
+    #[error("ChecksumMismatch: Snippet #{index} was corrupted or changed outside the-way")]
+    ChecksumMismatch { index: usize },
+    /// Thrown when a stored snippet isn't in a layout this version can read
+    #[error("SnippetLayoutError: {message:?}")]
+    SnippetLayoutError { message: String },
     /// Thrown when `skim` search fails
     #[error("SearchError: Search failed")]
     SearchError,
@@ -1,5 +1,7 @@
 //! Simple Gist API wrapper
 use std::collections::HashMap;
+use std::io::Read;
+use std::time::Duration;
 
 use chrono::{DateTime, Utc};
 use color_eyre::Help;
@@ -11,6 +13,11 @@ const GITHUB_API_URL: &str = "https://api.github.com";
 const GITHUB_BASE_PATH: &str = "";
 const ACCEPT: &str = "application/vnd.github.v3+json";
 const USER_AGENT: &str = "the-way";
+/// Times a request is sent before giving up on GitHub's rate limit
+const MAX_ATTEMPTS: usize = 3;
+/// Longest wait (in seconds) for GitHub's rate limit to reset before retrying a request,
+/// beyond this the user is told when to try again instead
+const MAX_RETRY_WAIT: u64 = 60;
 
 /// Expects URL like `https://gist.github.com/user/<gist_id>`
 /// or `https://gist.github.com/<gist_id>`
@@ -21,6 +28,39 @@ fn gist_id_from_url(gist_url: &str) -> color_eyre::Result<Option<&str>> {
         .and_then(|cap| cap.name("gist_id").map(|gist_id| gist_id.as_str())))
 }
 
+/// Seconds to wait before retrying a request GitHub refused because of a rate limit,
+/// from its Retry-After header or (when no requests are left) its X-RateLimit-Reset header.
+/// None if the response isn't a rate limit error
+pub fn rate_limit_wait(response: &ureq::Response, now: DateTime<Utc>) -> Option<u64> {
+    if !matches!(response.status(), 403 | 429) {
+        return None;
+    }
+    if let Some(retry_after) = response
+        .header("retry-after")
+        .and_then(|seconds| seconds.trim().parse().ok())
+    {
+        return Some(retry_after);
+    }
+    if response.header("x-ratelimit-remaining").map(str::trim) != Some("0") {
+        return None;
+    }
+    let reset = response
+        .header("x-ratelimit-reset")?
+        .trim()
+        .parse::<i64>()
+        .ok()?;
+    Some(u64::try_from(reset - now.timestamp()).unwrap_or(0))
+}
+
+/// Tells the user when they can try again after hitting GitHub's rate limit
+pub fn rate_limit_message(wait: u64, now: DateTime<Utc>) -> String {
+    let retry_at = now + chrono::Duration::seconds(i64::try_from(wait).unwrap_or(i64::MAX));
+    format!(
+        "GitHub API rate limit exceeded, try again in {wait} seconds (after {})",
+        retry_at.format("%Y-%m-%d %H:%M:%S UTC")
+    )
+}
+
 /// Gist code content
 #[derive(Serialize, Debug)]
 pub struct GistContent<'a> {
@@ -44,6 +84,7 @@ pub struct UpdateGistPayload<'a> {
 pub struct Gist {
     pub html_url: String,
     pub id: String,
+    pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub description: String,
     pub files: HashMap<String, GistFile>,
@@ -53,22 +94,66 @@ pub struct Gist {
 pub struct GistFile {
     pub content: String,
     pub language: String,
+    /// Set when the API cut `content` short (for files over ~1MB), the full file is at `raw_url`
+    #[serde(default)]
+    pub truncated: bool,
+    #[serde(default)]
+    pub raw_url: String,
+}
+
+impl Gist {
+    /// Replaces the content of truncated files with the full file fetched from its `raw_url`
+    pub fn fetch_truncated(
+        &mut self,
+        fetch: impl Fn(&str) -> color_eyre::Result<String>,
+    ) -> color_eyre::Result<()> {
+        for (file_name, file) in &mut self.files {
+            if file.truncated {
+                log::debug!("Fetching truncated file {file_name} from {}", file.raw_url);
+                file.content = fetch(&file.raw_url)?;
+                file.truncated = false;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A service snippets can be synced to, working with Gist payloads and responses
+pub trait SnippetSyncBackend {
+    /// Create a new snippet collection with the given files
+    fn create(&self, payload: &CreateGistPayload<'_>) -> color_eyre::Result<Gist>;
+    /// Add, change or (with `None` content) delete files in an existing collection
+    fn update(&self, id: &str, payload: &UpdateGistPayload<'_>) -> color_eyre::Result<Gist>;
+    /// Retrieve a collection and the contents of its files by ID
+    fn get(&self, id: &str) -> color_eyre::Result<Gist>;
+    /// Delete a collection by ID
+    fn delete(&self, id: &str) -> color_eyre::Result<()>;
 }
 
 pub struct GistClient<'a> {
     client: ureq::Agent,
+    api_url: String,
     access_token: Option<&'a str>,
 }
 
 impl<'a> GistClient<'a> {
-    /// Create a new Gist client
-    pub fn new(access_token: Option<&'a str>) -> color_eyre::Result<Self> {
+    /// Create a new Gist client, for api.github.com unless a GitHub Enterprise API URL is given
+    pub fn new(api_url: Option<&str>, access_token: Option<&'a str>) -> color_eyre::Result<Self> {
         Ok(Self {
             client: ureq::agent(),
+            api_url: api_url
+                .unwrap_or(GITHUB_API_URL)
+                .trim_end_matches('/')
+                .to_owned(),
             access_token,
         })
     }
 
+    /// API URL creating Gists, and under which each Gist is found by ID
+    pub fn gists_url(&self) -> String {
+        format!("{}{GITHUB_BASE_PATH}/gists", self.api_url)
+    }
+
     fn add_headers(&self, request: ureq::Request) -> ureq::Request {
         let mut request = request
             .set("user-agent", USER_AGENT)
@@ -107,13 +192,48 @@ impl<'a> GistClient<'a> {
         }
     }
 
+    /// Sends a request (with a JSON payload if given), retrying it (up to MAX_ATTEMPTS times in all)
+    /// if GitHub's rate limit resets within MAX_RETRY_WAIT seconds.
+    /// Fails with when to try again if it takes longer
+    fn send(
+        request: impl Fn() -> ureq::Request,
+        payload: Option<&serde_json::Value>,
+    ) -> color_eyre::Result<Gist> {
+        let mut attempt = 1;
+        loop {
+            let response = match payload {
+                Some(payload) => request().send_json(payload),
+                None => request().call(),
+            };
+            if let Err(ureq::Error::Status(_, error_response)) = &response {
+                match rate_limit_wait(error_response, Utc::now()) {
+                    Some(wait) if wait <= MAX_RETRY_WAIT && attempt < MAX_ATTEMPTS => {
+                        log::warn!("Hit the GitHub API rate limit, retrying in {wait} seconds");
+                        std::thread::sleep(Duration::from_secs(wait.max(1)));
+                        attempt += 1;
+                        continue;
+                    }
+                    Some(wait) => {
+                        return Err(LostTheWay::SyncError {
+                            message: rate_limit_message(wait, Utc::now()),
+                        }
+                        .into());
+                    }
+                    None => (),
+                }
+            }
+            return Self::get_response(response);
+        }
+    }
+
     /// Create a new Gist with the given payload
     pub fn create_gist(&self, payload: &CreateGistPayload<'_>) -> color_eyre::Result<Gist> {
-        let url = format!("{GITHUB_API_URL}{GITHUB_BASE_PATH}/gists");
-        let response = self
-            .add_headers(self.client.post(&url))
-            .send_json(serde_json::to_value(payload)?);
-        Self::get_response(response)
+        let url = self.gists_url();
+        log::debug!("Creating Gist with {} files", payload.files.len());
+        Self::send(
+            || self.add_headers(self.client.post(&url)),
+            Some(&serde_json::to_value(payload)?),
+        )
     }
 
     /// Update an existing Gist
@@ -122,18 +242,37 @@ impl<'a> GistClient<'a> {
         gist_id: &str,
         payload: &UpdateGistPayload<'_>,
     ) -> color_eyre::Result<Gist> {
-        let url = format!("{GITHUB_API_URL}{GITHUB_BASE_PATH}/gists");
-        let response = self
-            .add_headers(self.client.request("PATCH", &format!("{url}/{gist_id}")))
-            .send_json(serde_json::to_value(payload)?);
-        Self::get_response(response)
+        let url = self.gists_url();
+        log::debug!("Updating {} files in Gist {gist_id}", payload.files.len());
+        Self::send(
+            || self.add_headers(self.client.request("PATCH", &format!("{url}/{gist_id}"))),
+            Some(&serde_json::to_value(payload)?),
+        )
     }
 
     /// Retrieve a Gist by ID
     pub fn get_gist(&self, gist_id: &str) -> color_eyre::Result<Gist> {
-        let url = format!("{GITHUB_API_URL}{GITHUB_BASE_PATH}/gists");
-        let response = self.add_headers(self.client.get(&format!("{url}/{gist_id}")));
-        Self::get_response(response.call())
+        let url = self.gists_url();
+        log::debug!("Fetching Gist {gist_id}");
+        let mut gist = Self::send(
+            || self.add_headers(self.client.get(&format!("{url}/{gist_id}"))),
+            None,
+        )?;
+        gist.fetch_truncated(|raw_url| self.get_raw_file(raw_url))?;
+        Ok(gist)
+    }
+
+    /// Retrieve the full contents of a Gist file from its raw URL
+    fn get_raw_file(&self, raw_url: &str) -> color_eyre::Result<String> {
+        let response = self
+            .add_headers(self.client.get(raw_url))
+            .call()
+            .map_err(|e| LostTheWay::SyncError {
+                message: format!("Couldn't fetch {raw_url}: {e}"),
+            })?;
+        let mut content = String::new();
+        response.into_reader().read_to_string(&mut content)?;
+        Ok(content)
     }
 
     /// Retrieve a Gist by URL
@@ -150,7 +289,8 @@ impl<'a> GistClient<'a> {
 
     /// Delete Gist by ID
     pub fn delete_gist(&self, gist_id: &str) -> color_eyre::Result<()> {
-        let url = format!("{GITHUB_API_URL}{GITHUB_BASE_PATH}/gists");
+        let url = self.gists_url();
+        log::debug!("Deleting Gist {gist_id}");
         let status = self.add_headers(self.client.delete(&format!("{url}/{gist_id}")));
         if status.call().is_err() {
             Err(LostTheWay::GistUrlError {
@@ -162,3 +302,21 @@ impl<'a> GistClient<'a> {
         }
     }
 }
+
+impl SnippetSyncBackend for GistClient<'_> {
+    fn create(&self, payload: &CreateGistPayload<'_>) -> color_eyre::Result<Gist> {
+        self.create_gist(payload)
+    }
+
+    fn update(&self, id: &str, payload: &UpdateGistPayload<'_>) -> color_eyre::Result<Gist> {
+        self.update_gist(id, payload)
+    }
+
+    fn get(&self, id: &str) -> color_eyre::Result<Gist> {
+        self.get_gist(id)
+    }
+
+    fn delete(&self, id: &str) -> color_eyre::Result<()> {
+        self.delete_gist(id)
+    }
+}
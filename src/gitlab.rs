@@ -0,0 +1,258 @@
+//! Simple GitLab snippets API wrapper, speaking in the Gist types used for sync
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Utc};
+use color_eyre::Help;
+
+use crate::errors::LostTheWay;
+use crate::gist::{CreateGistPayload, Gist, GistFile, SnippetSyncBackend, UpdateGistPayload};
+
+pub const GITLAB_URL: &str = "https://gitlab.com";
+const USER_AGENT: &str = "the-way";
+
+/// Percent-encodes everything but unreserved characters, for file paths and refs in URLs
+fn encode_path_segment(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+/// A file in a GitLab snippet, its content is fetched separately
+#[derive(Deserialize, Debug)]
+struct GitLabFile {
+    path: String,
+    raw_url: String,
+}
+
+impl GitLabFile {
+    /// Git ref the file's raw URL (like .../-/snippets/<id>/raw/<ref>/<path>) points to
+    fn git_ref(&self) -> &str {
+        self.raw_url
+            .split_once("/raw/")
+            .and_then(|(_, rest)| rest.split('/').next())
+            .unwrap_or("main")
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct GitLabSnippet {
+    id: u64,
+    title: String,
+    web_url: String,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+    #[serde(default)]
+    files: Vec<GitLabFile>,
+}
+
+/// Body for creating a GitLab snippet from a Gist payload
+pub fn create_payload(payload: &CreateGistPayload<'_>) -> serde_json::Value {
+    let mut files = payload
+        .files
+        .iter()
+        .map(|(file_path, content)| (file_path.as_str(), content.content))
+        .collect::<Vec<_>>();
+    files.sort_unstable();
+    serde_json::json!({
+        "title": payload.description,
+        "description": payload.description,
+        "visibility": if payload.public { "public" } else { "private" },
+        "files": files
+            .into_iter()
+            .map(|(file_path, content)| serde_json::json!({"file_path": file_path, "content": content}))
+            .collect::<Vec<_>>(),
+    })
+}
+
+/// Body for updating a GitLab snippet from a Gist payload.
+/// GitLab needs to know whether each file is created or updated,
+/// so this takes the paths of the files the snippet already has. Deleting a file it doesn't have is left out
+pub fn update_payload(
+    payload: &UpdateGistPayload<'_>,
+    existing_paths: &HashSet<String>,
+) -> serde_json::Value {
+    let mut files = payload.files.iter().collect::<Vec<_>>();
+    files.sort_unstable_by_key(|(file_path, _)| file_path.as_str());
+    serde_json::json!({
+        "title": payload.description,
+        "description": payload.description,
+        "files": files
+            .into_iter()
+            .filter_map(|(file_path, content)| {
+                let exists = existing_paths.contains(file_path);
+                match content {
+                    Some(content) => Some(serde_json::json!({
+                        "action": if exists { "update" } else { "create" },
+                        "file_path": file_path,
+                        "content": content.content,
+                    })),
+                    None if exists => {
+                        Some(serde_json::json!({"action": "delete", "file_path": file_path}))
+                    }
+                    None => None,
+                }
+            })
+            .collect::<Vec<_>>(),
+    })
+}
+
+pub struct GitLabClient<'a> {
+    client: ureq::Agent,
+    base_url: String,
+    access_token: &'a str,
+}
+
+impl<'a> GitLabClient<'a> {
+    /// Create a new GitLab client, for gitlab.com unless a self-hosted instance's URL is given
+    pub fn new(base_url: Option<&str>, access_token: &'a str) -> Self {
+        Self {
+            client: ureq::agent(),
+            base_url: base_url
+                .unwrap_or(GITLAB_URL)
+                .trim_end_matches('/')
+                .to_owned(),
+            access_token,
+        }
+    }
+
+    /// API URL listing and creating snippets
+    pub fn snippets_url(&self) -> String {
+        format!("{}/api/v4/snippets", self.base_url)
+    }
+
+    /// API URL of a snippet
+    pub fn snippet_url(&self, id: &str) -> String {
+        format!("{}/{id}", self.snippets_url())
+    }
+
+    /// API URL of the raw content of a snippet's file at a git ref
+    pub fn raw_file_url(&self, id: &str, git_ref: &str, file_path: &str) -> String {
+        format!(
+            "{}/files/{}/{}/raw",
+            self.snippet_url(id),
+            encode_path_segment(git_ref),
+            encode_path_segment(file_path)
+        )
+    }
+
+    fn add_headers(&self, request: ureq::Request) -> ureq::Request {
+        request
+            .set("user-agent", USER_AGENT)
+            .set("PRIVATE-TOKEN", self.access_token)
+    }
+
+    fn check_response(
+        &self,
+        response: Result<ureq::Response, ureq::Error>,
+    ) -> color_eyre::Result<ureq::Response> {
+        let suggestion = format!(
+            "Make sure your GitLab access token is valid.\n\
+        Get one from {}/-/user_settings/personal_access_tokens (add the \"api\" scope).\n\
+        Set it to the environment variable $THE_WAY_GITLAB_TOKEN",
+            self.base_url
+        );
+        match response {
+            Ok(response) => Ok(response),
+            Err(ureq::Error::Status(code, response)) => Err(LostTheWay::SyncError {
+                message: format!("{code} {}", response.into_string()?),
+            })
+            .suggestion(suggestion),
+            Err(_) => Err(LostTheWay::SyncError {
+                message: "io/transport error".into(),
+            })
+            .suggestion(suggestion),
+        }
+    }
+
+    fn get_snippet(&self, id: &str) -> color_eyre::Result<GitLabSnippet> {
+        let response = self.add_headers(self.client.get(&self.snippet_url(id)));
+        Ok(self.check_response(response.call())?.into_json()?)
+    }
+
+    /// Converts a GitLab snippet to a Gist, file contents are only fetched if `with_contents` is set.
+    /// The snippets API doesn't return contents, so that's one request per file
+    /// (over the agent's kept-alive connection), which makes syncs slower as the snippet grows
+    fn to_gist(&self, snippet: GitLabSnippet, with_contents: bool) -> color_eyre::Result<Gist> {
+        let id = snippet.id.to_string();
+        let mut files = HashMap::with_capacity(snippet.files.len());
+        for file in snippet.files {
+            let content = if with_contents {
+                let url = self.raw_file_url(&id, file.git_ref(), &file.path);
+                let response = self.add_headers(self.client.get(&url));
+                self.check_response(response.call())?.into_string()?
+            } else {
+                String::new()
+            };
+            files.insert(
+                file.path,
+                GistFile {
+                    content,
+                    language: String::new(),
+                    truncated: false,
+                    raw_url: String::new(),
+                },
+            );
+        }
+        Ok(Gist {
+            html_url: snippet.web_url,
+            id,
+            created_at: snippet.created_at,
+            updated_at: snippet.updated_at,
+            description: snippet.title,
+            files,
+        })
+    }
+}
+
+/// File contents are only fetched by `get`, the others return just the file names
+impl SnippetSyncBackend for GitLabClient<'_> {
+    fn create(&self, payload: &CreateGistPayload<'_>) -> color_eyre::Result<Gist> {
+        log::debug!("Creating GitLab snippet with {} files", payload.files.len());
+        let response = self
+            .add_headers(self.client.post(&self.snippets_url()))
+            .send_json(create_payload(payload));
+        let snippet = self.check_response(response)?.into_json()?;
+        self.to_gist(snippet, false)
+    }
+
+    fn update(&self, id: &str, payload: &UpdateGistPayload<'_>) -> color_eyre::Result<Gist> {
+        log::debug!(
+            "Updating {} files in GitLab snippet {id}",
+            payload.files.len()
+        );
+        let existing_paths = self
+            .get_snippet(id)?
+            .files
+            .into_iter()
+            .map(|file| file.path)
+            .collect();
+        let response = self
+            .add_headers(self.client.put(&self.snippet_url(id)))
+            .send_json(update_payload(payload, &existing_paths));
+        let snippet = self.check_response(response)?.into_json()?;
+        self.to_gist(snippet, false)
+    }
+
+    fn get(&self, id: &str) -> color_eyre::Result<Gist> {
+        log::debug!("Fetching GitLab snippet {id}");
+        let snippet = self.get_snippet(id)?;
+        log::debug!(
+            "Fetching {} files of GitLab snippet {id}",
+            snippet.files.len()
+        );
+        self.to_gist(snippet, true)
+    }
+
+    fn delete(&self, id: &str) -> color_eyre::Result<()> {
+        log::debug!("Deleting GitLab snippet {id}");
+        let response = self.add_headers(self.client.delete(&self.snippet_url(id)));
+        self.check_response(response.call())?;
+        Ok(())
+    }
+}
@@ -8,7 +8,8 @@ use hex::FromHex;
 use serde_yaml::Value;
 use syntect::easy::HighlightLines;
 use syntect::highlighting::{Color, FontStyle, Style, StyleModifier, ThemeSet, ThemeSettings};
-use syntect::parsing::{SyntaxDefinition, SyntaxSet};
+use syntect::html::highlighted_html_for_string;
+use syntect::parsing::{SyntaxDefinition, SyntaxReference, SyntaxSet};
 use syntect::util::LinesWithEndings;
 
 use crate::errors::LostTheWay;
@@ -48,10 +49,19 @@ impl Language {
     }
 
     fn get_color(color_string: Option<String>) -> color_eyre::Result<Color> {
-        let mut language_color = [0; 3];
-        if let Some(color) = color_string {
-            language_color = <[u8; 3]>::from_hex(color.get(1..).unwrap_or("FFFFFF"))?;
+        match color_string {
+            Some(color) => Self::parse_color(&color),
+            None => Self::parse_color("000000"),
         }
+    }
+
+    /// Parses a hex color like "#dea584" (the "#" is optional)
+    pub(crate) fn parse_color(color: &str) -> color_eyre::Result<Color> {
+        let language_color = <[u8; 3]>::from_hex(color.trim_start_matches('#'))
+            .map_err(|e| LostTheWay::ConfigError {
+                message: format!("Invalid color {color:?}: {e}"),
+            })
+            .suggestion("Use a hex color like #dea584")?;
         Ok(Color {
             r: language_color[0],
             g: language_color[1],
@@ -66,7 +76,7 @@ impl Language {
         if let Some(l) = languages.get(language_name) {
             l.extension.clone()
         } else {
-            eprintln!(
+            log::info!(
                 "Couldn't find language {} in the list of extensions, defaulting to .txt",
                 language_name
             );
@@ -86,6 +96,33 @@ fn read_languages_from_yml(yml_string: &str) -> color_eyre::Result<HashMap<Strin
     Ok(languages)
 }
 
+/// Overrides the colors of languages (by name or alias, ignoring case) with hex colors.
+/// Languages not in languages.yml are added as plain text with the given color
+pub(crate) fn set_language_colors(
+    languages: &mut HashMap<String, Language>,
+    colors: &HashMap<String, String>,
+) -> color_eyre::Result<()> {
+    for (name, color) in colors {
+        let color = Language::parse_color(color)?;
+        let mut found = false;
+        for (key, language) in languages.iter_mut() {
+            if key.eq_ignore_ascii_case(name) {
+                language.color = color;
+                found = true;
+            }
+        }
+        if !found {
+            let language = Language {
+                name: name.clone(),
+                color,
+                ..Language::default()
+            };
+            languages.insert(name.clone(), language);
+        }
+    }
+    Ok(())
+}
+
 /// Loads language extension and color information for each language and its aliases
 pub fn get_languages(yml_string: &str) -> color_eyre::Result<HashMap<String, Language>> {
     let languages = read_languages_from_yml(yml_string)?;
@@ -104,6 +141,24 @@ pub fn get_languages(yml_string: &str) -> color_eyre::Result<HashMap<String, Lan
     Ok(name_to_language)
 }
 
+/// Line comment start and end (empty if comments end with the line) for a language, `#` if unknown
+pub(crate) fn comment_delimiters(language: &str) -> (&'static str, &'static str) {
+    match language.to_ascii_lowercase().as_str() {
+        "c" | "c++" | "cpp" | "c#" | "csharp" | "rust" | "go" | "java" | "javascript" | "js"
+        | "typescript" | "ts" | "kotlin" | "swift" | "scala" | "dart" | "php" | "groovy"
+        | "objective-c" | "zig" | "jsonc" => ("//", ""),
+        "sql" | "lua" | "haskell" | "elm" | "ada" => ("--", ""),
+        "lisp" | "common lisp" | "clojure" | "scheme" | "racket" | "emacs lisp" | "elisp"
+        | "assembly" | "asm" | "ini" => (";", ""),
+        "tex" | "latex" | "matlab" | "erlang" | "prolog" => ("%", ""),
+        "vim script" | "vim" | "viml" => ("\"", ""),
+        "html" | "xml" | "markdown" | "md" | "svg" | "vue" => ("<!--", " -->"),
+        "css" => ("/*", " */"),
+        "ocaml" | "f#" | "fsharp" | "pascal" => ("(*", " *)"),
+        _ => ("#", ""),
+    }
+}
+
 pub(crate) struct CodeHighlight {
     syntax_set: SyntaxSet,
     theme_set: ThemeSet,
@@ -269,6 +324,19 @@ impl CodeHighlight {
         self.theme_set.themes.keys().cloned().collect()
     }
 
+    /// Gets names of languages with syntax highlighting support, sorted
+    pub(crate) fn get_syntaxes(&self) -> Vec<String> {
+        let mut syntaxes = self
+            .syntax_set
+            .syntaxes()
+            .iter()
+            .map(|syntax| syntax.name.clone())
+            .collect::<Vec<_>>();
+        syntaxes.sort();
+        syntaxes.dedup();
+        syntaxes
+    }
+
     /// Gets current theme name
     pub(crate) fn get_theme_name(&self) -> String {
         self.theme_name.clone()
@@ -342,6 +410,57 @@ impl CodeHighlight {
         )
     }
 
+    /// Checks if a language (or its extension) can be syntax highlighted.
+    /// Plain text counts as highlighted.
+    pub(crate) fn has_syntax(&self, language: &str, extension: &str) -> bool {
+        let extension = extension.split('.').nth(1).unwrap_or("txt");
+        matches!(language, "text" | "txt")
+            || (extension != "txt"
+                && self
+                    .syntax_set
+                    .find_syntax_by_extension(extension)
+                    .is_some())
+            || self.syntax_set.find_syntax_by_token(language).is_some()
+    }
+
+    /// Guesses a code block's language from its first line (shebangs, `<?php`, `<?xml` etc.)
+    pub(crate) fn detect_language(
+        &self,
+        code: &str,
+        languages: &HashMap<String, Language>,
+    ) -> Option<String> {
+        let syntax = self
+            .syntax_set
+            .find_syntax_by_first_line(code.lines().next()?)?;
+        Self::language_of_syntax(syntax, languages)
+    }
+
+    /// Guesses a file's language from its extension (without the dot), e.g. "rust" for "rs"
+    pub(crate) fn language_from_extension(
+        &self,
+        extension: &str,
+        languages: &HashMap<String, Language>,
+    ) -> Option<String> {
+        let syntax = self.syntax_set.find_syntax_by_extension(extension)?;
+        Self::language_of_syntax(syntax, languages)
+    }
+
+    /// Name of a known language matching a syntax, by syntax name or one of its extensions
+    fn language_of_syntax(
+        syntax: &SyntaxReference,
+        languages: &HashMap<String, Language>,
+    ) -> Option<String> {
+        let name = syntax.name.to_ascii_lowercase();
+        if languages.contains_key(&name) {
+            return Some(name);
+        }
+        syntax
+            .file_extensions
+            .iter()
+            .find(|extension| languages.contains_key(*extension))
+            .cloned()
+    }
+
     /// Syntax highlight code block
     pub(crate) fn highlight_code(
         &self,
@@ -349,20 +468,50 @@ impl CodeHighlight {
         extension: &str,
     ) -> color_eyre::Result<Vec<(Style, String)>> {
         let mut colorized = Vec::new();
+        self.highlight_code_lines(code, extension, |line| {
+            colorized.extend(line.iter().map(|(style, s)| (*style, (*s).to_owned())));
+            Ok(())
+        })?;
+        Ok(colorized)
+    }
+
+    /// Syntax highlight code block one line at a time, handing each highlighted line to `on_line`
+    pub(crate) fn highlight_code_lines(
+        &self,
+        code: &str,
+        extension: &str,
+        mut on_line: impl FnMut(&[(Style, &str)]) -> color_eyre::Result<()>,
+    ) -> color_eyre::Result<()> {
+        let mut h = HighlightLines::new(
+            self.syntax_for_extension(extension),
+            &self.theme_set.themes[&self.theme_name],
+        );
+        for line in LinesWithEndings::from(code) {
+            on_line(&h.highlight_line(line, &self.syntax_set)?)?;
+        }
+        Ok(())
+    }
+
+    /// Syntax highlight code block as an HTML `<pre>` element, styled inline with the current theme
+    pub(crate) fn highlight_code_html(
+        &self,
+        code: &str,
+        extension: &str,
+    ) -> color_eyre::Result<String> {
+        Ok(highlighted_html_for_string(
+            code,
+            &self.syntax_set,
+            self.syntax_for_extension(extension),
+            &self.theme_set.themes[&self.theme_name],
+        )?)
+    }
+
+    /// Syntax for a file extension (with the dot), plain text if there isn't one
+    fn syntax_for_extension(&self, extension: &str) -> &SyntaxReference {
         let extension = extension.split('.').nth(1).unwrap_or("txt");
-        let syntax = self.syntax_set.find_syntax_by_extension(extension);
-        let syntax = match syntax {
+        match self.syntax_set.find_syntax_by_extension(extension) {
             Some(syntax) => syntax,
             None => self.syntax_set.find_syntax_by_extension("txt").unwrap(),
-        };
-        let mut h = HighlightLines::new(syntax, &self.theme_set.themes[&self.theme_name]);
-        for line in LinesWithEndings::from(code) {
-            colorized.extend(
-                h.highlight_line(line, &self.syntax_set)?
-                    .into_iter()
-                    .map(|(style, s)| (style, s.to_owned())),
-            );
         }
-        Ok(colorized)
     }
 }
@@ -4,6 +4,8 @@ extern crate serde_derive;
 pub mod configuration;
 mod errors;
 pub mod gist;
+pub mod gitlab;
 pub mod language;
 pub mod the_way;
+pub mod token_store;
 mod utils;
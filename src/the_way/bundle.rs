@@ -0,0 +1,110 @@
+//! Code related to bundling snippets, themes, and configuration into a single archive
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Component, Path};
+
+use chrono::Utc;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use crate::configuration::TheWayConfig;
+use crate::language::CodeHighlight;
+use crate::the_way::{filter::Filters, TheWay};
+
+/// Name of the snippets file inside a bundle
+const BUNDLE_SNIPPETS: &str = "snippets.json";
+/// Name of the configuration file inside a bundle
+const BUNDLE_CONFIG: &str = "the-way.toml";
+/// Directory inside a bundle holding theme and syntax files
+const BUNDLE_THEMES: &str = "themes";
+
+/// Adds a file with the given contents to a tar archive
+fn append_bytes<W: Write>(
+    archive: &mut tar::Builder<W>,
+    path: &str,
+    contents: &[u8],
+) -> color_eyre::Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(contents.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(Utc::now().timestamp() as u64);
+    header.set_cksum();
+    archive.append_data(&mut header, path, contents)?;
+    Ok(())
+}
+
+impl TheWay {
+    /// Writes (optionally filtered) snippets, the themes directory,
+    /// and the configuration (without the GitHub access token) to a gzipped tarball
+    pub(crate) fn export_bundle(&self, filters: &Filters, bundle: &Path) -> color_eyre::Result<()> {
+        let mut snippets = Vec::new();
+        self.export_to_writer(filters, &mut snippets)?;
+        let mut config = self.config.clone();
+        config.github_access_token = None;
+        let config = toml::to_string(&config)?;
+
+        let encoder = GzEncoder::new(fs::File::create(bundle)?, Compression::default());
+        let mut archive = tar::Builder::new(encoder);
+        append_bytes(&mut archive, BUNDLE_SNIPPETS, &snippets)?;
+        append_bytes(&mut archive, BUNDLE_CONFIG, config.as_bytes())?;
+        archive.append_dir_all(BUNDLE_THEMES, &self.config.themes_dir)?;
+        archive.into_inner()?.finish()?;
+        Ok(())
+    }
+
+    /// Restores snippets, themes, and configuration from a bundle made by `export --bundle`.
+    /// Snippets are appended to existing ones. The data locations and access token of the
+    /// current configuration are kept. Returns the number of imported snippets.
+    pub(crate) fn import_bundle(
+        &mut self,
+        bundle: &Path,
+        force: bool,
+    ) -> color_eyre::Result<usize> {
+        let mut archive = tar::Archive::new(GzDecoder::new(fs::File::open(bundle)?));
+        let mut snippets = Vec::new();
+        let mut bundled_config = None;
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+            let path = entry.path()?.into_owned();
+            if path == Path::new(BUNDLE_SNIPPETS) {
+                snippets = self.read_snippets(&mut entry)?;
+            } else if path == Path::new(BUNDLE_CONFIG) {
+                let mut contents = String::new();
+                entry.read_to_string(&mut contents)?;
+                bundled_config = Some(toml::from_str::<TheWayConfig>(&contents)?);
+            } else if let Ok(theme_file) = path.strip_prefix(BUNDLE_THEMES) {
+                // Don't let entries escape the themes directory
+                if theme_file
+                    .components()
+                    .all(|c| matches!(c, Component::Normal(_)))
+                {
+                    let target = self.config.themes_dir.join(theme_file);
+                    if let Some(parent) = target.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    entry.unpack(target)?;
+                }
+            }
+        }
+        let num = self.add_imported_snippets(snippets, force)?;
+        if let Some(bundled_config) = bundled_config {
+            // Reload highlighter to pick up restored themes and syntaxes
+            self.highlighter =
+                CodeHighlight::new(&self.config.theme, self.config.themes_dir.clone())?;
+            self.highlighter.set_theme(bundled_config.theme.clone())?;
+            self.config.theme = bundled_config.theme;
+            self.config.copy_cmd = bundled_config.copy_cmd;
+            self.config.search_preview_cmd = bundled_config.search_preview_cmd;
+            self.config.gist_id = bundled_config.gist_id;
+            self.config.gist_url = bundled_config.gist_url;
+            self.config.gitlab_snippet_id = bundled_config.gitlab_snippet_id;
+            self.config.gitlab_snippet_url = bundled_config.gitlab_snippet_url;
+            self.config.store()?;
+        }
+        Ok(num)
+    }
+}
@@ -1,101 +1,274 @@
 //! `Clap` data
 use std::path::PathBuf;
 
-use clap::Parser;
+use clap::{ArgAction, Parser, ValueEnum};
 use clap_complete::Shell;
 
 use crate::configuration::ConfigCommand;
 use crate::the_way::filter::Filters;
+use crate::utils;
+
+/// Crate version followed by the git commit and date it was built from
+pub const VERSION: &str = concat!(
+    env!("CARGO_PKG_VERSION"),
+    " (",
+    env!("GIT_HASH"),
+    " ",
+    env!("BUILD_DATE"),
+    ")"
+);
 
 #[derive(Debug, Parser)]
-#[command(name = "the-way", author, version, about, long_about)]
+#[command(name = "the-way", author, version = VERSION, about, long_about)]
 /// A code snippets manager for your terminal
 ///
 /// Record, retrieve, search, and categorize code snippets
 pub struct TheWayCLI {
     /// Force colorization even when not in TTY mode
-    #[clap(short, long)]
+    #[clap(short, long, global = true)]
     pub colorize: bool,
-    /// Turn off colorization
-    #[clap(short, long, conflicts_with = "colorize")]
+    /// Turn off colorization (also -p, before the subcommand)
+    #[clap(long, conflicts_with = "colorize", global = true)]
     pub plain: bool,
+    /// -p for --plain, only before the subcommand since subcommands use -p for --pattern
+    #[clap(short = 'p', hide = true, conflicts_with = "colorize")]
+    pub plain_short: bool,
+    /// Don't shorten long snippet descriptions to fit the terminal width
+    #[clap(long, global = true)]
+    pub no_truncate: bool,
+    /// Configuration file to use, overriding $THE_WAY_CONFIG
+    #[clap(long, global = true, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+    /// Fail instead of warning on snippets that don't match their stored checksum
+    #[clap(long, global = true)]
+    pub strict_checksums: bool,
+    /// Log diagnostics to stderr (-v for info, -vv for debug, -vvv for trace).
+    /// With `config get`, also print the data locations
+    #[clap(short, long, action = ArgAction::Count, global = true)]
+    pub verbose: u8,
     #[clap(subcommand)]
     pub cmd: TheWaySubcommand,
 }
 
+impl TheWayCLI {
+    /// Log level corresponding to the number of -v flags, only warnings by default
+    pub fn log_level(&self) -> log::LevelFilter {
+        match self.verbose {
+            0 => log::LevelFilter::Warn,
+            1 => log::LevelFilter::Info,
+            2 => log::LevelFilter::Debug,
+            _ => log::LevelFilter::Trace,
+        }
+    }
+}
+
 #[derive(Debug, Parser)]
 pub enum TheWaySubcommand {
     /// Add a new code snippet
-    New,
+    #[clap(alias = "n")]
+    New {
+        /// Description, skips the description prompt
+        #[clap(short, long)]
+        description: Option<String>,
+        /// Language, skips the language prompt
+        #[clap(short, long)]
+        language: Option<String>,
+        /// Tags to attach, skips the tag prompt (multiple with 'tag1 tag2' or repeated flags)
+        #[clap(short, long)]
+        tags: Option<Vec<String>>,
+        /// Choose from existing tags in a multi-select, then add new ones (also set by `pick_tags` in the configuration file)
+        #[clap(long, conflicts_with = "tags")]
+        pick_tags: bool,
+        /// Code, skips the code prompt and editor
+        #[clap(long)]
+        code: Option<String>,
+        /// File to read the code from, skips the code prompt and editor
+        #[clap(long, conflicts_with = "code")]
+        file: Option<PathBuf>,
+        /// Never sync the snippet to the Gist (change this with `the-way private <index>`)
+        #[clap(long)]
+        private: bool,
+    },
     /// Add a new shell snippet
     Cmd {
         /// shell snippet code
         code: Option<String>,
+        /// Description, skips the description prompt
+        #[clap(short, long)]
+        description: Option<String>,
+        /// Tags to attach, skips the tag prompt (multiple with 'tag1 tag2' or repeated flags).
+        /// With a description and code too, nothing is prompted for
+        #[clap(short, long)]
+        tags: Option<Vec<String>>,
     },
     /// Search to find a snippet and copy, edit or delete it
+    #[clap(alias = "s")]
     Search {
         #[clap(flatten)]
         filters: Filters,
         /// Use exact search instead of fuzzy
         #[clap(long, short)]
         exact: bool,
+        /// Use fuzzy search, overriding `search_exact` in the configuration file
+        #[clap(long, conflicts_with = "exact")]
+        fuzzy: bool,
         /// Print to stdout instead of copying (with Enter)
         #[clap(long, short)]
         stdout: bool,
         /// Don't ask for confirmation when deleting
         #[clap(long, short)]
         force: bool,
+        /// Only copy or print snippets, leaving out the delete and edit keys
+        #[clap(long, conflicts_with = "force")]
+        read_only: bool,
     },
     /// Sync snippets to a Gist
     ///
     /// Controlled by $THE_WAY_GITHUB_TOKEN env variable.
     /// Set this to an access token with the "gist" scope obtained from https://github.com/settings/tokens/new
+    ///
+    /// With `sync_backend = "gitlab"` in the configuration file, syncs to a GitLab snippet instead,
+    /// using an access token with the "api" scope from $THE_WAY_GITLAB_TOKEN
     Sync {
         #[clap(subcommand)]
-        cmd: SyncCommand,
-        /// Don't ask for confirmation before deleting local snippets
+        cmd: Option<SyncCommand>,
+        /// Don't ask for confirmation before deleting local or Gist snippets,
+        /// or before `sync local` with far fewer local snippets than the Gist has
         #[clap(long, short)]
         force: bool,
+        /// Open the Gist in a browser after syncing
+        #[clap(long, short)]
+        open: bool,
+        /// Show what syncing would upload, download and delete, without changing anything
+        #[clap(long, conflicts_with_all = ["open", "adopt"])]
+        dry_run: bool,
+        /// Sync to an existing Gist not created by the-way (by ID),
+        /// adding local snippets and an index file to it
+        #[clap(long, value_name = "GIST_ID")]
+        adopt: Option<String>,
+        /// Replace Gist files with the same names as the-way's when adopting
+        #[clap(long, requires = "adopt")]
+        overwrite: bool,
+        /// Print the URL of the synced Gist instead of syncing
+        #[clap(long, conflicts_with_all = ["force", "open", "dry_run", "adopt"])]
+        url: bool,
+    },
+    /// Open the synced Gist without syncing
+    Gist {
+        #[clap(subcommand)]
+        cmd: GistCommand,
     },
     /// Lists (optionally filtered) snippets
+    #[clap(alias = "l")]
     List {
         #[clap(flatten)]
         filters: Filters,
+        /// Print one tab-separated line per snippet, for scripts:
+        /// index, language, comma-separated tags, and description.
+        /// This format won't change across releases
+        #[clap(long)]
+        porcelain: bool,
+        /// Order snippets by index, date added, or language (then index)
+        #[clap(long, value_enum, default_value_t = SortBy::Index)]
+        sort: SortBy,
+        /// Reverse the order, e.g. newest first with `--sort date`
+        #[clap(long)]
+        reverse: bool,
     },
-    /// Imports code snippets from JSON.
+    /// Imports code snippets from JSON, YAML, or TOML.
     ///
-    /// Looks for description, language, and code fields.
+    /// Looks for description, language, and code fields in each object of a JSON stream or array.
     Import {
-        /// filename, reads from stdin if not given
+        /// filename, reads from stdin if not given.
+        /// A .zip archive imports each text file in it as a snippet, described by its path in the archive
         file: Option<PathBuf>,
 
-        /// URL to a Gist, if provided will import snippets from given Gist
+        /// Input format, guessed from the file extension if not given
+        /// (stdin is read as JSON, then as YAML if that fails)
+        #[clap(long, value_enum, conflicts_with_all = ["gist_url", "gist_file", "the_way_url", "bundle"])]
+        format: Option<ImportFormat>,
+
+        /// URL to a Gist, if provided will import snippets from given Gist (repeat for more)
         ///
         /// Multiple files will be converted to separate snippets.
+        /// Gists that can't be imported are reported and skipped.
         /// Snippet description is created based on Gist description and file name with the format
         /// "<gist_description> - <gist_id> - <file_name>".
         /// Each snippet will be tagged with "gist" and its Gist ID.
         /// Works for both secret and public gists.
         #[clap(long, short, value_name = "URL")]
-        gist_url: Option<String>,
+        gist_url: Vec<String>,
+
+        /// Gist saved from the GitHub API (e.g. https://api.github.com/gists/<gist_id>) as JSON,
+        /// imported like --gist-url without going online (repeat for more)
+        #[clap(long, value_name = "FILE", conflicts_with_all = ["file", "gist_url"])]
+        gist_file: Vec<PathBuf>,
 
         /// URL to a gist file produced by `the-way sync`. If provided will import snippets with
         /// descriptions and tags taken from the `index.md` index file in the gist.
-        #[clap(long, short = 'w', conflicts_with = "gist_url", value_name = "URL")]
+        /// Files not named like snippet_<index>.<ext> are skipped, unless --strict is given.
+        #[clap(long, short = 'w', conflicts_with_all = ["gist_url", "gist_file"], value_name = "URL")]
         the_way_url: Option<String>,
+
+        /// Bundle produced by `the-way export --bundle`.
+        /// Restores snippets, themes, and configuration (keeping current data locations and access token).
+        #[clap(long, short, value_name = "FILE", conflicts_with_all = ["file", "gist_url", "gist_file", "the_way_url"])]
+        bundle: Option<PathBuf>,
+
+        /// Extra tag to add to every imported snippet (repeat for more)
+        #[clap(long = "tag", value_name = "TAG", conflicts_with = "bundle")]
+        tags: Vec<String>,
+
+        /// Guess the language of snippets stored as "text" from their first line (e.g. a shebang)
+        #[clap(long, conflicts_with_all = ["gist_url", "gist_file", "the_way_url", "bundle"])]
+        detect_language: bool,
+
+        /// Import snippets larger than `max_code_bytes` from the configuration file
+        #[clap(long, short)]
+        force: bool,
+
+        /// Fail on files in the Gist not named like snippet_<index>.<ext> instead of skipping them
+        #[clap(long, requires = "the_way_url")]
+        strict: bool,
+
+        /// Skip snippets with the same description, language, code and tags as one already stored
+        #[clap(long, conflicts_with_all = ["gist_url", "gist_file", "the_way_url", "bundle"])]
+        skip_duplicates: bool,
+
+        /// Print the number, descriptions and languages of snippets that would be imported,
+        /// without importing them
+        #[clap(long, conflicts_with = "bundle")]
+        dry_run: bool,
     },
-    /// Saves (optionally filtered) snippets to JSON.
+    /// Saves (optionally filtered) snippets to JSON, YAML, TOML, Markdown, HTML, or plain code.
     Export {
         /// filename, writes to stdout if not given
         file: Option<PathBuf>,
         #[clap(flatten)]
         filters: Filters,
+        /// Write a gzipped tarball bundling snippets, themes, and configuration (access token removed)
+        #[clap(long, short, value_name = "FILE", conflicts_with = "file")]
+        bundle: Option<PathBuf>,
+        /// Output format
+        #[clap(long, value_enum, default_value_t = ExportFormat::Json, conflicts_with = "bundle")]
+        format: ExportFormat,
+        /// Line separating snippets in plain format
+        #[clap(long, default_value = "---")]
+        delimiter: String,
+        /// Start each snippet with a comment line containing its index and description in plain format
+        #[clap(long)]
+        with_header: bool,
     },
+    /// Renumbers snippets to 1, 2, 3, ... in their current order, closing gaps left by deletions
+    Reindex,
     /// Clears all data
     Clear {
         /// Don't ask for confirmation
         #[clap(long, short)]
         force: bool,
+        /// Clear even if the database directory contains files the-way didn't create
+        #[clap(long)]
+        force_unsafe: bool,
     },
     /// Generate shell completions
     Complete {
@@ -110,7 +283,7 @@ pub enum TheWaySubcommand {
     },
     /// Manage the-way data locations.
     ///
-    /// Controlled by $THE_WAY_CONFIG env variable,
+    /// Controlled by $THE_WAY_CONFIG env variable (or the --config flag),
     /// use this to have independent snippet sources for different projects.
     #[clap(alias = "configure")]
     Config {
@@ -119,61 +292,164 @@ pub enum TheWaySubcommand {
     },
     /// Change snippet
     Edit {
-        /// Index of snippet to change, opens a search window if not given
-        index: Option<usize>,
+        /// Indices or exact descriptions of snippets to change, opens a search window if not given
+        indices: Vec<String>,
         #[clap(flatten)]
         filters: Filters,
         /// Use exact search instead of fuzzy
         #[clap(long, short)]
         exact: bool,
+        /// Use fuzzy search, overriding `search_exact` in the configuration file
+        #[clap(long, conflicts_with = "exact")]
+        fuzzy: bool,
+        /// Add a tag to the given snippets without prompting (repeat for more)
+        #[clap(long = "add-tag", value_name = "TAG", requires = "indices")]
+        add_tags: Vec<String>,
+        /// Remove a tag from the given snippets without prompting (repeat for more)
+        #[clap(long = "remove-tag", value_name = "TAG", requires = "indices")]
+        remove_tags: Vec<String>,
+        /// Change the language of the given snippets without prompting
+        #[clap(long, requires = "indices")]
+        language: Option<String>,
+        /// Choose from existing tags in a multi-select with the snippet's tags checked, then add new ones
+        /// (also set by `pick_tags` in the configuration file)
+        #[clap(long, conflicts_with_all = ["add_tags", "remove_tags", "language"])]
+        pick_tags: bool,
+        /// Only change the code, opening it straight in the external editor
+        #[clap(long, requires = "indices", conflicts_with_all = ["add_tags", "remove_tags", "language", "pick_tags"])]
+        code_only: bool,
     },
     /// Delete snippet
     #[clap(alias = "delete")]
     Del {
-        /// Index of snippet to delete, opens a search window if not given
-        index: Option<usize>,
+        /// Index or exact description of snippet to delete, opens a search window if not given
+        index: Option<String>,
         #[clap(flatten)]
         filters: Filters,
         /// Use exact search instead of fuzzy
         #[clap(long, short)]
         exact: bool,
+        /// Use fuzzy search, overriding `search_exact` in the configuration file
+        #[clap(long, conflicts_with = "exact")]
+        fuzzy: bool,
         /// Don't ask for confirmation
         #[clap(long, short)]
         force: bool,
     },
+    /// Star a snippet, listing it first and making it show up with --favorites
+    Star {
+        /// Index or exact description of snippet to star
+        index: String,
+    },
+    /// Remove a snippet's star
+    Unstar {
+        /// Index or exact description of snippet to unstar
+        index: String,
+    },
+    /// Make a snippet private, so it's never synced to the Gist, or make a private snippet syncable again
+    Private {
+        /// Index or exact description of snippet to make private (or syncable again)
+        index: String,
+    },
+    /// Change a snippet's index
+    #[clap(alias = "mv")]
+    Move {
+        /// Index or exact description of snippet to move
+        from: String,
+        /// New index, must not belong to another snippet
+        #[clap(value_parser = utils::parse_snippet_index)]
+        to: usize,
+    },
     /// Copy snippet to clipboard
     #[clap(alias = "copy")]
     Cp {
-        /// Index of snippet to copy, opens a search window if not given
-        index: Option<usize>,
+        /// Indices, index ranges (like 3-6) or exact descriptions of snippets to copy.
+        /// If not given, copies all snippets matching the filters, or opens a search window without filters
+        indices: Vec<String>,
         #[clap(flatten)]
         filters: Filters,
         /// Use exact search instead of fuzzy
         #[clap(long, short)]
         exact: bool,
+        /// Use fuzzy search, overriding `search_exact` in the configuration file
+        #[clap(long, conflicts_with = "exact")]
+        fuzzy: bool,
         /// Print to stdout instead of copying
         #[clap(long, short)]
         stdout: bool,
+        /// Start each printed snippet with a comment line containing its index and description
+        #[clap(long, requires = "stdout")]
+        with_header: bool,
+        /// Copy shell snippets as they are, without asking to fill in their <parameters>
+        #[clap(long)]
+        no_fill: bool,
+    },
+    /// Fill in a snippet used as a template and write it out.
+    ///
+    /// Each {{name}} or {{name=default}} in the code is a placeholder, whatever the snippet's language.
+    /// Placeholders without a --set value are asked for.
+    Scaffold {
+        /// Index or exact description of the template snippet
+        index: String,
+        /// File to write to, prints to stdout if not given
+        #[clap(long, short)]
+        out: Option<PathBuf>,
+        /// Value for a placeholder (repeat for more)
+        #[clap(long = "set", value_name = "NAME=VALUE", value_parser = utils::parse_key_value)]
+        values: Vec<(String, String)>,
+        /// Overwrite an existing file
+        #[clap(long, short, requires = "out")]
+        force: bool,
     },
     /// View snippet
+    #[clap(alias = "v")]
     View {
-        /// Index of snippet to show, opens a search window if not given
-        index: Option<usize>,
+        /// Indices, index ranges (like 3-6) or exact descriptions of snippets to show,
+        /// opens a search window if not given
+        indices: Vec<String>,
         #[clap(flatten)]
         filters: Filters,
         /// Use exact search instead of fuzzy
         #[clap(long, short)]
         exact: bool,
+        /// Use fuzzy search, overriding `search_exact` in the configuration file
+        #[clap(long, conflicts_with = "exact")]
+        fuzzy: bool,
+        /// Also list up to N (default 5) other snippets sharing the most tags with this one
+        #[clap(long, value_name = "N", num_args = 0..=1, default_missing_value = "5", requires = "indices")]
+        related: Option<usize>,
+        /// Print just "#<index> <description> (<language>)" on one line, without code or colors
+        #[clap(long, requires = "indices", conflicts_with = "related")]
+        oneline: bool,
+        /// Also show when the snippet was created and last updated
+        #[clap(long, requires = "indices", conflicts_with = "oneline")]
+        dates: bool,
+        /// Print just the code as stored, without header, colors or filling shell parameters
+        #[clap(long, requires = "indices", conflicts_with_all = ["related", "oneline", "dates"])]
+        raw: bool,
     },
     /// Lists (optionally filtered) tags
     Tags {
+        #[clap(subcommand)]
+        cmd: Option<TagsCommand>,
         #[clap(flatten)]
         filters: Filters,
+        /// Print a JSON array of {"name", "count"} objects
+        #[clap(long)]
+        json: bool,
     },
     /// Lists (optionally filtered) languages
     Languages {
+        #[clap(subcommand)]
+        cmd: Option<LanguagesCommand>,
         #[clap(flatten)]
         filters: Filters,
+        /// List languages with syntax highlighting support instead
+        #[clap(long, conflicts_with_all = ["languages", "tags", "from", "to", "after", "before", "pattern", "all_patterns", "fixed_strings", "ignore_case", "count", "favorites"])]
+        supported: bool,
+        /// Print a JSON array of {"name", "count"} objects
+        #[clap(long, conflicts_with = "supported")]
+        json: bool,
     },
 }
 
@@ -195,6 +471,63 @@ pub enum ThemeCommand {
     Get,
 }
 
+#[derive(ValueEnum, Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SortBy {
+    Index,
+    Date,
+    Language,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ExportFormat {
+    /// One JSON object per snippet, readable by `import`
+    Json,
+    /// A single YAML document listing all snippets
+    Yaml,
+    /// A TOML array of tables, one `[[snippets]]` table per snippet
+    Toml,
+    /// Just the code of each snippet, separated by a delimiter line
+    Plain,
+    /// A Markdown document with a heading, fenced code block, and tag line per snippet
+    Markdown,
+    /// A standalone HTML page with the code syntax highlighted in the current theme
+    Html,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ImportFormat {
+    /// A stream or array of JSON objects, as written by `export`
+    Json,
+    /// A YAML list of snippets, as written by `export --format yaml`
+    Yaml,
+    /// A `[[snippets]]` array of tables, as written by `export --format toml`
+    Toml,
+}
+
+#[derive(Parser, Debug)]
+pub enum TagsCommand {
+    /// Lowercase all existing tags, merging tags that only differ in case and dropping repeated ones
+    Normalize,
+    /// Rename a tag on every snippet that has it, merging it into the new tag if that's already in use
+    Rename {
+        /// Tag to rename
+        old: String,
+        /// New name for the tag
+        new: String,
+    },
+}
+
+#[derive(Parser, Debug)]
+pub enum LanguagesCommand {
+    /// Override the color of a language's box, stored in the configuration file
+    SetColor {
+        /// Language to recolor
+        language: String,
+        /// Hex color, like "#dea584"
+        color: String,
+    },
+}
+
 #[derive(Parser, Debug, Eq, PartialEq)]
 pub enum SyncCommand {
     /// Sync by comparing each snippet's updated date to Gist updated date
@@ -204,3 +537,14 @@ pub enum SyncCommand {
     /// Use Gist snippets as source of truth, choose this to sync snippets across computers
     Gist,
 }
+
+#[derive(Parser, Debug)]
+pub enum GistCommand {
+    /// Opens the synced Gist in a browser, printing its URL instead if there's no terminal
+    /// or no browser could be opened
+    Open {
+        /// Just print the URL
+        #[clap(long)]
+        print: bool,
+    },
+}
@@ -3,13 +3,36 @@ use std::path::Path;
 
 use chrono::{DateTime, Utc};
 use color_eyre::Help;
+use sled::transaction::{
+    ConflictableTransactionResult, TransactionalTree, UnabortableTransactionError,
+};
+use sled::Transactional;
 
 use crate::errors::LostTheWay;
-use crate::the_way::{snippet::Snippet, TheWay};
+use crate::the_way::{
+    snippet::{Checksum, Snippet, SNIPPET_LAYOUT_VERSION},
+    TheWay,
+};
 use crate::utils;
 
-/// If key exists, add value to existing values - join with a semicolon
+/// Key of the snippet layout version in the default tree.
+/// Databases without it were written before snippet records started with a layout version
+const SNIPPET_LAYOUT_KEY: &str = "snippet_layout";
+
+/// Key marking that tag keys in the tag tree are all lowercase.
+/// Databases without it may have tags keyed in the case they were added with
+const LOWERCASE_TAG_KEYS_KEY: &str = "lowercase_tag_keys";
+
+/// If key exists, add value to existing values (unless it's already there) - join with a semicolon
 fn merge_index(_key: &[u8], old_indices: Option<&[u8]>, new_index: &[u8]) -> Option<Vec<u8>> {
+    if let Some(old) = old_indices {
+        if old
+            .split(|byte| *byte == utils::SEMICOLON)
+            .any(|index| index == new_index)
+        {
+            return Some(old.to_vec());
+        }
+    }
     let mut ret = old_indices.map_or_else(Vec::new, |old| old.to_vec());
     if !ret.is_empty() {
         ret.extend_from_slice(&[utils::SEMICOLON]);
@@ -18,10 +41,64 @@ fn merge_index(_key: &[u8], old_indices: Option<&[u8]>, new_index: &[u8]) -> Opt
     Some(ret)
 }
 
+/// Adds a snippet index to the index list at `key` within a transaction,
+/// like the merge operator does outside of one
+fn add_index(
+    tree: &TransactionalTree,
+    key: &[u8],
+    index_key: &[u8],
+) -> Result<(), UnabortableTransactionError> {
+    let old_indices = tree.get(key)?;
+    if let Some(indices) = merge_index(key, old_indices.as_deref(), index_key) {
+        tree.insert(key, indices)?;
+    }
+    Ok(())
+}
+
+/// Removes a snippet index from the index list at `key` within a transaction,
+/// removing the key if no indices are left
+fn remove_index(
+    tree: &TransactionalTree,
+    key: &[u8],
+    index_key: &[u8],
+) -> Result<(), UnabortableTransactionError> {
+    if let Some(old_indices) = tree.get(key)? {
+        let indices = old_indices
+            .split(|byte| *byte == utils::SEMICOLON)
+            .filter(|index| *index != index_key)
+            .collect::<Vec<_>>()
+            .join(&utils::SEMICOLON);
+        if indices.is_empty() {
+            tree.remove(key)?;
+        } else {
+            tree.insert(key, indices)?;
+        }
+    }
+    Ok(())
+}
+
+/// Keys of the given tags in the tag tree.
+/// Tags are matched ignoring case, so they're keyed in lowercase whatever case they're shown in
+fn tag_keys(tags: &[String]) -> Vec<String> {
+    let mut tag_keys = tags
+        .iter()
+        .map(|tag| tag.to_lowercase())
+        .collect::<Vec<_>>();
+    tag_keys.sort();
+    tag_keys.dedup();
+    tag_keys
+}
+
+/// Checks if a file in the database directory is one `sled` creates
+pub(crate) fn is_database_entry(file_name: &str) -> bool {
+    matches!(file_name, "conf" | "db" | "blobs") || file_name.starts_with("snap.")
+}
+
 impl TheWay {
     /// Gets the `sled` database with all the-way info.
     /// Makes a new one the first time round
     pub(crate) fn get_db(db_dir: &Path) -> color_eyre::Result<sled::Db> {
+        log::debug!("Opening database at {}", db_dir.display());
         Ok(sled::open(db_dir)?)
     }
 
@@ -32,11 +109,96 @@ impl TheWay {
         Ok(())
     }
 
+    /// Rewrites snippets stored before records started with a layout version in the current layout,
+    /// in one transaction. Records that can't be read are moved to the unreadable snippets tree
+    /// (and taken out of the language and tag trees) so the rest of the database stays usable
+    pub(crate) fn migrate_unversioned_snippets(&self) -> color_eyre::Result<()> {
+        if self.db.contains_key(SNIPPET_LAYOUT_KEY)? {
+            return Ok(());
+        }
+        let snippets_tree = self.snippets_tree()?;
+        let mut migrated = Vec::new();
+        let mut unreadable = Vec::new();
+        for item in &snippets_tree {
+            let (index_key, bytes) = item?;
+            match Snippet::from_unversioned_bytes(&bytes) {
+                Ok(snippet) => migrated.push((index_key, snippet.to_bytes()?)),
+                Err(error) => {
+                    log::warn!(
+                        "Couldn't read snippet #{} stored by an older version ({error}), \
+                        moving it to the unreadable_snippets tree",
+                        utils::u8_to_str(&index_key)?
+                    );
+                    unreadable.push((index_key, bytes));
+                }
+            }
+        }
+        if !migrated.is_empty() {
+            log::debug!(
+                "Migrating {} snippets to layout version {SNIPPET_LAYOUT_VERSION}",
+                migrated.len()
+            );
+        }
+        let (language_tree, tag_tree) = (self.language_tree()?, self.tag_tree()?);
+        let unreadable_keys =
+            |tree: &sled::Tree| -> color_eyre::Result<Vec<(sled::IVec, sled::IVec)>> {
+                let mut keys = Vec::new();
+                for item in tree {
+                    let (key, index_list) = item?;
+                    for (index_key, _) in &unreadable {
+                        if index_list
+                            .split(|byte| *byte == utils::SEMICOLON)
+                            .any(|index| index == index_key.as_ref())
+                        {
+                            keys.push((key.clone(), index_key.clone()));
+                        }
+                    }
+                }
+                Ok(keys)
+            };
+        let (language_keys, tag_keys) = (
+            unreadable_keys(&language_tree)?,
+            unreadable_keys(&tag_tree)?,
+        );
+        (
+            &*self.db,
+            &snippets_tree,
+            &self.unreadable_snippets_tree()?,
+            &language_tree,
+            &tag_tree,
+        )
+            .transaction(
+                |(db, snippets, unreadable_snippets, languages, tags)| -> ConflictableTransactionResult<(), sled::Error> {
+                    for (index_key, bytes) in &migrated {
+                        snippets.insert(index_key, bytes.as_slice())?;
+                    }
+                    for (index_key, bytes) in &unreadable {
+                        snippets.remove(index_key)?;
+                        unreadable_snippets.insert(index_key, bytes)?;
+                    }
+                    for (key, index_key) in &language_keys {
+                        remove_index(languages, key, index_key)?;
+                    }
+                    for (key, index_key) in &tag_keys {
+                        remove_index(tags, key, index_key)?;
+                    }
+                    db.insert(SNIPPET_LAYOUT_KEY, &[SNIPPET_LAYOUT_VERSION])?;
+                    Ok(())
+                },
+            )?;
+        Ok(())
+    }
+
     /// Gets snippet index: snippet tree
     fn snippets_tree(&self) -> color_eyre::Result<sled::Tree> {
         Ok(self.db.open_tree("snippets")?)
     }
 
+    /// Gets index: snippet record tree of records that couldn't be migrated to the current layout
+    fn unreadable_snippets_tree(&self) -> color_eyre::Result<sled::Tree> {
+        Ok(self.db.open_tree("unreadable_snippets")?)
+    }
+
     /// Gets latest snippet's index
     pub(crate) fn get_current_snippet_index(&self) -> color_eyre::Result<usize> {
         match self.db.get("snippet_index")? {
@@ -71,11 +233,30 @@ impl TheWay {
         Ok(())
     }
 
+    /// Read a snippet stored in the database,
+    /// warning about (or with --strict-checksums, failing on) a checksum mismatch
+    fn read_snippet(&self, bytes: &[u8]) -> color_eyre::Result<Snippet> {
+        let (snippet, checksum) = Snippet::from_bytes(bytes)?;
+        if checksum == Checksum::Mismatch {
+            let error = LostTheWay::ChecksumMismatch {
+                index: snippet.index,
+            };
+            if self.strict_checksums {
+                return Err(error).suggestion(
+                    "Check it with `the-way view`, then fix it with `the-way edit` \
+                    or restore it from a backup",
+                );
+            }
+            log::warn!("{error}");
+        }
+        Ok(snippet)
+    }
+
     /// Retrieve a snippet by index
     pub(crate) fn get_snippet(&self, index: usize) -> color_eyre::Result<Snippet> {
         let index_key = index.to_string();
         let index_key = index_key.as_bytes();
-        Snippet::from_bytes(
+        self.read_snippet(
             &self
                 .snippets_tree()?
                 .get(index_key)?
@@ -84,6 +265,11 @@ impl TheWay {
         )
     }
 
+    /// Checks whether a snippet is stored at the given index
+    pub(crate) fn snippet_exists(&self, index: usize) -> color_eyre::Result<bool> {
+        Ok(self.snippets_tree()?.contains_key(index.to_string())?)
+    }
+
     /// Retrieve snippets at indices
     pub(crate) fn get_snippets(&self, indices: &[usize]) -> color_eyre::Result<Vec<Snippet>> {
         indices.iter().map(|i| self.get_snippet(*i)).collect()
@@ -113,7 +299,7 @@ impl TheWay {
                     }
                     .into()
                 })
-                .and_then(|(_, snippet)| Snippet::from_bytes(&snippet))
+                .and_then(|(_, snippet)| self.read_snippet(&snippet))
             })
             .collect::<color_eyre::Result<Vec<_>>>()
     }
@@ -173,9 +359,8 @@ impl TheWay {
         tags: &[String],
         index_key: &[u8],
     ) -> color_eyre::Result<()> {
-        for tag in tags {
-            let tag_key = tag.as_bytes();
-            self.tag_tree()?.merge(tag_key, index_key)?;
+        for tag_key in tag_keys(tags) {
+            self.tag_tree()?.merge(tag_key.as_bytes(), index_key)?;
         }
         Ok(())
     }
@@ -190,8 +375,21 @@ impl TheWay {
         Ok(())
     }
 
+    /// Snippet with its tags as they're stored: deduplicated, and lowercased with `lowercase_tags`
+    fn clean_tags(&self, snippet: &Snippet) -> Snippet {
+        let mut cleaned = snippet.clone();
+        if self.config.lowercase_tags {
+            cleaned.lowercase_tags();
+        } else {
+            cleaned.dedup_tags();
+        }
+        cleaned
+    }
+
     /// Add a snippet (with all attached data) to the database and change metadata accordingly
     pub(crate) fn add_snippet(&mut self, snippet: &Snippet) -> color_eyre::Result<usize> {
+        let cleaned = self.clean_tags(snippet);
+        let snippet = &cleaned;
         let language_key = snippet.language.as_bytes();
         let index_key = snippet.index.to_string();
         let index_key = index_key.as_bytes();
@@ -269,23 +467,69 @@ impl TheWay {
         let language_key = snippet.language.as_bytes();
         self.delete_from_language(language_key, index)?;
         let mut tag_batch = sled::Batch::default();
-        for tag in &snippet.tags {
-            self.delete_from_tag(tag.as_bytes(), index, &mut tag_batch)?;
+        for tag_key in tag_keys(&snippet.tags) {
+            self.delete_from_tag(tag_key.as_bytes(), index, &mut tag_batch)?;
         }
         self.tag_tree()?.apply_batch(tag_batch)?;
         Ok(())
     }
 
+    /// Deletes the snippets at `delete` and adds the snippets in `add` in one transaction
+    /// across the snippet, language and tag trees, so nothing changes if any of it fails
+    pub(crate) fn replace_snippets(
+        &mut self,
+        delete: &[usize],
+        add: &[Snippet],
+    ) -> color_eyre::Result<()> {
+        let deleted = self.get_snippets(delete)?;
+        let added = add
+            .iter()
+            .map(|snippet| {
+                let snippet = self.clean_tags(snippet);
+                let bytes = snippet.to_bytes()?;
+                Ok((snippet, bytes))
+            })
+            .collect::<color_eyre::Result<Vec<_>>>()?;
+        (
+            &self.snippets_tree()?,
+            &self.language_tree()?,
+            &self.tag_tree()?,
+        )
+            .transaction(
+                |(snippets, languages, tags)| -> ConflictableTransactionResult<(), sled::Error> {
+                    for snippet in &deleted {
+                        let index_key = snippet.index.to_string();
+                        snippets.remove(index_key.as_bytes())?;
+                        remove_index(languages, snippet.language.as_bytes(), index_key.as_bytes())?;
+                        for tag_key in tag_keys(&snippet.tags) {
+                            remove_index(tags, tag_key.as_bytes(), index_key.as_bytes())?;
+                        }
+                    }
+                    for (snippet, bytes) in &added {
+                        let index_key = snippet.index.to_string();
+                        snippets.insert(index_key.as_bytes(), bytes.as_slice())?;
+                        add_index(languages, snippet.language.as_bytes(), index_key.as_bytes())?;
+                        for tag_key in tag_keys(&snippet.tags) {
+                            add_index(tags, tag_key.as_bytes(), index_key.as_bytes())?;
+                        }
+                    }
+                    Ok(())
+                },
+            )?;
+        Ok(())
+    }
+
     /// Delete snippet from the snippet tree
     fn delete_from_snippets_tree(&mut self, index: usize) -> color_eyre::Result<Snippet> {
         let index_key = index.to_string();
         let index_key = index_key.as_bytes();
-        Snippet::from_bytes(
+        Ok(Snippet::from_bytes(
             &self
                 .snippets_tree()?
                 .remove(index_key)?
                 .ok_or(LostTheWay::SnippetNotFound { index })?,
-        )
+        )?
+        .0)
     }
 
     /// Retrieve snippets written in a given language
@@ -300,12 +544,48 @@ impl TheWay {
         )
     }
 
-    /// Retrieve snippets associated with a given tag
+    /// Retrieve snippets associated with a given tag, ignoring case
     pub(crate) fn get_tag_snippets(&self, tag: &str) -> color_eyre::Result<Vec<usize>> {
-        utils::split_indices_usize(&self.tag_tree()?.get(tag.as_bytes())?.ok_or(
+        utils::split_indices_usize(&self.tag_tree()?.get(tag.to_lowercase().as_bytes())?.ok_or(
             LostTheWay::TagNotFound {
                 tag: tag.to_owned(),
             },
         )?)
     }
+
+    /// Moves snippet indices from tag keys with uppercase letters, stored by older versions,
+    /// to their lowercase keys, in one transaction. Only runs once per database
+    pub(crate) fn lowercase_tag_keys(&self) -> color_eyre::Result<()> {
+        if self.db.contains_key(LOWERCASE_TAG_KEYS_KEY)? {
+            return Ok(());
+        }
+        let tag_tree = self.tag_tree()?;
+        let mut uppercase_keys = Vec::new();
+        for item in &tag_tree {
+            let (tag_key, index_list) = item?;
+            let lowercase_tag = utils::u8_to_str(&tag_key)?.to_lowercase();
+            if tag_key != lowercase_tag.as_bytes() {
+                uppercase_keys.push((tag_key, lowercase_tag, index_list));
+            }
+        }
+        if !uppercase_keys.is_empty() {
+            log::debug!(
+                "Lowercasing {} tag keys stored by an older version",
+                uppercase_keys.len()
+            );
+        }
+        (&*self.db, &tag_tree).transaction(
+            |(db, tags)| -> ConflictableTransactionResult<(), sled::Error> {
+                for (tag_key, lowercase_tag, index_list) in &uppercase_keys {
+                    for index_key in index_list.split(|byte| *byte == utils::SEMICOLON) {
+                        add_index(tags, lowercase_tag.as_bytes(), index_key)?;
+                    }
+                    tags.remove(tag_key)?;
+                }
+                db.insert(LOWERCASE_TAG_KEYS_KEY, &[1])?;
+                Ok(())
+            },
+        )?;
+        Ok(())
+    }
 }
@@ -4,12 +4,14 @@ use std::ffi::OsString;
 
 use chrono::{DateTime, Utc};
 use clap::Parser;
-use regex::Regex;
+use color_eyre::Help;
+use regex::RegexBuilder;
 
+use crate::errors::LostTheWay;
 use crate::the_way::{snippet::Snippet, TheWay};
 use crate::utils;
 
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Default)]
 pub struct Filters {
     /// Snippets written in <language> (multiple with 'lang1 lang2')
     #[clap(short, long)]
@@ -23,14 +25,61 @@ pub struct Filters {
     /// Snippets before <date>
     #[clap(long, value_parser = utils::parse_date)]
     pub(crate) to: Option<DateTime<Utc>>,
-    /// Snippets matching pattern
+    /// Snippets with index greater than <index>
+    #[clap(long, value_name = "INDEX")]
+    pub(crate) after: Option<usize>,
+    /// Snippets with index less than <index>
+    #[clap(long, value_name = "INDEX")]
+    pub(crate) before: Option<usize>,
+    /// Snippets matching pattern (multiple with repeated flags, matches any by default)
     #[clap(short, long)]
-    pub(crate) pattern: Option<OsString>,
+    pub(crate) pattern: Option<Vec<OsString>>,
+    /// Snippets must match all given patterns
+    #[clap(long, requires = "pattern")]
+    pub(crate) all_patterns: bool,
+    /// Match patterns as literal strings instead of regular expressions
+    #[clap(short = 'F', long, requires = "pattern")]
+    pub(crate) fixed_strings: bool,
+    /// Match patterns case-insensitively
+    #[clap(short = 'i', long, requires = "pattern")]
+    pub(crate) ignore_case: bool,
+    /// Only the <count> most recently added of the matching snippets
+    #[clap(short = 'n', long)]
+    pub(crate) count: Option<usize>,
+    /// Only starred snippets
+    #[clap(long)]
+    pub(crate) favorites: bool,
+}
+
+impl Filters {
+    /// True if no filter is given, i.e. all snippets match
+    pub(crate) fn is_empty(&self) -> bool {
+        self.languages.is_none()
+            && self.tags.is_none()
+            && self.from.is_none()
+            && self.to.is_none()
+            && self.after.is_none()
+            && self.before.is_none()
+            && self.pattern.is_none()
+            && self.count.is_none()
+            && !self.favorites
+    }
 }
 
 impl TheWay {
-    /// Filters a list of snippets by given language/tag/date
+    /// Filters a list of snippets by given language/tag/date/index/pattern/favorite,
+    /// keeping the `count` most recent ones if given
     pub(crate) fn filter_snippets(&self, filters: &Filters) -> color_eyre::Result<Vec<Snippet>> {
+        let mut snippets = self.filter_all_snippets(filters)?;
+        if let Some(count) = filters.count {
+            snippets.sort_by(|a, b| b.date.cmp(&a.date).then(b.index.cmp(&a.index)));
+            snippets.truncate(count);
+        }
+        Ok(snippets)
+    }
+
+    /// All snippets matching the filters, ignoring `count`
+    fn filter_all_snippets(&self, filters: &Filters) -> color_eyre::Result<Vec<Snippet>> {
         let from_date = utils::date_start(filters.from);
         let to_date = utils::date_end(filters.to);
         let snippets: Option<Vec<_>> = match &filters.languages {
@@ -72,16 +121,50 @@ impl TheWay {
             }
             (None, None) => self.list_snippets_in_date_range(from_date, to_date),
         };
+        let snippets = snippets.map(|snippets: Vec<Snippet>| {
+            snippets
+                .into_iter()
+                .filter(|snippet| {
+                    filters.after.is_none_or(|after| snippet.index > after)
+                        && filters.before.is_none_or(|before| snippet.index < before)
+                        && (!filters.favorites || snippet.favorite)
+                })
+                .collect::<Vec<_>>()
+        });
         match &filters.pattern {
-            Some(pattern) => {
-                let regex = Regex::new(&pattern.to_string_lossy())?;
+            Some(patterns) => {
+                let regexes = patterns
+                    .iter()
+                    .map(|pattern| {
+                        let pattern = pattern.to_string_lossy();
+                        if filters.fixed_strings {
+                            return Ok(RegexBuilder::new(&regex::escape(&pattern))
+                                .case_insensitive(filters.ignore_case)
+                                .build()?);
+                        }
+                        RegexBuilder::new(&pattern)
+                            .case_insensitive(filters.ignore_case)
+                            .build()
+                            .map_err(|e| LostTheWay::InvalidPattern {
+                                pattern: pattern.to_string(),
+                                message: e.to_string(),
+                            })
+                            .suggestion(
+                                "Patterns are regular expressions, so characters like ( ) [ ] { } . * + ? | \\ \
+                                have special meanings. Escape them with a backslash to match them literally, \
+                                e.g. 'foo\\(bar', or use --fixed-strings",
+                            )
+                    })
+                    .collect::<color_eyre::Result<Vec<_>>>()?;
                 snippets.map(|snippets| {
                     snippets
                         .into_iter()
                         .filter(|snippet| {
-                            regex.is_match(&snippet.description)
-                                || snippet.tags.iter().any(|tag| regex.is_match(tag))
-                                || regex.is_match(&snippet.code)
+                            if filters.all_patterns {
+                                regexes.iter().all(|regex| snippet.matches(regex))
+                            } else {
+                                regexes.iter().any(|regex| snippet.matches(regex))
+                            }
                         })
                         .collect()
                 })
@@ -1,11 +1,17 @@
 //! Code related to dealing with Gists
-use chrono::Utc;
 use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::{fs, io};
 
 use color_eyre::Help;
 
+use crate::configuration::SyncBackend;
 use crate::errors::LostTheWay;
-use crate::gist::{CreateGistPayload, Gist, GistClient, GistContent, UpdateGistPayload};
+use crate::gist::{
+    CreateGistPayload, Gist, GistClient, GistContent, GistFile, SnippetSyncBackend,
+    UpdateGistPayload,
+};
+use crate::gitlab::GitLabClient;
 use crate::language::Language;
 use crate::the_way::{cli::SyncCommand, snippet::Snippet, TheWay};
 use crate::utils;
@@ -16,56 +22,153 @@ use strum_macros::Display;
 const DESCRIPTION: &str = "The Way Code Snippets";
 /// Heading for the index.md file
 const INDEX_HEADING: &str = "# Is it not written...\n";
+/// `sync local` asks before syncing if there are fewer local snippets than this fraction of the Gist's
+const LOCAL_SYNC_MIN_FRACTION: f64 = 0.25;
 
-/// Parse line in Gist index.md file to get the snippet index, description and tags
-pub(crate) fn parse_index_line(
-    index_line: &str,
-) -> color_eyre::Result<(usize, String, Vec<String>)> {
-    // "[{snippet.description}]({result.html_url}#file-{snippet_{snippet.index}{snippet.extension}}) :tag1:tag2:\n"
-    let re = regex::Regex::new(r"\* \[(.*)\]\(.*#file-snippet_([0-9]*)(.*)\)( :(.*)+:)?")?;
-    let caps = re
-        .captures(index_line)
-        .ok_or(LostTheWay::GistFormattingError {
-            message: format!("Index line isn't formatted correctly:\n{index_line}"),
-        })?;
-    let description = caps[1].to_owned();
-    let index = caps[2].parse::<usize>()?;
-    if let Some(tags) = caps.get(4) {
-        let tags = tags
-            .as_str()
-            .trim()
-            .split(':')
-            .filter_map(|t| {
-                let t = t.to_owned();
-                if t.is_empty() {
-                    None
-                } else {
-                    Some(t)
-                }
-            })
-            .collect::<Vec<_>>();
-        Ok((index, description, tags))
-    } else {
-        Ok((index, description, vec![]))
+/// Default format of each snippet's line in the Gist index.md file
+pub(crate) const DEFAULT_INDEX_LINE: &str = "* [{description}]({url}#file-{file}) {tags}";
+/// Placeholders allowed in the index.md line format
+const INDEX_LINE_PLACEHOLDERS: &str = r"\{(description|url|file|tags)\}";
+
+/// Make a list item for the Gist index.md file
+fn make_index_line(
+    index_file_content: &mut String,
+    template: &str,
+    html_url: &str,
+    snippet: &Snippet,
+) -> color_eyre::Result<()> {
+    index_file_content.push_str(&snippet.index_line(template, html_url)?);
+    index_file_content.push('\n');
+    Ok(())
+}
+
+/// Payload creating a public or secret Gist with each code snippet as a separate file
+/// (named snippet_<index>.<ext>). Private snippets are left out
+pub(crate) fn create_payload(snippets: &[Snippet], public: bool) -> CreateGistPayload<'_> {
+    let files = snippets
+        .iter()
+        .filter(|snippet| !snippet.private)
+        .map(|snippet| {
+            (
+                format!("snippet_{}{}", snippet.index, snippet.extension),
+                GistContent {
+                    content: snippet.code.as_str(),
+                },
+            )
+        })
+        .collect();
+    CreateGistPayload {
+        description: DESCRIPTION,
+        public,
+        files,
     }
 }
 
-/// Make a list item for the Gist index.md file
-fn make_index_line(index_file_content: &mut String, html_url: &str, snippet: &Snippet) {
-    index_file_content.push_str(&format!(
-        "* [{}]({}#file-{}){}\n",
-        snippet.description,
-        html_url,
-        format!("snippet_{}{}", snippet.index, snippet.extension).replace('.', "-"),
-        if snippet.tags.is_empty() {
+/// Regex matching lines made from an index.md line format.
+/// Whitespace in the format is optional so lines without tags (trailing whitespace trimmed) still match
+fn index_line_regex(template: &str) -> color_eyre::Result<regex::Regex> {
+    let placeholders = regex::Regex::new(INDEX_LINE_PLACEHOLDERS)?;
+    let escape = |literal: &str| {
+        let mut escaped = String::new();
+        for (i, part) in literal.split(char::is_whitespace).enumerate() {
+            if i > 0 && !escaped.ends_with(r"\s*") {
+                escaped.push_str(r"\s*");
+            }
+            escaped.push_str(&regex::escape(part));
+        }
+        escaped
+    };
+    let mut pattern = String::from("^");
+    let mut last = 0;
+    for caps in placeholders.captures_iter(template) {
+        let placeholder = caps.get(0).unwrap();
+        pattern.push_str(&escape(&template[last..placeholder.start()]));
+        pattern.push_str(match &caps[1] {
+            "description" => "(?P<description>.*)",
+            "url" => ".*?",
+            "file" => r"snippet_(?P<index>[0-9]+)[-\w]*",
+            _ => "(?P<tags>:.*:)?",
+        });
+        last = placeholder.end();
+    }
+    pattern.push_str(&escape(&template[last..]));
+    pattern.push('$');
+    let error: color_eyre::Result<regex::Regex> = match regex::Regex::new(&pattern) {
+        Ok(re)
+            if re
+                .capture_names()
+                .flatten()
+                .any(|name| name == "description")
+                && re.capture_names().flatten().any(|name| name == "index") =>
+        {
+            return Ok(re)
+        }
+        Ok(_) => Err(LostTheWay::ConfigError {
+            message: format!(
+                "Index line format {template:?} is missing {{description}} or {{file}}"
+            ),
+        }
+        .into()),
+        Err(e) => Err(LostTheWay::ConfigError {
+            message: format!("Invalid index line format {template:?}: {e}"),
+        }
+        .into()),
+    };
+    error.suggestion(format!(
+        "Set gist_index_line in the configuration file to a format using each of {{description}}, \
+        {{url}}, {{file}} and {{tags}} at most once, like {DEFAULT_INDEX_LINE:?}"
+    ))
+}
+
+impl Snippet {
+    /// Make this snippet's line in the Gist index.md file from a format with
+    /// `{description}`, `{url}`, `{file}` and `{tags}` placeholders
+    pub(crate) fn index_line(&self, template: &str, html_url: &str) -> color_eyre::Result<String> {
+        let placeholders = regex::Regex::new(INDEX_LINE_PLACEHOLDERS)?;
+        let file = format!("snippet_{}{}", self.index, self.extension).replace('.', "-");
+        let tags = if self.tags.is_empty() {
             String::new()
         } else {
-            format!(" :{}:", snippet.tags.join(":"))
-        }
-    ));
+            format!(":{}:", self.tags.join(":"))
+        };
+        let line =
+            placeholders.replace_all(template, |caps: &regex::Captures<'_>| match &caps[1] {
+                "description" => self.description.clone(),
+                "url" => html_url.to_owned(),
+                "file" => file.clone(),
+                _ => tags.clone(),
+            });
+        Ok(line.trim_end().to_owned())
+    }
+
+    /// Parse line in Gist index.md file with the regex made from its format (see `index_line_regex`)
+    /// to get the snippet index, description and tags
+    pub(crate) fn parse_index_line(
+        re: &regex::Regex,
+        index_line: &str,
+    ) -> color_eyre::Result<(usize, String, Vec<String>)> {
+        let caps = re
+            .captures(index_line.trim_end())
+            .ok_or(LostTheWay::GistFormattingError {
+                message: format!("Index line isn't formatted correctly:\n{index_line}"),
+            })?;
+        let description = caps["description"].to_owned();
+        let index = caps["index"].parse::<usize>()?;
+        let tags = caps
+            .name("tags")
+            .map(|tags| {
+                tags.as_str()
+                    .split(':')
+                    .filter(|t| !t.is_empty())
+                    .map(ToOwned::to_owned)
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok((index, description, tags))
+    }
 }
 
-#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Display)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Display)]
 enum SyncAction {
     #[strum(serialize = "downloaded from Gist")]
     Downloaded,
@@ -83,22 +186,72 @@ enum SyncAction {
     UpToDate,
 }
 
+/// Changes a sync makes, worked out before any of them are applied
+#[derive(Debug, Default)]
+pub(crate) struct SyncPlan {
+    /// Gist files to add or update (with their new content) or delete (`None`), including index.md
+    pub(crate) gist_files: HashMap<String, Option<String>>,
+    /// Gist snippets replacing local ones
+    pub(crate) download: Vec<Snippet>,
+    /// Gist snippets missing locally
+    pub(crate) add_local: Vec<Snippet>,
+    /// Indices of local snippets to delete
+    pub(crate) delete_local: Vec<usize>,
+    /// Number of snippets for each sync action
+    action_counts: HashMap<SyncAction, usize>,
+    /// Number of (non-private) local snippets
+    num_local: usize,
+    /// Number of snippets in the Gist
+    num_gist: usize,
+}
+
+impl SyncPlan {
+    fn count(&self, action: SyncAction) -> usize {
+        self.action_counts.get(&action).copied().unwrap_or_default()
+    }
+
+    /// Whether there are so few local snippets that syncing from them would (nearly) wipe the Gist
+    pub(crate) fn wipes_gist(&self) -> bool {
+        (self.num_local as f64) < LOCAL_SYNC_MIN_FRACTION * self.num_gist as f64
+    }
+
+    /// Number of snippets for each sync action, like "2 snippet(s) uploaded to Gist"
+    pub(crate) fn action_lines(&self) -> Vec<String> {
+        let mut counts = self.action_counts.iter().collect::<Vec<_>>();
+        counts.sort();
+        counts
+            .into_iter()
+            .map(|(action, count)| format!("{count} snippet(s) {action}"))
+            .collect()
+    }
+
+    /// One line describing the plan, shown before syncing
+    pub(crate) fn summary(&self) -> String {
+        format!(
+            "Will upload {}, delete {} from the Gist, download {}, add {} locally and delete {} locally",
+            self.count(SyncAction::Uploaded) + self.count(SyncAction::AddedGist),
+            self.count(SyncAction::DeletedGist),
+            self.count(SyncAction::Downloaded),
+            self.count(SyncAction::AddedLocal),
+            self.count(SyncAction::DeletedLocal),
+        )
+    }
+}
+
 impl Snippet {
     /// Read potentially multiple snippets from a Gist
-    /// if `start_index` is None, indices are read from the Gist filenames (index.md is set to index 0)
+    /// if `start_index` is None, indices are read from the Gist filenames (index.md is set to index 0).
+    /// Snippets are tagged with "gist" and any extra `tags`.
+    /// Snippet dates are taken from the Gist's creation and update times
     pub(crate) fn from_gist(
         start_index: Option<usize>,
         languages: &HashMap<String, Language>,
         gist: &Gist,
+        tags: &[String],
     ) -> color_eyre::Result<Vec<Self>> {
         let mut current_index = start_index;
         let mut snippets = Vec::new();
         for (file_name, gist_file) in &gist.files {
-            let code = &gist_file.content;
-            let description = format!("{} - {} - {file_name}", gist.description, gist.id);
-            let language = &gist_file.language.to_ascii_lowercase();
-            let tags = "gist";
-            let extension = Language::get_extension(language, languages);
             let index = if let Some(i) = current_index {
                 i
             } else if file_name == "index.md" {
@@ -117,28 +270,66 @@ impl Snippet {
                     })?
                     .parse()?
             };
-            let snippet = Self::new(
-                index,
-                description,
-                language.to_string(),
-                extension.to_owned(),
-                tags,
-                Utc::now(),
-                Utc::now(),
-                code.to_string(),
-            );
+            let mut snippet = Self::from_gist_file(index, languages, gist, file_name, gist_file);
+            snippet.add_tags(tags);
             snippets.push(snippet);
             current_index = current_index.map(|i| i + 1);
         }
         Ok(snippets)
     }
 
-    /// Read snippets from a gist created by `the-way sync`
+    /// Make a snippet from a single Gist file
+    fn from_gist_file(
+        index: usize,
+        languages: &HashMap<String, Language>,
+        gist: &Gist,
+        file_name: &str,
+        gist_file: &GistFile,
+    ) -> Self {
+        let description = format!("{} - {} - {file_name}", gist.description, gist.id);
+        let language = gist_file.language.to_ascii_lowercase();
+        let extension = Language::get_extension(&language, languages);
+        Self::new(
+            index,
+            description,
+            language.clone(),
+            extension.to_owned(),
+            "gist",
+            gist.created_at,
+            gist.updated_at,
+            gist_file.content.clone(),
+        )
+    }
+
+    /// Read snippets from a gist created by `the-way sync`.
+    /// Files not named like snippet_<index>.<ext> are an error if `strict` is set,
+    /// otherwise they're skipped and their names returned alongside the snippets
     pub(crate) fn from_the_way_gist(
         languages: &HashMap<String, Language>,
         gist: &Gist,
-    ) -> color_eyre::Result<Vec<Self>> {
-        let snippets = Self::from_gist(None, languages, gist)?;
+        index_line_template: &str,
+        strict: bool,
+    ) -> color_eyre::Result<(Vec<Self>, Vec<String>)> {
+        let mut snippets = Vec::new();
+        let mut skipped = Vec::new();
+        for (file_name, gist_file) in &gist.files {
+            let index = if file_name == "index.md" {
+                0
+            } else {
+                match get_gist_snippet_index(file_name) {
+                    Ok(index) => index,
+                    Err(e) if strict => return Err(e),
+                    Err(_) => {
+                        skipped.push(file_name.clone());
+                        continue;
+                    }
+                }
+            };
+            snippets.push(Self::from_gist_file(
+                index, languages, gist, file_name, gist_file,
+            ));
+        }
+        skipped.sort();
         let index_snippet =
             snippets
                 .iter()
@@ -146,9 +337,16 @@ impl Snippet {
                 .ok_or(LostTheWay::GistFormattingError {
                     message: String::from("Index file not found"),
                 })?;
+        let index_line_re = index_line_regex(index_line_template)?;
         let mut index_mapping = HashMap::new();
-        for line in index_snippet.code.trim().split('\n').skip(1) {
-            let (index, description, tags) = parse_index_line(line)?;
+        for line in index_snippet
+            .code
+            .trim()
+            .split('\n')
+            .skip(1)
+            .filter(|line| !line.trim().is_empty())
+        {
+            let (index, description, tags) = Self::parse_index_line(&index_line_re, line)?;
             let val = index_mapping.insert(index, (description, tags));
             if val.is_some() {
                 return Err(LostTheWay::GistFormattingError {
@@ -157,6 +355,12 @@ impl Snippet {
                 .into());
             }
         }
+        if index_mapping.is_empty() && snippets.len() > 1 {
+            return Err(LostTheWay::GistFormattingError {
+                message: String::from("Gist index is empty — is this a the-way gist?"),
+            })
+            .suggestion("Use `the-way import --gist-url` to import it as a plain Gist");
+        }
         let mut found_indices = HashSet::new();
         let mut gist_snippets = Vec::new();
         for mut snippet in snippets.into_iter().filter(|s| s.index != 0) {
@@ -177,14 +381,83 @@ impl Snippet {
             found_indices.insert(snippet.index);
             gist_snippets.push(snippet);
         }
-        Ok(gist_snippets)
+        // Files come in no particular order, keep the Gist's
+        gist_snippets.sort_by_key(|snippet| snippet.index);
+        Ok((gist_snippets, skipped))
     }
 }
 
+impl Snippet {
+    /// Makes the files needed to turn an existing Gist into one managed by the-way:
+    /// one file per snippet (named snippet_<index>.<ext>) and an index file (index.md).
+    /// Fails if any of these would replace a file already in the Gist, unless `overwrite` is set.
+    pub(crate) fn to_adopted_gist_files(
+        snippets: &[Self],
+        gist: &Gist,
+        index_line_template: &str,
+        overwrite: bool,
+    ) -> color_eyre::Result<HashMap<String, String>> {
+        let mut files = HashMap::new();
+        let mut index_file_content = String::from(INDEX_HEADING);
+        for snippet in snippets {
+            files.insert(
+                format!("snippet_{}{}", snippet.index, snippet.extension),
+                snippet.code.clone(),
+            );
+            make_index_line(
+                &mut index_file_content,
+                index_line_template,
+                &gist.html_url,
+                snippet,
+            )?;
+        }
+        files.insert(String::from("index.md"), index_file_content);
+        if !overwrite {
+            let mut clobbered = files
+                .keys()
+                .filter(|file_name| gist.files.contains_key(*file_name))
+                .cloned()
+                .collect::<Vec<_>>();
+            if !clobbered.is_empty() {
+                clobbered.sort();
+                let error: color_eyre::Result<HashMap<String, String>> =
+                    Err(LostTheWay::SyncError {
+                        message: format!(
+                            "Adopting Gist {} would replace {}",
+                            gist.id,
+                            clobbered.join(", ")
+                        ),
+                    }
+                    .into());
+                return error.suggestion("Use --overwrite to replace these files");
+            }
+        }
+        Ok(files)
+    }
+}
+
+/// URLs of Gists that couldn't be read, with their errors
+type FailedGists = Vec<(String, color_eyre::Report)>;
+
 impl TheWay {
+    /// Format of each snippet's line in the Gist index.md file
+    fn index_line_template(&self) -> &str {
+        self.config
+            .gist_index_line
+            .as_deref()
+            .unwrap_or(DEFAULT_INDEX_LINE)
+    }
+
+    /// GitHub API URL from $THE_WAY_GITHUB_API_URL or the configuration file, if either is set
+    fn github_api_url(&self) -> Option<String> {
+        std::env::var("THE_WAY_GITHUB_API_URL")
+            .ok()
+            .or_else(|| self.config.github_api_url.clone())
+    }
+
     /// Fetch gist
-    fn get_gist(gist_url: &str) -> color_eyre::Result<Gist> {
-        let client = GistClient::new(None)?;
+    fn get_gist(&self, gist_url: &str) -> color_eyre::Result<Gist> {
+        let client = GistClient::new(self.github_api_url().as_deref(), None)?;
         let spinner = utils::get_spinner("Fetching gist...");
         let gist = client.get_gist_by_url(gist_url);
         if let Err(err) = gist {
@@ -194,67 +467,114 @@ impl TheWay {
         gist
     }
 
-    /// Import Snippets from a regular Gist
-    pub(crate) fn import_gist(&mut self, gist_url: &str) -> color_eyre::Result<Vec<Snippet>> {
-        let gist = Self::get_gist(gist_url)?;
+    /// Read snippets to import from regular Gists, adding extra `tags` to each.
+    /// Returns the snippets and the URLs of Gists that couldn't be read with their errors
+    pub(crate) fn read_gists(
+        &self,
+        gist_urls: &[String],
+        tags: &[String],
+    ) -> color_eyre::Result<(Vec<Snippet>, FailedGists)> {
         let start_index = self.get_current_snippet_index()? + 1;
-        let snippets = Snippet::from_gist(Some(start_index), &self.languages, &gist)?;
-        for snippet in &snippets {
-            self.add_snippet(snippet)?;
-            self.increment_snippet_index()?;
+        let mut snippets = Vec::new();
+        let mut failed = Vec::new();
+        for gist_url in gist_urls {
+            let gist_snippets = self.get_gist(gist_url).and_then(|gist| {
+                Snippet::from_gist(
+                    Some(start_index + snippets.len()),
+                    &self.languages,
+                    &gist,
+                    tags,
+                )
+            });
+            match gist_snippets {
+                Ok(gist_snippets) => snippets.extend(gist_snippets),
+                Err(e) => failed.push((gist_url.clone(), e)),
+            }
+        }
+        Ok((snippets, failed))
+    }
+
+    /// Read snippets to import from Gists saved as GitHub API JSON responses, adding extra `tags` to each
+    pub(crate) fn read_gist_files(
+        &self,
+        gist_files: &[PathBuf],
+        tags: &[String],
+    ) -> color_eyre::Result<Vec<Snippet>> {
+        let start_index = self.get_current_snippet_index()? + 1;
+        let mut snippets = Vec::new();
+        for gist_file in gist_files {
+            let gist: Gist = serde_json::from_reader(io::BufReader::new(fs::File::open(
+                gist_file,
+            )?))
+            .map_err(|e| LostTheWay::GistFormattingError {
+                message: format!("{} isn't a Gist API response: {e}", gist_file.display()),
+            })?;
+            snippets.extend(Snippet::from_gist(
+                Some(start_index + snippets.len()),
+                &self.languages,
+                &gist,
+                tags,
+            )?);
         }
         Ok(snippets)
     }
 
-    /// Import snippets from a Gist created by `the_way sync`
-    pub(crate) fn import_the_way_gist(
-        &mut self,
+    /// Read snippets to import from a Gist created by `the_way sync`, adding extra `tags` to each.
+    /// Returns the snippets and the names of skipped files (see `Snippet::from_the_way_gist`)
+    pub(crate) fn read_the_way_gist(
+        &self,
         gist_url: &str,
-    ) -> color_eyre::Result<Vec<Snippet>> {
-        let gist = Self::get_gist(gist_url)?;
-        let mut snippets = Snippet::from_the_way_gist(&self.languages, &gist)?;
-        let mut current_index = self.get_current_snippet_index()? + 1;
+        tags: &[String],
+        strict: bool,
+    ) -> color_eyre::Result<(Vec<Snippet>, Vec<String>)> {
+        let gist = self.get_gist(gist_url)?;
+        let (mut snippets, skipped) =
+            Snippet::from_the_way_gist(&self.languages, &gist, self.index_line_template(), strict)?;
         for snippet in &mut snippets {
-            snippet.index = current_index;
-            self.add_snippet(snippet)?;
-            self.increment_snippet_index()?;
-            current_index += 1;
+            snippet.add_tags(tags);
         }
-        Ok(snippets)
+        Ok((snippets, skipped))
+    }
+
+    /// Client for the configured sync backend
+    fn sync_client<'a>(
+        &self,
+        access_token: &'a str,
+    ) -> color_eyre::Result<Box<dyn SnippetSyncBackend + 'a>> {
+        Ok(match self.config.sync_backend {
+            SyncBackend::Github => Box::new(GistClient::new(
+                self.github_api_url().as_deref(),
+                Some(access_token),
+            )?),
+            SyncBackend::Gitlab => Box::new(GitLabClient::new(
+                self.config.gitlab_url.as_deref(),
+                access_token,
+            )),
+        })
     }
 
     /// Creates a Gist with each code snippet as a separate file (named snippet_<index>.<ext>)
     /// and an index file (index.md) listing each snippet's description
-    pub(crate) fn make_gist(&self, access_token: &str) -> color_eyre::Result<String> {
+    pub(crate) fn make_gist(&self, access_token: &str) -> color_eyre::Result<Gist> {
         // Make client
-        let client = GistClient::new(Some(access_token))?;
+        let client = self.sync_client(access_token)?;
         // Start creating
         let spinner = utils::get_spinner("Creating Gist...");
 
-        // Make snippet files
-        let mut files = HashMap::new();
         let snippets = self.list_snippets()?;
-        for snippet in &snippets {
-            let filename = format!("snippet_{}{}", snippet.index, snippet.extension);
-            files.insert(
-                filename,
-                GistContent {
-                    content: snippet.code.as_str(),
-                },
-            );
-        }
-        let payload = CreateGistPayload {
-            description: DESCRIPTION,
-            public: false,
-            files,
-        };
+        let payload = create_payload(&snippets, self.config.gist_public);
         // Upload snippet files to Gist
-        let result = client.create_gist(&payload)?;
+        let result = client.create(&payload)?;
 
         // Make index file
         let mut index_file_content = String::from(INDEX_HEADING);
-        for snippet in &snippets {
-            make_index_line(&mut index_file_content, &result.html_url, snippet);
+        for snippet in snippets.iter().filter(|snippet| !snippet.private) {
+            make_index_line(
+                &mut index_file_content,
+                self.index_line_template(),
+                &result.html_url,
+                snippet,
+            )?;
         }
         let mut update_files = HashMap::new();
         update_files.insert(
@@ -268,7 +588,7 @@ impl TheWay {
             files: update_files,
         };
         // Upload index file to Gist
-        let result = client.update_gist(&result.id, &update_payload)?;
+        let result = client.update(&result.id, &update_payload)?;
         spinner.finish_with_message(utils::highlight_string(
             &format!(
                 "Created gist at {} with {} snippets",
@@ -278,57 +598,94 @@ impl TheWay {
             self.highlighter.main_style,
         ));
 
-        // Return created Gist ID
-        Ok(result.id)
+        // Return created Gist
+        Ok(result)
     }
 
-    /// Syncs local and Gist snippets according to user-selected source
-    pub(crate) fn sync_gist(
-        &mut self,
-        github_access_token: Option<&str>,
-        source: SyncCommand,
-        force: bool,
-    ) -> color_eyre::Result<()> {
-        // Retrieve local snippets
+    /// Uploads local snippets and an index file to an existing Gist not created by the-way,
+    /// so it can be synced from now on. Other files in the Gist are kept.
+    pub(crate) fn adopt_gist(
+        &self,
+        access_token: &str,
+        gist_id: &str,
+        overwrite: bool,
+    ) -> color_eyre::Result<Gist> {
+        let client = self.sync_client(access_token)?;
+        let spinner = utils::get_spinner("Adopting Gist...");
+        let gist = client.get(gist_id)?;
         let mut snippets = self.list_snippets()?;
-        if snippets.is_empty() && source == SyncCommand::Local {
-            self.color_print("No snippets to sync.\n")?;
-            return Ok(());
-        }
-        // Make client
-        let client = GistClient::new(github_access_token)?;
-
-        // Start sync
-        let spinner = utils::get_spinner("Syncing...");
+        snippets.retain(|snippet| !snippet.private);
+        let files = match Snippet::to_adopted_gist_files(
+            &snippets,
+            &gist,
+            self.index_line_template(),
+            overwrite,
+        ) {
+            Ok(files) => files,
+            Err(err) => {
+                spinner.finish_with_message("Error adopting gist.");
+                return Err(err);
+            }
+        };
+        let update_payload = UpdateGistPayload {
+            description: DESCRIPTION,
+            files: files
+                .iter()
+                .map(|(file_name, content)| {
+                    (
+                        file_name.clone(),
+                        Some(GistContent {
+                            content: content.as_str(),
+                        }),
+                    )
+                })
+                .collect(),
+        };
+        let result = client.update(gist_id, &update_payload)?;
+        spinner.finish_with_message(utils::highlight_string(
+            &format!(
+                "Adopted gist at {} with {} snippets",
+                result.html_url,
+                snippets.len()
+            ),
+            self.highlighter.main_style,
+        ));
+        Ok(result)
+    }
 
-        // Count each type of sync action
-        let mut action_counts = HashMap::new();
-        // Keep track of added and updated Gist files
-        let mut files = HashMap::new();
-        // Keep track of local snippets to add and delete
-        let mut add_snippets = Vec::new();
-        let mut delete_snippets = Vec::new();
+    /// Works out what syncing with `gist` would change, without changing anything
+    pub(crate) fn compute_sync_plan(
+        &self,
+        gist: &Gist,
+        source: &SyncCommand,
+    ) -> color_eyre::Result<SyncPlan> {
+        let mut plan = SyncPlan::default();
+        // Private snippets are left out, and kept locally when the Gist has a snippet at their index
+        let (private, snippets): (Vec<_>, Vec<_>) = self
+            .list_snippets()?
+            .into_iter()
+            .partition(|snippet| snippet.private);
+        let private_indices = private
+            .iter()
+            .map(|snippet| snippet.index)
+            .collect::<HashSet<_>>();
+        let local_indices = snippets
+            .iter()
+            .map(|snippet| snippet.index)
+            .collect::<HashSet<_>>();
+        let gist_snippets =
+            Snippet::from_the_way_gist(&self.languages, gist, self.index_line_template(), true)?
+                .0
+                .into_iter()
+                .map(|snippet| (snippet.index, snippet))
+                .collect::<HashMap<_, _>>();
+        plan.num_local = snippets.len();
+        plan.num_gist = gist_snippets.len();
         // Index file
         let mut index_file_content = String::from(INDEX_HEADING);
 
-        // Retrieve gist and gist snippets
-        let gist = client.get_gist(self.config.gist_id.as_ref().unwrap());
-        if gist.is_err() {
-            spinner.finish_with_message(utils::highlight_string(
-                "Gist not found.",
-                self.highlighter.main_style,
-            ));
-            self.config.gist_id = Some(self.make_gist(github_access_token.as_ref().unwrap())?);
-            return Ok(());
-        }
-        let gist = gist?;
-        let gist_snippets = Snippet::from_the_way_gist(&self.languages, &gist)?
-            .into_iter()
-            .map(|snippet| (snippet.index, snippet))
-            .collect::<HashMap<_, _>>();
-
         // Compare local snippets to gist snippets
-        for snippet in &mut snippets {
+        for snippet in &snippets {
             // if snippet is already present in gist:
             //     if source is Gist download changes to local snippet
             //     if source is Date and gist date is newer, download changes to local snippet
@@ -337,30 +694,29 @@ impl TheWay {
             // else
             //     if source is Local or Date, add snippet to gist
             //     if source is Gist, delete snippet from local
+            let mut synced_snippet = snippet;
             let sync_action = if let Some(gist_snippet) = gist_snippets.get(&snippet.index) {
                 // Snippets with same index exist in local and gist
                 if snippet == gist_snippet {
                     // No change
                     SyncAction::UpToDate
-                } else if source == SyncCommand::Local
-                    || (source == SyncCommand::Date && snippet.updated > gist.updated_at)
+                } else if *source == SyncCommand::Local
+                    || (*source == SyncCommand::Date && snippet.updated > gist.updated_at)
                 {
                     // Snippet updated locally or source is local => update Gist
-                    files.insert(
+                    plan.gist_files.insert(
                         format!("snippet_{}{}", snippet.index, snippet.extension),
-                        Some(GistContent {
-                            content: snippet.code.as_str(),
-                        }),
+                        Some(snippet.code.clone()),
                     );
                     SyncAction::Uploaded
-                } else if source == SyncCommand::Gist
-                    || (source == SyncCommand::Date && snippet.updated <= gist.updated_at)
+                } else if *source == SyncCommand::Gist
+                    || (*source == SyncCommand::Date && snippet.updated <= gist.updated_at)
                 {
                     // Snippet updated in Gist or source is Gist => update local snippet
-                    let index_key = gist_snippet.index.to_string();
-                    let index_key = index_key.as_bytes();
-                    self.add_to_snippet(index_key, &gist_snippet.to_bytes()?)?;
-                    *snippet = gist_snippet.clone();
+                    let mut downloaded = gist_snippet.clone();
+                    downloaded.favorite = snippet.favorite;
+                    plan.download.push(downloaded);
+                    synced_snippet = gist_snippet;
                     SyncAction::Downloaded
                 } else {
                     // Update dates match
@@ -371,26 +727,29 @@ impl TheWay {
                 match source {
                     SyncCommand::Local | SyncCommand::Date => {
                         // Snippet in local and not in gist => add to gist
-                        files.insert(
+                        plan.gist_files.insert(
                             format!("snippet_{}{}", snippet.index, snippet.extension),
-                            Some(GistContent {
-                                content: snippet.code.as_str(),
-                            }),
+                            Some(snippet.code.clone()),
                         );
                         SyncAction::AddedGist
                     }
                     SyncCommand::Gist => {
                         // Snippet deleted in gist => delete from local
-                        delete_snippets.push(snippet.index);
+                        plan.delete_local.push(snippet.index);
                         SyncAction::DeletedLocal
                     }
                 }
             };
             if sync_action != SyncAction::DeletedLocal {
                 // add snippet to index file
-                make_index_line(&mut index_file_content, &gist.html_url, snippet);
+                make_index_line(
+                    &mut index_file_content,
+                    self.index_line_template(),
+                    &gist.html_url,
+                    synced_snippet,
+                )?;
             }
-            *action_counts.entry(sync_action).or_insert(0) += 1;
+            *plan.action_counts.entry(sync_action).or_insert(0) += 1;
         }
         // Compare gist snippets to local snippets
         for file in gist.files.keys() {
@@ -399,79 +758,168 @@ impl TheWay {
                 // if snippet is not present locally:
                 //     if source is Local or Date, delete snippet from gist
                 //     if source is Gist add snippet to local snippets
-                if self.get_snippet(snippet_index).is_err() {
+                if !local_indices.contains(&snippet_index) {
                     let sync_action = match source {
                         SyncCommand::Local | SyncCommand::Date => {
                             // delete from Gist
-                            files.insert(file.clone(), None);
+                            plan.gist_files.insert(file.clone(), None);
                             SyncAction::DeletedGist
                         }
                         SyncCommand::Gist => {
-                            // add to local
                             let gist_snippet = gist_snippets.get(&snippet_index).ok_or(
                                 LostTheWay::GistFormattingError {
                                     message: format!("Invalid snippet index {snippet_index}"),
                                 },
                             )?;
-                            add_snippets.push(gist_snippet);
-                            // add snippet to index file
-                            make_index_line(&mut index_file_content, &gist.html_url, gist_snippet);
+                            // add snippet to index file, the Gist keeps it
+                            make_index_line(
+                                &mut index_file_content,
+                                self.index_line_template(),
+                                &gist.html_url,
+                                gist_snippet,
+                            )?;
+                            if private_indices.contains(&snippet_index) {
+                                continue;
+                            }
+                            // add to local
+                            plan.add_local.push(gist_snippet.clone());
                             SyncAction::AddedLocal
                         }
                     };
-                    *action_counts.entry(sync_action).or_insert(0) += 1;
+                    *plan.action_counts.entry(sync_action).or_insert(0) += 1;
                 }
             }
         }
-        // Update Gist
         if let Some(index_file) = gist.files.get("index.md") {
             if index_file.content != index_file_content {
-                files.insert(
-                    "index.md".to_owned(),
-                    Some(GistContent {
-                        content: index_file_content.as_str(),
-                    }),
-                );
+                plan.gist_files
+                    .insert("index.md".to_owned(), Some(index_file_content));
             }
         }
-        if !files.is_empty() {
-            client.update_gist(
-                &gist.id,
-                &UpdateGistPayload {
-                    description: DESCRIPTION,
-                    files,
-                },
-            )?;
+        Ok(plan)
+    }
+
+    /// Applies the local side of a sync plan: downloads, additions and deletions
+    pub(crate) fn apply_sync_plan_locally(&mut self, plan: &SyncPlan) -> color_eyre::Result<()> {
+        for snippet in &plan.download {
+            let index_key = snippet.index.to_string();
+            self.add_to_snippet(index_key.as_bytes(), &snippet.to_bytes()?)?;
         }
         let mut max_index = self.get_current_snippet_index()?;
-        for snippet in add_snippets {
+        for snippet in &plan.add_local {
             let index = self.add_snippet(snippet)?;
             if index > max_index {
                 max_index = index;
             }
         }
         self.modify_snippet_index(max_index)?;
-        spinner.finish_with_message("Done!");
-        let delete = if delete_snippets.is_empty() || force {
-            true
-        } else {
-            utils::confirm(
-                &format!("Delete {} snippets locally?", delete_snippets.len()),
-                false,
-            )?
-        };
-        if delete {
-            for index in delete_snippets {
-                self.delete_snippet(index)?;
+        for index in &plan.delete_local {
+            self.delete_snippet(*index)?;
+        }
+        Ok(())
+    }
+
+    /// Syncs local and Gist snippets according to user-selected source.
+    /// Shows what will change first, and asks before deleting anything unless `force` is set.
+    /// With `dry_run`, stops after showing what would change
+    pub(crate) fn sync_gist(
+        &mut self,
+        access_token: &str,
+        source: SyncCommand,
+        force: bool,
+        dry_run: bool,
+    ) -> color_eyre::Result<()> {
+        if source == SyncCommand::Local && self.list_snippets()?.is_empty() {
+            self.color_print("No snippets to sync.\n")?;
+            return Ok(());
+        }
+        // Make client
+        let client = self.sync_client(access_token)?;
+
+        // Retrieve gist and work out what to change
+        let spinner = utils::get_spinner("Fetching Gist...");
+        let gist = client.get(self.config.synced_id().unwrap());
+        if gist.is_err() {
+            spinner.finish_with_message(utils::highlight_string(
+                "Gist not found.",
+                self.highlighter.main_style,
+            ));
+            if dry_run {
+                return self.color_print(&format!(
+                    "Would create a new Gist with {} snippets\n",
+                    self.list_snippets()?.len()
+                ));
             }
+            let gist = self.make_gist(access_token)?;
+            self.config.set_synced(Some(gist.id), gist.html_url);
+            return Ok(());
         }
+        let gist = gist?;
+        self.config.set_synced(None, gist.html_url.clone());
+        let plan = self.compute_sync_plan(&gist, &source)?;
+        spinner.finish_and_clear();
 
-        // Print results
-        for (action, count) in action_counts {
-            if action == SyncAction::DeletedLocal && !delete {
-                continue;
+        self.color_print(&format!("{}\n", plan.summary()))?;
+        if dry_run {
+            for line in plan.action_lines() {
+                self.color_print(&format!("{line}\n"))?;
             }
-            self.color_print(&format!("{count} snippet(s) {action}\n"))?;
+            return Ok(());
+        }
+        if !force {
+            // Guard against wiping the Gist from a (nearly) empty local database
+            if source == SyncCommand::Local && plan.wipes_gist() {
+                let prompt = format!(
+                    "This will delete {} snippets from the Gist — continue?",
+                    plan.count(SyncAction::DeletedGist)
+                );
+                if !utils::confirm(&prompt, false)? {
+                    let error: color_eyre::Result<()> = Err(LostTheWay::DoingNothing.into());
+                    return error.suggestion(
+                        "Use `the-way sync gist` to restore local snippets from the Gist, \
+                        or --force to skip this check",
+                    );
+                }
+            } else if plan.count(SyncAction::DeletedGist) > 0 || !plan.delete_local.is_empty() {
+                let prompt = format!(
+                    "Delete {} snippets from the Gist and {} snippets locally?",
+                    plan.count(SyncAction::DeletedGist),
+                    plan.delete_local.len()
+                );
+                if !utils::confirm(&prompt, false)? {
+                    let error: color_eyre::Result<()> = Err(LostTheWay::DoingNothing.into());
+                    return error.suggestion("Use --force to skip this check");
+                }
+            }
+        }
+
+        // Start sync
+        let spinner = utils::get_spinner("Syncing...");
+        if !plan.gist_files.is_empty() {
+            let files = plan
+                .gist_files
+                .iter()
+                .map(|(file, content)| {
+                    (
+                        file.clone(),
+                        content.as_deref().map(|content| GistContent { content }),
+                    )
+                })
+                .collect();
+            client.update(
+                &gist.id,
+                &UpdateGistPayload {
+                    description: DESCRIPTION,
+                    files,
+                },
+            )?;
+        }
+        self.apply_sync_plan_locally(&plan)?;
+        spinner.finish_with_message("Done!");
+
+        // Print results
+        for line in plan.action_lines() {
+            self.color_print(&format!("{line}\n"))?;
         }
         self.color_print(&format!("\nGist: {}\n", gist.html_url))?;
         Ok(())
@@ -489,7 +937,7 @@ fn get_gist_snippet_index(file: &str) -> color_eyre::Result<usize> {
         })
         .suggestion(suggestion)?
         .split('_')
-        .last()
+        .next_back()
         .ok_or(LostTheWay::GistFormattingError {
             message: format!("Invalid filename {file}: No _"),
         })
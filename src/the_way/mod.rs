@@ -1,28 +1,47 @@
 //! CLI code
-use std::collections::HashMap;
-use std::io::{ErrorKind, Write};
-use std::path::Path;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, ErrorKind, IsTerminal, Write};
+use std::path::{Path, PathBuf};
 use std::{fs, io, process};
 
+use chrono::Utc;
 use clap::CommandFactory;
 use clap_complete::Shell;
 use color_eyre::Help;
 use dialoguer::theme::ColorfulTheme;
-use dialoguer::{Confirm, Select};
+use dialoguer::{Confirm, FuzzySelect, Select};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
 
-use crate::configuration::{ConfigCommand, TheWayConfig};
+use crate::configuration::{ConfigCommand, SyncBackend, TheWayConfig};
 use crate::errors::LostTheWay;
-use crate::language::{CodeHighlight, Language};
+use crate::gitlab::GITLAB_URL;
+use crate::language::{self, CodeHighlight, Language};
 use crate::the_way::{
-    cli::{SyncCommand, TheWayCLI, TheWaySubcommand, ThemeCommand},
+    cli::{
+        ExportFormat, GistCommand, ImportFormat, LanguagesCommand, SortBy, SyncCommand,
+        TagsCommand, TheWayCLI, TheWaySubcommand, ThemeCommand,
+    },
+    database::is_database_entry,
     filter::Filters,
-    snippet::Snippet,
+    snippet::{GivenFields, Snippet},
 };
+use crate::token_store::{self, Keyring};
 use crate::utils;
 
+/// First bytes of a gzip stream
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+/// Most bytes read from a single zip archive entry, so a zip bomb can't exhaust memory
+const MAX_ZIP_ENTRY_BYTES: u64 = 16 * 1024 * 1024;
+/// Suggestion for snippets larger than `max_code_bytes`
+const CODE_SIZE_SUGGESTION: &str = "Consider storing large files in a Gist instead";
+
+mod bundle;
 pub mod cli;
 mod database;
-mod filter;
+pub mod filter;
 mod gist;
 mod search;
 pub mod snippet;
@@ -44,148 +63,523 @@ pub struct TheWay {
     colorize: bool,
     /// don't colorize output even if terminal is in tty mode
     plain: bool,
+    /// don't shorten snippet descriptions to fit the terminal width
+    no_truncate: bool,
+    /// fail instead of warning on checksum mismatches
+    strict_checksums: bool,
 }
 
 pub enum ListType {
-    Snippet,
     Tag,
     Language,
 }
 
+/// How to treat snippets imported from a file or Gist
+struct ImportOptions<'a> {
+    /// Extra tags added to each snippet
+    tags: &'a [String],
+    /// Input format, guessed if not given (file imports)
+    format: Option<ImportFormat>,
+    /// Guess the language of plain text snippets (file imports)
+    detect_language: bool,
+    /// Import snippets larger than `max_code_bytes` (file imports)
+    force: bool,
+    /// Fail on malformed file names (the-way Gist imports)
+    strict: bool,
+    /// Leave out snippets that are already stored (file imports)
+    skip_duplicates: bool,
+    /// Only print what would be imported
+    dry_run: bool,
+}
+
+/// Import format from a file's extension (looking past a trailing .gz), if it's a known one
+fn import_format(file: &Path) -> Option<ImportFormat> {
+    let file = match file.extension() {
+        Some(extension) if extension == "gz" => Path::new(file.file_stem()?),
+        _ => file,
+    };
+    match file.extension()?.to_str()? {
+        "json" => Some(ImportFormat::Json),
+        "yaml" | "yml" => Some(ImportFormat::Yaml),
+        "toml" => Some(ImportFormat::Toml),
+        _ => None,
+    }
+}
+
+/// Sorts snippets by index, date or language, snippets sharing a date or language stay in index order.
+/// Favorites come first either way
+fn sort_snippets(snippets: &mut [Snippet], sort_by: SortBy, reverse: bool) {
+    snippets.sort_by(|a, b| {
+        let order = match sort_by {
+            SortBy::Index => a.index.cmp(&b.index),
+            SortBy::Date => a.date.cmp(&b.date),
+            SortBy::Language => a.language.cmp(&b.language),
+        };
+        let order = if reverse { order.reverse() } else { order };
+        b.favorite
+            .cmp(&a.favorite)
+            .then(order)
+            .then(a.index.cmp(&b.index))
+    });
+}
+
+/// Tags or languages with their counts, most common first and alphabetically among equals
+fn sort_counts(object_to_count: &HashMap<String, usize>) -> Vec<(&String, &usize)> {
+    let mut objects = object_to_count.iter().collect::<Vec<_>>();
+    objects.sort_by(|(a_name, a), (b_name, b)| b.cmp(a).then(a_name.cmp(b_name)));
+    objects
+}
+
 // All command-line related functions
 impl TheWay {
     /// Initialize program with command line input.
     /// Reads `sled` trees and metadata file from the locations specified in config.
     /// (makes new ones the first time).
     pub fn start(cli: TheWayCLI, languages: HashMap<String, Language>) -> color_eyre::Result<()> {
-        if let TheWaySubcommand::Config {
-            cmd: ConfigCommand::Default { file },
-        } = &cli.cmd
-        {
-            TheWayConfig::default_config(file.as_deref())?;
-            return Ok(());
+        env_logger::Builder::new()
+            .filter_module("the_way", cli.log_level())
+            .format_timestamp(None)
+            .init();
+        let cmd = cli.cmd;
+        match &cmd {
+            TheWaySubcommand::Config {
+                cmd: ConfigCommand::Default { file, force },
+            } => {
+                TheWayConfig::default_config(file.as_deref(), *force)?;
+                return Ok(());
+            }
+            TheWaySubcommand::Config {
+                cmd: ConfigCommand::Get,
+            } if cli.verbose > 0 => {
+                return TheWayConfig::load(cli.config.as_deref())?.print_locations();
+            }
+            _ => {}
         }
 
-        let config = TheWayConfig::load()?;
-        let mut the_way = Self {
+        let mut the_way = Self::new(TheWayConfig::load(cli.config.as_deref())?, languages)?;
+        the_way.colorize = cli.colorize;
+        the_way.plain = cli.plain || cli.plain_short;
+        the_way.no_truncate = cli.no_truncate;
+        the_way.strict_checksums = cli.strict_checksums;
+        the_way.run(cmd)?;
+        Ok(())
+    }
+
+    /// Opens the snippet database and highlighter for the given configuration,
+    /// for using the-way as a library
+    pub fn new(
+        config: TheWayConfig,
+        mut languages: HashMap<String, Language>,
+    ) -> color_eyre::Result<Self> {
+        language::set_language_colors(&mut languages, &config.language_colors)?;
+        let the_way = Self {
             db: Self::get_db(&config.db_dir)?,
             languages,
             highlighter: CodeHighlight::new(&config.theme, config.themes_dir.clone())?,
             config,
-            colorize: cli.colorize,
-            plain: cli.plain,
+            colorize: false,
+            plain: false,
+            no_truncate: false,
+            strict_checksums: false,
         };
         the_way.set_merge()?;
-        the_way.run(cli)?;
-        Ok(())
+        the_way.migrate_unversioned_snippets()?;
+        the_way.lowercase_tag_keys()?;
+        Ok(the_way)
     }
 
-    fn run(&mut self, cli: TheWayCLI) -> color_eyre::Result<()> {
-        self.colorize = cli.colorize;
-        self.plain = cli.plain;
-        match cli.cmd {
-            TheWaySubcommand::New => self.the_way(),
-            TheWaySubcommand::Cmd { code } => self.the_way_cmd(code),
+    fn run(&mut self, cmd: TheWaySubcommand) -> color_eyre::Result<()> {
+        match cmd {
+            TheWaySubcommand::New {
+                description,
+                language,
+                tags,
+                pick_tags,
+                code,
+                file,
+                private,
+            } => {
+                let code = match file {
+                    Some(file) => Some(fs::read_to_string(file)?),
+                    None => code,
+                };
+                let given = GivenFields {
+                    description,
+                    language,
+                    tags: tags.map(|tags| tags.join(" ")),
+                    code,
+                };
+                self.the_way(given, pick_tags, private)
+            }
+            TheWaySubcommand::Cmd {
+                code,
+                description,
+                tags,
+            } => self.the_way_cmd(code, description, tags),
             TheWaySubcommand::Search {
                 filters,
                 exact,
+                fuzzy,
                 stdout,
                 force,
+                read_only,
             } => self.search(
                 &filters,
-                search::SearchOptions::new(search::SkimCommand::All, exact, stdout, force),
+                search::SearchOptions {
+                    command: search::SkimCommand::All,
+                    exact: self.exact_search(exact, fuzzy),
+                    stdout,
+                    force,
+                    read_only,
+                    ..Default::default()
+                },
             ),
             TheWaySubcommand::Cp {
-                index,
+                indices,
                 filters,
                 exact,
+                fuzzy,
                 stdout,
-            } => match index {
-                Some(index) => self.copy(index, stdout),
-                None => self.search(
+                with_header,
+                no_fill,
+            } => match indices.as_slice() {
+                [_, ..] => {
+                    let indices = self.find_snippets(&indices)?;
+                    let snippets = self.get_snippets(&indices)?;
+                    self.copy_snippets(snippets, stdout, with_header, no_fill)
+                }
+                [] if !filters.is_empty() => {
+                    self.copy_filtered(&filters, stdout, with_header, no_fill)
+                }
+                [] => self.search(
                     &filters,
-                    search::SearchOptions::new(search::SkimCommand::Copy, exact, stdout, false),
+                    search::SearchOptions {
+                        command: search::SkimCommand::Copy,
+                        exact: self.exact_search(exact, fuzzy),
+                        stdout,
+                        with_header,
+                        no_fill,
+                        ..Default::default()
+                    },
                 ),
             },
             TheWaySubcommand::Edit {
-                index,
+                indices,
                 filters,
                 exact,
-            } => match index {
-                Some(index) => self.edit(index),
-                None => self.search(
-                    &filters,
-                    search::SearchOptions::new(search::SkimCommand::Edit, exact, false, false),
-                ),
-            },
+                fuzzy,
+                add_tags,
+                remove_tags,
+                language,
+                pick_tags,
+                code_only,
+            } => {
+                if indices.is_empty() {
+                    return self.search(
+                        &filters,
+                        search::SearchOptions {
+                            command: search::SkimCommand::Edit,
+                            exact: self.exact_search(exact, fuzzy),
+                            ..Default::default()
+                        },
+                    );
+                }
+                let indices = indices
+                    .iter()
+                    .map(|index| self.find_snippet(index))
+                    .collect::<color_eyre::Result<Vec<_>>>()?;
+                if !add_tags.is_empty() || !remove_tags.is_empty() || language.is_some() {
+                    return self.edit_metadata(
+                        &indices,
+                        &add_tags,
+                        &remove_tags,
+                        language.as_deref(),
+                    );
+                }
+                for index in indices {
+                    self.edit(index, pick_tags, code_only)?;
+                }
+                Ok(())
+            }
+            TheWaySubcommand::Scaffold {
+                index,
+                out,
+                values,
+                force,
+            } => {
+                let index = self.find_snippet(&index)?;
+                self.scaffold(index, out.as_deref(), values.into_iter().collect(), force)
+            }
+            TheWaySubcommand::Star { index } => {
+                let index = self.find_snippet(&index)?;
+                self.set_favorite(index, true)
+            }
+            TheWaySubcommand::Unstar { index } => {
+                let index = self.find_snippet(&index)?;
+                self.set_favorite(index, false)
+            }
+            TheWaySubcommand::Private { index } => {
+                let index = self.find_snippet(&index)?;
+                self.toggle_private(index)
+            }
+            TheWaySubcommand::Move { from, to } => {
+                let from = self.find_snippet(&from)?;
+                self.move_snippet(from, to)
+            }
             TheWaySubcommand::Del {
                 index,
                 filters,
                 exact,
+                fuzzy,
                 force,
             } => match index {
-                Some(index) => self.delete(index, force),
+                Some(index) => {
+                    let index = self.find_snippet(&index)?;
+                    self.delete(index, force)
+                }
                 None => self.search(
                     &filters,
-                    search::SearchOptions::new(search::SkimCommand::Delete, exact, false, force),
+                    search::SearchOptions {
+                        command: search::SkimCommand::Delete,
+                        exact: self.exact_search(exact, fuzzy),
+                        force,
+                        ..Default::default()
+                    },
                 ),
             },
             TheWaySubcommand::View {
-                index,
+                indices,
                 filters,
                 exact,
-            } => match index {
-                Some(index) => self.view(index),
-                None => self.search(
-                    &filters,
-                    search::SearchOptions::new(search::SkimCommand::View, exact, false, false),
-                ),
-            },
-            TheWaySubcommand::List { filters } => self.list(&filters, ListType::Snippet),
+                fuzzy,
+                related,
+                oneline,
+                dates,
+                raw,
+            } => {
+                if indices.is_empty() {
+                    return self.search(
+                        &filters,
+                        search::SearchOptions {
+                            command: search::SkimCommand::View,
+                            exact: self.exact_search(exact, fuzzy),
+                            ..Default::default()
+                        },
+                    );
+                }
+                for index in self.find_snippets(&indices)? {
+                    if raw {
+                        self.view_raw(index)?;
+                    } else if oneline {
+                        self.view_oneline(index)?;
+                    } else {
+                        self.view(index, related, dates)?;
+                    }
+                }
+                Ok(())
+            }
+            TheWaySubcommand::List {
+                filters,
+                porcelain,
+                sort,
+                reverse,
+            } => {
+                let mut snippets = self.filter_snippets(&filters)?;
+                sort_snippets(&mut snippets, sort, reverse);
+                if porcelain {
+                    self.list_porcelain(&snippets)
+                } else {
+                    self.show_snippets(&snippets)
+                }
+            }
             TheWaySubcommand::Import {
                 file,
+                format,
                 gist_url,
+                gist_file,
                 the_way_url,
-            } => self.import(file.as_deref(), gist_url, the_way_url),
-            TheWaySubcommand::Export { filters, file } => self.export(&filters, file.as_deref()),
+                bundle,
+                tags,
+                detect_language,
+                force,
+                strict,
+                skip_duplicates,
+                dry_run,
+            } => match bundle {
+                Some(bundle) => {
+                    let num = self.import_bundle(&bundle, force)?;
+                    self.color_print(&format!("Imported {num} snippets\n"))
+                }
+                None => self.import(
+                    file.as_deref(),
+                    &gist_url,
+                    &gist_file,
+                    the_way_url,
+                    &ImportOptions {
+                        tags: &tags,
+                        format,
+                        detect_language,
+                        force,
+                        strict,
+                        skip_duplicates,
+                        dry_run,
+                    },
+                ),
+            },
+            TheWaySubcommand::Export {
+                filters,
+                file,
+                bundle,
+                format,
+                delimiter,
+                with_header,
+            } => match bundle {
+                Some(bundle) => self.export_bundle(&filters, &bundle),
+                None => self.export(&filters, file.as_deref(), format, &delimiter, with_header),
+            },
             TheWaySubcommand::Complete { shell } => {
                 Self::complete(shell);
                 Ok(())
             }
             TheWaySubcommand::Themes { cmd } => self.themes(cmd),
-            TheWaySubcommand::Clear { force } => self.clear(force),
+            TheWaySubcommand::Reindex => self.reindex(),
+            TheWaySubcommand::Clear {
+                force,
+                force_unsafe,
+            } => self.clear(force, force_unsafe),
             TheWaySubcommand::Config { cmd } => match cmd {
-                ConfigCommand::Default { file } => TheWayConfig::default_config(file.as_deref()), //Already handled
-                ConfigCommand::Get => TheWayConfig::print_config_location(),
+                ConfigCommand::Default { file, force } => {
+                    TheWayConfig::default_config(file.as_deref(), force) //Already handled
+                }
+                ConfigCommand::Get => self.config.print_config_location(),
             },
-            TheWaySubcommand::Sync { cmd, force } => self.sync(cmd, force),
-            TheWaySubcommand::Tags { filters } => self.list(&filters, ListType::Tag),
-            TheWaySubcommand::Languages { filters } => self.list(&filters, ListType::Language),
+            TheWaySubcommand::Sync {
+                cmd,
+                force,
+                open,
+                dry_run,
+                adopt,
+                overwrite,
+                url,
+            } => match (cmd, adopt) {
+                (None, None) if url => self.print_gist_url(),
+                (None, Some(gist_id)) => self.adopt(&gist_id, overwrite, open),
+                (Some(cmd), None) if !url => self.sync(cmd, force, open, dry_run),
+                _ => {
+                    let error: color_eyre::Result<()> = Err(LostTheWay::SyncError {
+                        message: "Choose one of a sync source, --adopt or --url".into(),
+                    }
+                    .into());
+                    error.suggestion(
+                        "Use `the-way sync date|local|gist`, `the-way sync --adopt <gist_id>`, \
+                        or `the-way sync --url`",
+                    )
+                }
+            },
+            TheWaySubcommand::Gist { cmd } => match cmd {
+                GistCommand::Open { print } => self.open_gist(print),
+            },
+            TheWaySubcommand::Tags { cmd, filters, json } => match cmd {
+                Some(TagsCommand::Normalize) => self.normalize_tags(),
+                Some(TagsCommand::Rename { old, new }) => self.rename_tag(&old, &new),
+                None if json => {
+                    self.show_counts_json(self.count_snippets(&filters, &ListType::Tag)?)
+                }
+                None => self.list(&filters, ListType::Tag),
+            },
+            TheWaySubcommand::Languages {
+                cmd,
+                filters,
+                supported,
+                json,
+            } => {
+                if let Some(LanguagesCommand::SetColor { language, color }) = cmd {
+                    self.set_language_color(&language, &color)
+                } else if supported {
+                    self.list_supported_languages()
+                } else if json {
+                    self.show_counts_json(self.count_snippets(&filters, &ListType::Language)?)
+                } else {
+                    self.list(&filters, ListType::Language)
+                }
+            }
         }
     }
 
     /// Adds a new snippet
-    fn the_way(&mut self) -> color_eyre::Result<()> {
-        let snippet = Snippet::from_user(
+    fn the_way(
+        &mut self,
+        given: GivenFields,
+        pick_tags: bool,
+        private: bool,
+    ) -> color_eyre::Result<()> {
+        let mut snippet = Snippet::from_user(
             self.get_current_snippet_index()? + 1,
             &self.languages,
             self.list_tags()?,
             self.list_languages()?,
             None,
+            given,
+            self.pick_tags(pick_tags),
         )?;
+        snippet.private = private;
+        self.check_syntax(&snippet);
+        self.warn_code_size(&snippet);
         let index = self.add_snippet(&snippet)?;
         self.color_print(&format!("Snippet #{index} added\n"))?;
         self.increment_snippet_index()?;
         Ok(())
     }
 
+    /// Warns if a snippet's language can't be syntax highlighted
+    pub(crate) fn check_syntax(&self, snippet: &Snippet) {
+        if !self
+            .highlighter
+            .has_syntax(&snippet.language, &snippet.extension)
+        {
+            log::warn!(
+                "No syntax highlighting available for {}. \
+                Use `the-way themes language <file.sublime-syntax>` to add it.",
+                snippet.language
+            );
+        }
+    }
+
+    /// Checks a snippet's code against the `max_code_bytes` configuration option
+    fn check_code_size(&self, snippet: &Snippet) -> color_eyre::Result<()> {
+        match self.config.max_code_bytes {
+            Some(max) if snippet.code.len() > max => Err(LostTheWay::SnippetTooLarge {
+                description: snippet.description.clone(),
+                size: snippet.code.len(),
+                max,
+            })
+            .suggestion(format!("{CODE_SIZE_SUGGESTION}, or use --force")),
+            _ => Ok(()),
+        }
+    }
+
+    /// Warns if a snippet's code is larger than `max_code_bytes`
+    fn warn_code_size(&self, snippet: &Snippet) {
+        if let Err(e) = self.check_code_size(snippet) {
+            log::warn!("{}. {CODE_SIZE_SUGGESTION}.", e.root_cause());
+        }
+    }
+
     /// Adds a new shell snippet
-    fn the_way_cmd(&mut self, code: Option<String>) -> color_eyre::Result<()> {
+    fn the_way_cmd(
+        &mut self,
+        code: Option<String>,
+        description: Option<String>,
+        tags: Option<Vec<String>>,
+    ) -> color_eyre::Result<()> {
         let snippet = Snippet::cmd_from_user(
             self.get_current_snippet_index()? + 1,
             code.as_deref(),
+            description,
+            tags.map(|tags| tags.join(" ")),
             self.list_tags()?,
         )?;
+        self.warn_code_size(&snippet);
         let index = self.add_snippet(&snippet)?;
         self.color_print(&format!("Snippet #{index} added\n"))?;
         self.increment_snippet_index()?;
@@ -196,7 +590,7 @@ impl TheWay {
     fn delete(&mut self, index: usize, force: bool) -> color_eyre::Result<()> {
         if force
             || Confirm::with_theme(&ColorfulTheme::default())
-                .with_prompt(&format!("Delete snippet #{index}?\n"))
+                .with_prompt(format!("Delete snippet #{index}?\n"))
                 .default(false)
                 .interact()?
         {
@@ -209,43 +603,289 @@ impl TheWay {
         }
     }
 
-    /// Modify a stored snippet's information
-    fn edit(&mut self, index: usize) -> color_eyre::Result<()> {
+    /// Moves a snippet to a new, unused index, keeping everything else about it
+    fn move_snippet(&mut self, from: usize, to: usize) -> color_eyre::Result<()> {
+        if from == to {
+            return Ok(());
+        }
+        if self.snippet_exists(to)? {
+            let error: color_eyre::Result<()> = Err(LostTheWay::SnippetExists { index: to }.into());
+            return error.suggestion(format!(
+                "Move snippet #{to} out of the way first, or delete it with `the-way del {to}`"
+            ));
+        }
+        let mut snippet = self.get_snippet(from)?;
+        snippet.index = to;
+        self.replace_snippets(&[from], &[snippet])?;
+        // Keep new snippets from landing on the moved one
+        if to > self.get_current_snippet_index()? {
+            self.modify_snippet_index(to)?;
+        }
+        self.color_print(&format!("Snippet #{from} moved to #{to}\n"))?;
+        Ok(())
+    }
+
+    /// Modify a stored snippet's information, or only its code in the external editor
+    fn edit(&mut self, index: usize, pick_tags: bool, code_only: bool) -> color_eyre::Result<()> {
         let old_snippet = self.get_snippet(index)?;
-        let new_snippet = Snippet::from_user(
+        if code_only {
+            let mut new_snippet = old_snippet.clone();
+            new_snippet.code =
+                utils::external_editor_input(Some(&old_snippet.code), &old_snippet.extension)?;
+            if new_snippet.code.trim().is_empty() {
+                let error: color_eyre::Result<()> = Err(LostTheWay::EmptyCode.into());
+                return error.suggestion("Close the editor without saving to cancel instead");
+            }
+            new_snippet.updated = Utc::now();
+            self.warn_code_size(&new_snippet);
+            self.replace_snippets(&[index], &[new_snippet])?;
+            self.color_print(&format!("Snippet #{index} changed\n"))?;
+            return Ok(());
+        }
+        let mut new_snippet = Snippet::from_user(
             index,
             &self.languages,
             self.list_tags()?,
             self.list_languages()?,
             Some(&old_snippet),
+            GivenFields::default(),
+            self.pick_tags(pick_tags),
         )?;
-        self.delete_snippet(index)?;
-        self.add_snippet(&new_snippet)?;
+        new_snippet.favorite = old_snippet.favorite;
+        new_snippet.private = old_snippet.private;
+        if new_snippet.language != old_snippet.language {
+            self.check_syntax(&new_snippet);
+        }
+        self.warn_code_size(&new_snippet);
+        self.replace_snippets(&[index], &[new_snippet])?;
         self.color_print(&format!("Snippet #{index} changed\n"))?;
         Ok(())
     }
 
+    /// Changes snippets' tags and/or language without prompting, in one transaction
+    fn edit_metadata(
+        &mut self,
+        indices: &[usize],
+        add_tags: &[String],
+        remove_tags: &[String],
+        language: Option<&str>,
+    ) -> color_eyre::Result<()> {
+        let mut indices = indices.to_vec();
+        indices.sort_unstable();
+        indices.dedup();
+        let mut snippets = self.get_snippets(&indices)?;
+        for snippet in &mut snippets {
+            snippet.add_tags(add_tags);
+            snippet.tags.retain(|tag| {
+                !remove_tags
+                    .iter()
+                    .any(|remove_tag| remove_tag.to_lowercase() == tag.to_lowercase())
+            });
+            if let Some(language) = language {
+                // Languages are stored in lowercase, like when adding snippets
+                let language = language.to_lowercase();
+                snippet.set_extension(&language, &self.languages);
+                snippet.language = language;
+                self.check_syntax(snippet);
+            }
+            snippet.updated = Utc::now();
+        }
+        self.replace_snippets(&indices, &snippets)?;
+        for index in indices {
+            self.color_print(&format!("Snippet #{index} changed\n"))?;
+        }
+        Ok(())
+    }
+
+    /// Stars or unstars a snippet
+    fn set_favorite(&mut self, index: usize, favorite: bool) -> color_eyre::Result<()> {
+        let mut snippet = self.get_snippet(index)?;
+        snippet.favorite = favorite;
+        self.add_to_snippet(index.to_string().as_bytes(), &snippet.to_bytes()?)?;
+        let action = if favorite { "starred" } else { "unstarred" };
+        self.color_print(&format!("Snippet #{index} {action}\n"))?;
+        Ok(())
+    }
+
+    /// Makes a snippet private (never synced) or syncable again
+    fn toggle_private(&mut self, index: usize) -> color_eyre::Result<()> {
+        let mut snippet = self.get_snippet(index)?;
+        snippet.private = !snippet.private;
+        self.add_to_snippet(index.to_string().as_bytes(), &snippet.to_bytes()?)?;
+        let status = if snippet.private {
+            "is private, it won't be synced"
+        } else {
+            "isn't private anymore, it will be synced"
+        };
+        self.color_print(&format!("Snippet #{index} {status}\n"))?;
+        Ok(())
+    }
+
+    /// Gives snippets sequential indices starting at 1, keeping their order
+    fn reindex(&mut self) -> color_eyre::Result<()> {
+        let mut snippets = self.list_snippets()?;
+        snippets.sort_by_key(|snippet| snippet.index);
+        let num_moved = snippets
+            .iter()
+            .enumerate()
+            .filter(|(i, snippet)| snippet.index != i + 1)
+            .count();
+        let indices = snippets
+            .iter()
+            .map(|snippet| snippet.index)
+            .collect::<Vec<_>>();
+        for (i, snippet) in snippets.iter_mut().enumerate() {
+            snippet.index = i + 1;
+        }
+        self.replace_snippets(&indices, &snippets)?;
+        self.modify_snippet_index(snippets.len())?;
+        self.color_print(&format!("Reindexed {num_moved} snippets\n"))?;
+        if num_moved > 0 && self.config.synced_id().is_some() {
+            self.color_eprint(
+                "Warning: the synced Gist still has the old indices. Run `the-way sync local` to update it.\n",
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Overrides a language's box color in the configuration file
+    fn set_language_color(&mut self, language: &str, color: &str) -> color_eyre::Result<()> {
+        Language::parse_color(color)?;
+        self.config
+            .language_colors
+            .insert(language.to_lowercase(), color.to_owned());
+        self.config.store()?;
+        self.color_print(&format!("Set color of {language} to {color}\n"))?;
+        Ok(())
+    }
+
+    /// Lowercases the tags of all snippets, in one transaction
+    fn normalize_tags(&mut self) -> color_eyre::Result<()> {
+        let mut snippets = self.list_snippets()?;
+        snippets.retain_mut(|snippet| {
+            let tags = snippet.tags.clone();
+            snippet.lowercase_tags();
+            snippet.tags != tags
+        });
+        let indices = snippets
+            .iter()
+            .map(|snippet| snippet.index)
+            .collect::<Vec<_>>();
+        self.replace_snippets(&indices, &snippets)?;
+        self.color_print(&format!("Normalized tags of {} snippets\n", snippets.len()))?;
+        Ok(())
+    }
+
+    /// Renames a tag across all snippets, in one transaction
+    fn rename_tag(&mut self, old: &str, new: &str) -> color_eyre::Result<()> {
+        let mut indices = self.get_tag_snippets(old)?;
+        indices.sort_unstable();
+        indices.dedup();
+        let mut snippets = self.get_snippets(&indices)?;
+        for snippet in &mut snippets {
+            snippet.rename_tag(old, new);
+        }
+        self.replace_snippets(&indices, &snippets)?;
+        self.color_print(&format!(
+            "Renamed tag {old:?} to {new:?} on {} snippets\n",
+            indices.len()
+        ))?;
+        Ok(())
+    }
+
     /// Pretty prints a snippet to terminal
-    fn view(&self, index: usize) -> color_eyre::Result<()> {
+    fn view(&self, index: usize, related: Option<usize>, dates: bool) -> color_eyre::Result<()> {
         let snippet = self.get_snippet(index)?;
-        utils::smart_print(
-            &snippet.pretty_print(
-                &self.highlighter,
-                self.languages
-                    .get(&snippet.language)
-                    .unwrap_or(&Language::default()),
-            )?,
-            false,
-            self.colorize,
-            self.plain,
+        let mut printer = utils::SmartPrinter::new(self.colorize, self.plain);
+        snippet.pretty_print_streaming(
+            &self.highlighter,
+            self.languages
+                .get(&snippet.language)
+                .unwrap_or(&Language::default()),
+            self.header_width(),
+            dates,
+            &mut printer,
         )?;
+        if let Some(num) = related {
+            let related = self.related_snippets(&snippet, num)?;
+            if !related.is_empty() {
+                printer.print(&[(self.highlighter.accent_style, "Related:\n")])?;
+                for other in related {
+                    printer.print(
+                        &other.pretty_print_header(
+                            &self.highlighter,
+                            self.languages
+                                .get(&other.language)
+                                .unwrap_or(&Language::default()),
+                            self.header_width(),
+                        ),
+                    )?;
+                }
+            }
+        }
         Ok(())
     }
 
-    /// Copy a snippet to clipboard
-    fn copy(&self, index: usize, to_stdout: bool) -> color_eyre::Result<()> {
+    /// Prints a one-line summary of a snippet, for scripts and shell prompts
+    fn view_oneline(&self, index: usize) -> color_eyre::Result<()> {
         let snippet = self.get_snippet(index)?;
-        let code = snippet.fill_snippet(self.highlighter.selection_style)?;
+        println!(
+            "#{} {} ({})",
+            snippet.index, snippet.description, snippet.language
+        );
+        Ok(())
+    }
+
+    /// Prints a snippet's code exactly as stored, for piping into other programs
+    fn view_raw(&self, index: usize) -> color_eyre::Result<()> {
+        let snippet = self.get_snippet(index)?;
+        // See https://github.com/rust-lang/rust/issues/46016
+        if let Err(e) = io::stdout().write_all(snippet.code.as_bytes()) {
+            if e.kind() != ErrorKind::BrokenPipe {
+                return Err(e.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Up to `num` other snippets sharing the most tags with `snippet` (ties broken by index)
+    fn related_snippets(&self, snippet: &Snippet, num: usize) -> color_eyre::Result<Vec<Snippet>> {
+        let mut shared_tags: HashMap<usize, usize> = HashMap::new();
+        for tag in snippet.tags.iter().collect::<HashSet<_>>() {
+            for index in self.get_tag_snippets(tag)? {
+                if index != snippet.index {
+                    *shared_tags.entry(index).or_default() += 1;
+                }
+            }
+        }
+        let mut shared_tags: Vec<_> = shared_tags.into_iter().collect();
+        shared_tags.sort_by(|(index_1, count_1), (index_2, count_2)| {
+            count_2.cmp(count_1).then(index_1.cmp(index_2))
+        });
+        shared_tags
+            .into_iter()
+            .take(num)
+            .map(|(index, _)| self.get_snippet(index))
+            .collect()
+    }
+
+    /// Copy a snippet to clipboard, filling shell parameters unless `no_fill` is set
+    fn copy(
+        &self,
+        index: usize,
+        to_stdout: bool,
+        with_header: bool,
+        no_fill: bool,
+    ) -> color_eyre::Result<()> {
+        let snippet = self.get_snippet(index)?;
+        let mut code = if no_fill {
+            Cow::Borrowed(snippet.code.as_str())
+        } else {
+            snippet.fill_snippet(self.highlighter.selection_style)?
+        };
+        if with_header {
+            code = Cow::Owned(format!("{}{code}", snippet.comment_header()));
+        }
         if to_stdout {
             // See https://github.com/rust-lang/rust/issues/46016
             if let Err(e) = writeln!(io::stdout(), "{code}") {
@@ -256,41 +896,262 @@ impl TheWay {
             }
         } else {
             utils::copy_to_clipboard(&self.config.copy_cmd, &code)?;
-            eprintln!(
-                "{}",
-                utils::highlight_string(
-                    &format!("Snippet #{index} copied to clipboard\n"),
-                    self.highlighter.main_style
-                )
-            );
+            self.color_eprint(&format!("Snippet #{index} copied to clipboard\n"))?;
+        }
+        Ok(())
+    }
+
+    /// Fills in a template snippet and writes it to a file or stdout
+    fn scaffold(
+        &self,
+        index: usize,
+        out: Option<&Path>,
+        values: HashMap<String, String>,
+        force: bool,
+    ) -> color_eyre::Result<()> {
+        let snippet = self.get_snippet(index)?;
+        if let Some(out) = out {
+            if out.exists() && !force {
+                let error: color_eyre::Result<()> = Err(LostTheWay::DoingNothing.into());
+                return error.suggestion(format!(
+                    "{} already exists, use --force to overwrite it",
+                    out.display()
+                ));
+            }
+        }
+        let code = snippet.fill_template(&values, self.highlighter.selection_style)?;
+        match out {
+            Some(out) => {
+                fs::write(out, code.as_bytes())?;
+                self.color_eprint(&format!(
+                    "Snippet #{index} scaffolded to {}\n",
+                    out.display()
+                ))?;
+            }
+            None => {
+                // See https://github.com/rust-lang/rust/issues/46016
+                if let Err(e) = write!(io::stdout(), "{code}") {
+                    if e.kind() != ErrorKind::BrokenPipe {
+                        return Err(e.into());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Copy all snippets matching the filters in index order, separated by blank lines.
+    /// Asks for confirmation before copying more than one to the clipboard
+    fn copy_filtered(
+        &self,
+        filters: &Filters,
+        to_stdout: bool,
+        with_header: bool,
+        no_fill: bool,
+    ) -> color_eyre::Result<()> {
+        let mut snippets = self.filter_snippets(filters)?;
+        if snippets.is_empty() {
+            let error: color_eyre::Result<()> = Err(LostTheWay::NoMatchingSnippets.into());
+            return error.suggestion("Use `the-way list` with the same filters to check them");
+        }
+        snippets.sort_by_key(|snippet| snippet.index);
+        self.copy_snippets(snippets, to_stdout, with_header, no_fill)
+    }
+
+    /// Copies snippets in order, separated by blank lines.
+    /// Asks first when copying more than one to the clipboard
+    fn copy_snippets(
+        &self,
+        snippets: Vec<Snippet>,
+        to_stdout: bool,
+        with_header: bool,
+        no_fill: bool,
+    ) -> color_eyre::Result<()> {
+        if snippets.len() == 1 {
+            return self.copy(snippets[0].index, to_stdout, with_header, no_fill);
+        }
+        if !to_stdout
+            && !Confirm::with_theme(&ColorfulTheme::default())
+                .with_prompt(format!("Copy {} snippets to clipboard?", snippets.len()))
+                .default(false)
+                .interact()?
+        {
+            let error: color_eyre::Result<()> = Err(LostTheWay::DoingNothing.into());
+            return error.suggestion("Press Y next time!");
+        }
+        let mut codes = Vec::with_capacity(snippets.len());
+        for snippet in &snippets {
+            let code = if no_fill {
+                Cow::Borrowed(snippet.code.as_str())
+            } else {
+                snippet.fill_snippet(self.highlighter.selection_style)?
+            };
+            codes.push(if with_header {
+                format!("{}{code}", snippet.comment_header())
+            } else {
+                code.into_owned()
+            });
+        }
+        let code = codes
+            .iter()
+            .map(|code| code.trim_end_matches('\n'))
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        if to_stdout {
+            // See https://github.com/rust-lang/rust/issues/46016
+            if let Err(e) = writeln!(io::stdout(), "{code}") {
+                if e.kind() != ErrorKind::BrokenPipe {
+                    eprintln!("{e}");
+                    process::exit(1);
+                }
+            }
+        } else {
+            utils::copy_to_clipboard(&self.config.copy_cmd, &code)?;
+            self.color_eprint(&format!(
+                "{} snippets copied to clipboard\n",
+                snippets.len()
+            ))?;
         }
         Ok(())
     }
 
-    /// Import from file or gist
+    /// Gets snippet indices from indices, index ranges like 3-6 and exact descriptions.
+    /// Indices that don't have a snippet are skipped with a warning, unless none do
+    fn find_snippets(&self, indices_or_descriptions: &[String]) -> color_eyre::Result<Vec<usize>> {
+        let last_index = self.get_current_snippet_index()?;
+        let mut indices = Vec::new();
+        let mut missing = Vec::new();
+        for index_or_description in indices_or_descriptions {
+            match utils::parse_index_range(index_or_description) {
+                // Nothing is stored past the last index given out, so ranges stop there
+                Some(range) if *range.start() > last_index => missing.push(*range.start()),
+                Some(range) => indices.extend(*range.start()..=(*range.end()).min(last_index)),
+                None => indices.push(self.find_snippet(index_or_description)?),
+            }
+        }
+        let mut found = Vec::with_capacity(indices.len());
+        for index in indices {
+            if self.snippet_exists(index)? {
+                found.push(index);
+            } else {
+                missing.push(index);
+            }
+        }
+        if found.is_empty() {
+            let error: color_eyre::Result<Vec<usize>> =
+                Err(LostTheWay::SnippetNotFound { index: missing[0] }.into());
+            return error.suggestion("Use `the-way list` to see snippet descriptions and indices");
+        }
+        for index in missing {
+            log::warn!("Skipping #{index}, you haven't written that snippet");
+        }
+        Ok(found)
+    }
+
+    /// Gets a snippet's index from either its index or its exact description.
+    /// Asks which one to use if multiple snippets have that description
+    fn find_snippet(&self, index_or_description: &str) -> color_eyre::Result<usize> {
+        if let Ok(index) = index_or_description.parse::<usize>() {
+            return Ok(index);
+        }
+        let matches = self
+            .list_snippets()?
+            .into_iter()
+            .filter(|snippet| snippet.description == index_or_description)
+            .collect::<Vec<_>>();
+        match matches.len() {
+            0 => {
+                let error: color_eyre::Result<usize> = Err(LostTheWay::DescriptionNotFound {
+                    description: index_or_description.to_owned(),
+                }
+                .into());
+                error.suggestion("Use `the-way list` to see snippet descriptions and indices")
+            }
+            1 => Ok(matches[0].index),
+            _ => {
+                let items = matches
+                    .iter()
+                    .map(|snippet| {
+                        format!(
+                            "#{}. {} | {}",
+                            snippet.index, snippet.description, snippet.language
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                let selection = Select::with_theme(&ColorfulTheme::default())
+                    .with_prompt("Multiple snippets have this description, choose one")
+                    .items(&items)
+                    .default(0)
+                    .interact()?;
+                Ok(matches[selection].index)
+            }
+        }
+    }
+
+    /// Import from file, gist, or saved gist
     fn import(
         &mut self,
         file: Option<&Path>,
-        gist_url: Option<String>,
+        gist_urls: &[String],
+        gist_files: &[PathBuf],
         the_way_url: Option<String>,
+        options: &ImportOptions<'_>,
     ) -> color_eyre::Result<()> {
-        let mut num = 0;
-        match (gist_url, the_way_url) {
-            (Some(gist_url), None) => {
-                let snippets = self.import_gist(&gist_url)?;
-                num = snippets.len();
+        let mut num_duplicates = 0;
+        // Files that weren't imported, and why
+        let (mut snippets, skipped, skip_reason) = match (gist_urls.is_empty(), the_way_url) {
+            (false, None) => {
+                let (snippets, mut failed) = self.read_gists(gist_urls, options.tags)?;
+                if gist_urls.len() == 1 && failed.len() == 1 {
+                    return Err(failed.remove(0).1);
+                }
+                for (gist_url, error) in &failed {
+                    log::warn!("Couldn't import from {gist_url}: {error}");
+                }
+                if failed.len() == gist_urls.len() {
+                    return Err(LostTheWay::GistUrlError {
+                        message: "Couldn't import from any of the given Gists".into(),
+                    })
+                    .suggestion(
+                        "The URLs should look like https://gist.github.com/<user>/<gist_id>.",
+                    );
+                }
+                (snippets, Vec::new(), "")
             }
-            (None, Some(the_way_url)) => {
-                let snippets = self.import_the_way_gist(&the_way_url)?;
-                num += snippets.len();
+            (true, None) if !gist_files.is_empty() => (
+                self.read_gist_files(gist_files, options.tags)?,
+                Vec::new(),
+                "",
+            ),
+            (true, Some(the_way_url)) => {
+                let (snippets, skipped) =
+                    self.read_the_way_gist(&the_way_url, options.tags, options.strict)?;
+                (snippets, skipped, "bad filenames")
             }
-            (None, None) => {
-                for mut snippet in self.import_file(file)? {
-                    snippet.index = self.get_current_snippet_index()? + 1;
-                    self.add_snippet(&snippet)?;
-                    self.increment_snippet_index()?;
-                    num += 1;
+            (true, None) => {
+                let (mut snippets, skipped, skip_reason) = match file {
+                    Some(file) if file.extension().is_some_and(|e| e == "zip") => {
+                        let (snippets, skipped) = self.read_zip(file)?;
+                        (snippets, skipped, "not UTF-8 text or too large")
+                    }
+                    _ => {
+                        let (snippets, skipped) = self.import_file(file, options.format)?;
+                        (snippets, skipped, "not UTF-8 text")
+                    }
+                };
+                if options.detect_language {
+                    self.detect_languages(&mut snippets);
+                }
+                for snippet in &mut snippets {
+                    snippet.add_tags(options.tags);
+                }
+                if options.skip_duplicates {
+                    let mut stored = self.list_snippets()?.into_iter().collect::<HashSet<_>>();
+                    let num_snippets = snippets.len();
+                    snippets.retain(|snippet| stored.insert(snippet.clone()));
+                    num_duplicates = num_snippets - snippets.len();
                 }
+                (snippets, skipped, skip_reason)
             }
             _ => {
                 return Err(LostTheWay::OutOfCheeseError {
@@ -298,40 +1159,361 @@ impl TheWay {
                 }
                 .into());
             }
+        };
+        let mut skipped = if skipped.is_empty() {
+            String::new()
+        } else {
+            format!(
+                ", skipped {} ({skip_reason}: {})",
+                skipped.len(),
+                skipped.join(", ")
+            )
+        };
+        if options.skip_duplicates {
+            skipped.push_str(&format!(", skipped {num_duplicates} duplicates"));
         }
-        self.color_print(&format!("Imported {num} snippets\n"))?;
+        if options.dry_run {
+            self.color_print(&format!(
+                "Would import {} snippets{skipped}\n",
+                snippets.len()
+            ))?;
+            snippets.sort_by_key(|snippet| snippet.index);
+            for snippet in &snippets {
+                self.color_print(&format!("{} | {}\n", snippet.description, snippet.language))?;
+            }
+            return Ok(());
+        }
+        let num = self.add_imported_snippets(snippets, options.force)?;
+        self.color_print(&format!("Imported {num} snippets{skipped}\n"))?;
         Ok(())
     }
 
-    /// Imports snippets from a JSON file (ignores indices and appends to existing snippets)
-    /// TODO: It may be nice to check for duplicates somehow, too expensive?
-    fn import_file(&self, file: Option<&Path>) -> color_eyre::Result<Vec<Snippet>> {
-        let reader: Box<dyn io::Read> = match file {
-            Some(file) => Box::new(fs::File::open(file)?),
-            None => Box::new(io::stdin()),
+    /// Imports snippets from a JSON, YAML or TOML file (ignores indices and appends to existing snippets).
+    /// The format is taken from the file extension if not given.
+    /// Returns the JSON records skipped for not being UTF-8 alongside
+    fn import_file(
+        &self,
+        file: Option<&Path>,
+        format: Option<ImportFormat>,
+    ) -> color_eyre::Result<(Vec<Snippet>, Vec<String>)> {
+        let (reader, format): (Box<dyn io::Read>, _) = match file {
+            Some(file) => (
+                Box::new(fs::File::open(file)?),
+                format.or_else(|| import_format(file)),
+            ),
+            None => (Box::new(io::stdin()), format),
         };
+        self.read_maybe_gzipped(reader, format)
+    }
+
+    /// Makes a snippet from each text file in a zip archive, described by its path in the archive.
+    /// Directories are ignored. Returns the names of entries that aren't UTF-8 text
+    /// or are larger than `MAX_ZIP_ENTRY_BYTES` alongside
+    fn read_zip(&self, file: &Path) -> color_eyre::Result<(Vec<Snippet>, Vec<String>)> {
+        let mut archive = zip::ZipArchive::new(io::BufReader::new(fs::File::open(file)?))?;
+        let mut snippets = Vec::new();
+        let mut skipped = Vec::new();
+        for i in 0..archive.len() {
+            let entry = archive.by_index(i)?;
+            if entry.is_dir() {
+                continue;
+            }
+            let name = entry.name().to_owned();
+            if entry.size() > MAX_ZIP_ENTRY_BYTES {
+                skipped.push(name);
+                continue;
+            }
+            // The declared size can't be trusted, so stop reading just past the limit
+            let mut code = Vec::new();
+            io::Read::read_to_end(
+                &mut io::Read::take(entry, MAX_ZIP_ENTRY_BYTES + 1),
+                &mut code,
+            )?;
+            if code.len() as u64 > MAX_ZIP_ENTRY_BYTES {
+                skipped.push(name);
+                continue;
+            }
+            match String::from_utf8(code) {
+                Ok(code) => snippets.push(self.snippet_from_file(&name, code)),
+                Err(_) => skipped.push(name),
+            }
+        }
+        Ok((snippets, skipped))
+    }
+
+    /// Makes a snippet from a file's contents, described by its name,
+    /// with the language guessed from its extension (or its first line)
+    fn snippet_from_file(&self, file_name: &str, code: String) -> Snippet {
+        let language = Path::new(file_name)
+            .extension()
+            .and_then(|extension| {
+                self.highlighter
+                    .language_from_extension(&extension.to_string_lossy(), &self.languages)
+            })
+            .or_else(|| self.highlighter.detect_language(&code, &self.languages))
+            .unwrap_or_else(|| String::from("text"));
+        let extension = Language::get_extension(&language, &self.languages);
+        let now = Utc::now();
+        Snippet::new(
+            0,
+            file_name.to_owned(),
+            language,
+            extension,
+            "",
+            now,
+            now,
+            code,
+        )
+    }
+
+    /// Imports snippets from (possibly gzipped) JSON, as written by `export_to_writer`,
+    /// after existing ones. Returns the number of snippets added
+    pub fn import_from_reader(&mut self, reader: impl io::Read) -> color_eyre::Result<usize> {
+        let (snippets, skipped) = self.read_maybe_gzipped(reader, Some(ImportFormat::Json))?;
+        if !skipped.is_empty() {
+            log::warn!("Skipped {} (not UTF-8 text)", skipped.join(", "));
+        }
+        self.add_imported_snippets(snippets, false)
+    }
+
+    /// Reads snippets in the given format, decompressing them first if they're gzipped.
+    /// JSON records (top-level values) with invalid UTF-8 are skipped so one bad record
+    /// doesn't fail the rest, their numbers are returned alongside.
+    /// YAML and TOML can't be split into records, so invalid UTF-8 fails them
+    fn read_maybe_gzipped(
+        &self,
+        reader: impl io::Read,
+        format: Option<ImportFormat>,
+    ) -> color_eyre::Result<(Vec<Snippet>, Vec<String>)> {
         let mut buffered = io::BufReader::new(reader);
-        let mut snippets = Snippet::read(&mut buffered).collect::<Result<Vec<_>, _>>()?;
+        let mut bytes = Vec::new();
+        if buffered.fill_buf()?.starts_with(&GZIP_MAGIC) {
+            io::Read::read_to_end(&mut GzDecoder::new(buffered), &mut bytes)?;
+        } else {
+            io::Read::read_to_end(&mut buffered, &mut bytes)?;
+        }
+        let bytes = match String::from_utf8(bytes) {
+            Ok(text) => return Ok((self.parse_snippets(&text, format)?, Vec::new())),
+            Err(e) if matches!(format, Some(ImportFormat::Yaml | ImportFormat::Toml)) => {
+                return Err(e.into())
+            }
+            Err(e) => e.into_bytes(),
+        };
+        // Decode once, noting where invalid sequences were replaced
+        let mut text = String::with_capacity(bytes.len());
+        let mut invalid = Vec::new();
+        for chunk in bytes.utf8_chunks() {
+            text.push_str(chunk.valid());
+            if !chunk.invalid().is_empty() {
+                invalid.push(text.len());
+                text.push(char::REPLACEMENT_CHARACTER);
+            }
+        }
+        let mut snippets = Vec::new();
+        let mut skipped = Vec::new();
+        let mut records = serde_json::Deserializer::from_str(&text).into_iter();
+        let mut start = 0;
+        let mut number = 0;
+        while let Some(value) = records.next() {
+            let end = records.byte_offset();
+            number += 1;
+            if invalid.iter().any(|&i| start <= i && i < end) {
+                skipped.push(format!("record {number}"));
+            } else {
+                snippets.extend(Snippet::from_json_value(value?)?);
+            }
+            start = end;
+        }
+        for snippet in &mut snippets {
+            snippet.set_extension(&snippet.language.clone(), &self.languages);
+        }
+        Ok((snippets, skipped))
+    }
+
+    /// Reads snippets in the given format, setting extensions according to language.
+    /// Without a format, reads JSON and falls back to YAML if that fails
+    fn parse_snippets(
+        &self,
+        text: &str,
+        format: Option<ImportFormat>,
+    ) -> color_eyre::Result<Vec<Snippet>> {
+        let mut snippets = match format {
+            Some(ImportFormat::Json) => {
+                Snippet::read(&mut text.as_bytes()).collect::<Result<Vec<_>, _>>()?
+            }
+            Some(ImportFormat::Yaml) => Snippet::from_yaml(text)?,
+            Some(ImportFormat::Toml) => Snippet::from_toml(text)?,
+            None => Snippet::read(&mut text.as_bytes())
+                .collect::<Result<Vec<_>, _>>()
+                .or_else(|json_error| Snippet::from_yaml(text).map_err(|_| json_error))?,
+        };
         for snippet in &mut snippets {
             snippet.set_extension(&snippet.language.clone(), &self.languages);
         }
         Ok(snippets)
     }
 
-    /// Saves (optionally filtered) snippets to a JSON file
-    fn export(&self, filters: &Filters, file: Option<&Path>) -> color_eyre::Result<()> {
-        let writer: Box<dyn io::Write> = match file {
-            Some(file) => Box::new(fs::File::create(file)?),
-            None => Box::new(io::stdout()),
+    /// Reads snippets from a JSON stream, setting extensions according to language
+    fn read_snippets(&self, reader: &mut dyn io::Read) -> color_eyre::Result<Vec<Snippet>> {
+        let mut snippets = Snippet::read(reader).collect::<Result<Vec<_>, _>>()?;
+        for snippet in &mut snippets {
+            snippet.set_extension(&snippet.language.clone(), &self.languages);
+        }
+        Ok(snippets)
+    }
+
+    /// Sets the language of plain text snippets if it can be guessed from their first line
+    fn detect_languages(&self, snippets: &mut [Snippet]) {
+        for snippet in snippets {
+            if !matches!(snippet.language.as_str(), "text" | "txt") {
+                continue;
+            }
+            if let Some(language) = self
+                .highlighter
+                .detect_language(&snippet.code, &self.languages)
+            {
+                snippet.set_extension(&language, &self.languages);
+                snippet.language = language;
+            }
+        }
+    }
+
+    /// Adds imported snippets after existing ones (ignores their indices), returns the number added.
+    /// Adds nothing if any snippet is larger than `max_code_bytes`, unless `force` is set
+    fn add_imported_snippets(
+        &mut self,
+        snippets: Vec<Snippet>,
+        force: bool,
+    ) -> color_eyre::Result<usize> {
+        if !force {
+            for snippet in &snippets {
+                self.check_code_size(snippet)?;
+            }
+        }
+        let mut num = 0;
+        for mut snippet in snippets {
+            self.check_syntax(&snippet);
+            snippet.index = self.get_current_snippet_index()? + 1;
+            self.add_snippet(&snippet)?;
+            self.increment_snippet_index()?;
+            num += 1;
+        }
+        Ok(num)
+    }
+
+    /// Saves (optionally filtered) snippets to a file in the given format, gzipped if the file name ends in .gz
+    fn export(
+        &self,
+        filters: &Filters,
+        file: Option<&Path>,
+        format: ExportFormat,
+        delimiter: &str,
+        with_header: bool,
+    ) -> color_eyre::Result<()> {
+        let write_snippets = |writer: &mut dyn io::Write| match format {
+            ExportFormat::Json => self.export_to_writer(filters, writer),
+            ExportFormat::Yaml => Snippet::to_yaml(&self.snippets_to_export(filters)?, writer),
+            ExportFormat::Toml => Snippet::to_toml(&self.snippets_to_export(filters)?, writer),
+            ExportFormat::Plain => self.write_plain(filters, writer, delimiter, with_header),
+            ExportFormat::Markdown => self.write_markdown(filters, writer),
+            ExportFormat::Html => self.write_html(filters, writer),
         };
-        let mut buffered = io::BufWriter::new(writer);
-        for snippet in self.filter_snippets(filters)? {
-            snippet.to_json(&mut buffered)?;
-            buffered.write_all(b"\n")?;
+        match file {
+            Some(file) if file.extension().is_some_and(|e| e == "gz") => {
+                let mut encoder = GzEncoder::new(
+                    io::BufWriter::new(fs::File::create(file)?),
+                    Compression::default(),
+                );
+                write_snippets(&mut encoder)?;
+                encoder.finish()?.flush()?;
+            }
+            Some(file) => {
+                let mut buffered = io::BufWriter::new(fs::File::create(file)?);
+                write_snippets(&mut buffered)?;
+                buffered.flush()?;
+            }
+            None => {
+                let mut buffered = io::BufWriter::new(io::stdout());
+                write_snippets(&mut buffered)?;
+                buffered.flush()?;
+            }
         }
         Ok(())
     }
 
+    /// (Optionally filtered) snippets in index order, so exports of an index range read in order
+    fn snippets_to_export(&self, filters: &Filters) -> color_eyre::Result<Vec<Snippet>> {
+        let mut snippets = self.filter_snippets(filters)?;
+        snippets.sort_by_key(|snippet| snippet.index);
+        Ok(snippets)
+    }
+
+    /// Writes (optionally filtered) snippets as newline-delimited JSON, readable by `import_from_reader`
+    pub fn export_to_writer(
+        &self,
+        filters: &Filters,
+        mut writer: impl io::Write,
+    ) -> color_eyre::Result<()> {
+        for snippet in self.snippets_to_export(filters)? {
+            snippet.to_json(&mut writer)?;
+            writer.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    /// Writes the code of (optionally filtered) snippets separated by a delimiter line,
+    /// optionally starting each with a comment containing its index and description
+    fn write_plain(
+        &self,
+        filters: &Filters,
+        writer: &mut dyn io::Write,
+        delimiter: &str,
+        with_header: bool,
+    ) -> color_eyre::Result<()> {
+        for (i, snippet) in self.snippets_to_export(filters)?.iter().enumerate() {
+            if i > 0 {
+                writeln!(writer, "{delimiter}")?;
+            }
+            if with_header {
+                write!(writer, "{}", snippet.comment_header())?;
+            }
+            write!(writer, "{}", snippet.code)?;
+            if !snippet.code.ends_with('\n') {
+                writeln!(writer)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes (optionally filtered) snippets as Markdown sections separated by blank lines
+    fn write_markdown(
+        &self,
+        filters: &Filters,
+        writer: &mut dyn io::Write,
+    ) -> color_eyre::Result<()> {
+        for (i, snippet) in self.snippets_to_export(filters)?.iter().enumerate() {
+            if i > 0 {
+                writeln!(writer)?;
+            }
+            write!(writer, "{}", snippet.to_markdown())?;
+        }
+        Ok(())
+    }
+
+    /// Writes (optionally filtered) snippets as a standalone HTML page
+    fn write_html(&self, filters: &Filters, writer: &mut dyn io::Write) -> color_eyre::Result<()> {
+        write!(
+            writer,
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>\n",
+            utils::NAME
+        )?;
+        for snippet in self.snippets_to_export(filters)? {
+            write!(writer, "{}", snippet.to_html(&self.highlighter)?)?;
+        }
+        writeln!(writer, "</body>\n</html>")?;
+        Ok(())
+    }
+
     /// Prints given snippets in full
     fn show_snippets(&self, snippets: &[Snippet]) -> color_eyre::Result<()> {
         let mut colorized = Vec::new();
@@ -343,6 +1525,7 @@ impl TheWay {
                     self.languages
                         .get(&snippet.language)
                         .unwrap_or(&default_language),
+                    self.header_width(),
                 )?,
             );
         }
@@ -350,14 +1533,22 @@ impl TheWay {
         Ok(())
     }
 
+    /// Width to fit snippet titles in, unless `--no-truncate` is given
+    fn header_width(&self) -> Option<usize> {
+        if self.no_truncate {
+            None
+        } else {
+            utils::terminal_width()
+        }
+    }
+
     fn show_counts(
         &self,
         object_to_count: HashMap<String, usize>,
         list_type: ListType,
     ) -> color_eyre::Result<()> {
-        let mut objects = object_to_count.iter().collect::<Vec<_>>();
-        objects.sort_by(|(_, a), (_, b)| b.cmp(a));
         let mut colorized = Vec::new();
+        let objects = sort_counts(&object_to_count);
         for (object, count) in objects {
             match list_type {
                 ListType::Tag => {
@@ -366,7 +1557,6 @@ impl TheWay {
                 ListType::Language => {
                     colorized.push((self.highlighter.accent_style, object.to_string()));
                 }
-                _ => unreachable!(),
             }
             colorized.push((self.highlighter.main_style, format!(" ({count})\n")));
         }
@@ -374,29 +1564,77 @@ impl TheWay {
         Ok(())
     }
 
-    /// Lists snippets (optionally filtered)
-    fn list(&self, filters: &Filters, list_type: ListType) -> color_eyre::Result<()> {
-        let mut snippets = self.filter_snippets(filters)?;
-        match list_type {
-            ListType::Snippet => {
-                snippets.sort_by(|a, b| a.index.cmp(&b.index));
-                self.show_snippets(&snippets)?;
-            }
-            ListType::Tag => {
-                let mut tags = HashMap::new();
-                for snippet in &snippets {
-                    for tag in &snippet.tags {
-                        *tags.entry(tag.to_owned()).or_insert(0) += 1;
+    /// Prints tag or language counts as a JSON array of `{"name", "count"}` objects
+    fn show_counts_json(&self, object_to_count: HashMap<String, usize>) -> color_eyre::Result<()> {
+        let objects = sort_counts(&object_to_count)
+            .into_iter()
+            .map(|(name, count)| serde_json::json!({ "name": name, "count": count }))
+            .collect::<Vec<_>>();
+        println!("{}", serde_json::to_string_pretty(&objects)?);
+        Ok(())
+    }
+
+    /// Counts (optionally filtered) snippets per tag or language
+    fn count_snippets(
+        &self,
+        filters: &Filters,
+        list_type: &ListType,
+    ) -> color_eyre::Result<HashMap<String, usize>> {
+        let mut counts = HashMap::new();
+        for snippet in self.filter_snippets(filters)? {
+            match list_type {
+                ListType::Tag => {
+                    for tag in snippet.tags {
+                        *counts.entry(tag).or_insert(0) += 1;
                     }
                 }
-                self.show_counts(tags, list_type)?;
+                ListType::Language => *counts.entry(snippet.language).or_insert(0) += 1,
             }
-            ListType::Language => {
-                let mut languages = HashMap::new();
-                for snippet in &snippets {
-                    *languages.entry(snippet.language.to_owned()).or_insert(0) += 1;
+        }
+        Ok(counts)
+    }
+
+    /// Lists languages which can be syntax highlighted
+    fn list_supported_languages(&self) -> color_eyre::Result<()> {
+        let colorized = self
+            .highlighter
+            .get_syntaxes()
+            .into_iter()
+            .map(|syntax| (self.highlighter.accent_style, format!("{syntax}\n")))
+            .collect::<Vec<_>>();
+        utils::smart_print(&colorized, false, self.colorize, self.plain)?;
+        Ok(())
+    }
+
+    /// Lists tags or languages of (optionally filtered) snippets with their counts
+    fn list(&self, filters: &Filters, list_type: ListType) -> color_eyre::Result<()> {
+        self.show_counts(self.count_snippets(filters, &list_type)?, list_type)
+    }
+
+    /// Lists snippets as stable, tab-separated lines without color or code
+    fn list_porcelain(&self, snippets: &[Snippet]) -> color_eyre::Result<()> {
+        let mut stdout = io::BufWriter::new(io::stdout());
+        for snippet in snippets {
+            // Keep each snippet on one line with exactly four fields
+            let description = snippet.description.replace(['\t', '\n', '\r'], " ");
+            let line = format!(
+                "{}\t{}\t{}\t{}",
+                snippet.index,
+                snippet.language,
+                snippet.tags.join(","),
+                description
+            );
+            // See https://github.com/rust-lang/rust/issues/46016
+            if let Err(e) = writeln!(stdout, "{line}") {
+                if e.kind() == ErrorKind::BrokenPipe {
+                    return Ok(());
                 }
-                self.show_counts(languages, list_type)?;
+                return Err(e.into());
+            }
+        }
+        if let Err(e) = stdout.flush() {
+            if e.kind() != ErrorKind::BrokenPipe {
+                return Err(e.into());
             }
         }
         Ok(())
@@ -410,7 +1648,7 @@ impl TheWay {
         search_options: search::SearchOptions,
     ) -> color_eyre::Result<()> {
         let mut snippets = self.filter_snippets(filters)?;
-        snippets.sort_by(|a, b| a.index.cmp(&b.index));
+        snippets.sort_by_key(|s| s.index);
         self.make_search(
             snippets,
             self.highlighter.skim_theme.clone(),
@@ -427,16 +1665,38 @@ impl TheWay {
     }
 
     /// Removes all `sled` trees
-    fn clear(&self, force: bool) -> color_eyre::Result<()> {
+    fn clear(&self, force: bool, force_unsafe: bool) -> color_eyre::Result<()> {
+        // Refuse to wipe a misconfigured db_dir (e.g. $HOME)
+        let foreign_entries = fs::read_dir(&self.config.db_dir)?
+            .map(|entry| Ok(entry?.file_name().to_string_lossy().into_owned()))
+            .collect::<color_eyre::Result<Vec<_>>>()?
+            .into_iter()
+            .filter(|name| !is_database_entry(name))
+            .collect::<Vec<_>>();
+        if !foreign_entries.is_empty() && !force_unsafe {
+            let error: color_eyre::Result<()> = Err(LostTheWay::ConfigError {
+                message: format!(
+                    "{} doesn't look like a the-way database, found {}",
+                    self.config.db_dir.display(),
+                    foreign_entries.join(", ")
+                ),
+            }
+            .into());
+            return error.suggestion(
+                "Check db_dir in your configuration file, or use --force-unsafe to clear it anyway",
+            );
+        }
         if force
             || Confirm::with_theme(&ColorfulTheme::default())
                 .with_prompt("Clear all data?")
                 .default(false)
                 .interact()?
         {
-            for path in fs::read_dir(&self.config.db_dir)? {
-                let path = path?.path();
-                if path.is_dir() {
+            for entry in fs::read_dir(&self.config.db_dir)? {
+                let entry = entry?;
+                let path = entry.path();
+                // file_type doesn't follow symlinks, so links are removed rather than their targets
+                if entry.file_type()?.is_dir() {
                     fs::remove_dir_all(path)?;
                 } else {
                     fs::remove_file(path)?;
@@ -452,32 +1712,127 @@ impl TheWay {
     }
 
     /// Syncs snippets to Gist
-    fn sync(&mut self, cmd: SyncCommand, force: bool) -> color_eyre::Result<()> {
-        // Take token from environment variable or config file
-        let mut github_access_token = std::env::var("THE_WAY_GITHUB_TOKEN")
-            .ok()
-            .or_else(|| self.config.github_access_token.clone());
-        // Get token from user if not set
-        if github_access_token.is_none() {
-            self.color_print("Get a GitHub access token from https://github.com/settings/tokens/new (add the \"gist\" scope)\n\n")?;
-            github_access_token = Some(
-                dialoguer::Password::with_theme(&ColorfulTheme::default())
-                    .with_prompt("GitHub access token")
-                    .interact()?,
-            );
-            if utils::confirm("Save to config?", false)? {
-                self.config.github_access_token = github_access_token.clone();
+    fn sync(
+        &mut self,
+        cmd: SyncCommand,
+        force: bool,
+        open: bool,
+        dry_run: bool,
+    ) -> color_eyre::Result<()> {
+        let access_token = self.get_access_token()?;
+        if self.config.synced_id().is_some() {
+            self.sync_gist(&access_token, cmd, force, dry_run)?;
+            if dry_run {
+                return Ok(());
             }
-        }
-        if self.config.gist_id.is_some() {
-            self.sync_gist(github_access_token.as_deref(), cmd, force)?;
+        } else if dry_run {
+            return self.color_print(&format!(
+                "Would create a new Gist with {} snippets\n",
+                self.list_snippets()?.len()
+            ));
         } else {
-            self.config.gist_id = Some(self.make_gist(github_access_token.as_ref().unwrap())?);
+            let gist = self.make_gist(&access_token)?;
+            self.config.set_synced(Some(gist.id), gist.html_url);
+        }
+        self.config.store()?;
+        if open {
+            if let Some(gist_url) = self.config.synced_url() {
+                utils::open_url(gist_url)?;
+            }
         }
+        Ok(())
+    }
+
+    /// Starts syncing to an existing Gist which wasn't made by the-way
+    fn adopt(&mut self, gist_id: &str, overwrite: bool, open: bool) -> color_eyre::Result<()> {
+        let access_token = self.get_access_token()?;
+        let gist = self.adopt_gist(&access_token, gist_id, overwrite)?;
+        self.config.set_synced(Some(gist.id), gist.html_url);
         self.config.store()?;
+        if open {
+            if let Some(gist_url) = self.config.synced_url() {
+                utils::open_url(gist_url)?;
+            }
+        }
         Ok(())
     }
 
+    /// Gets the access token for the configured sync backend
+    fn get_access_token(&mut self) -> color_eyre::Result<String> {
+        match self.config.sync_backend {
+            SyncBackend::Github => self.get_github_access_token(),
+            SyncBackend::Gitlab => match std::env::var("THE_WAY_GITLAB_TOKEN") {
+                Ok(gitlab_access_token) => Ok(gitlab_access_token),
+                Err(_) => Err(LostTheWay::SyncError {
+                    message: "No GitLab access token".into(),
+                })
+                .suggestion(format!(
+                    "Get one from {}/-/user_settings/personal_access_tokens (add the \"api\" scope).\n\
+                    Set it to the environment variable $THE_WAY_GITLAB_TOKEN",
+                    self.config.gitlab_url.as_deref().unwrap_or(GITLAB_URL)
+                )),
+            },
+        }
+    }
+
+    /// Gets the GitHub access token from the environment, the configuration file, the keyring, or the user
+    fn get_github_access_token(&mut self) -> color_eyre::Result<String> {
+        // Take token from environment variable, config file or keyring
+        if let Some(github_access_token) = token_store::resolve_github_token(
+            std::env::var("THE_WAY_GITHUB_TOKEN").ok(),
+            &self.config,
+            &Keyring,
+        )? {
+            return Ok(github_access_token);
+        }
+        // Get token from user if not set
+        self.color_print("Get a GitHub access token from https://github.com/settings/tokens/new (add the \"gist\" scope)\n\n")?;
+        let github_access_token = dialoguer::Password::with_theme(&ColorfulTheme::default())
+            .with_prompt("GitHub access token")
+            .interact()?;
+        let prompt = if self.config.use_keyring {
+            "Save to keyring?"
+        } else {
+            "Save to config?"
+        };
+        if utils::confirm(prompt, false)? {
+            token_store::save_github_token(&mut self.config, &github_access_token, &Keyring)?;
+        }
+        Ok(github_access_token)
+    }
+
+    /// URL of the synced Gist, as stored or (for a GitHub Gist) made from its ID
+    fn synced_gist_url(&self) -> color_eyre::Result<String> {
+        match (self.config.synced_url(), self.config.synced_id()) {
+            (Some(gist_url), _) => Ok(gist_url.to_owned()),
+            (None, Some(gist_id)) if self.config.sync_backend == SyncBackend::Github => {
+                Ok(format!("https://gist.github.com/{gist_id}"))
+            }
+            _ => Err(LostTheWay::SyncError {
+                message: "No Gist URL stored".into(),
+            })
+            .suggestion("Run `the-way sync local` to create or sync a Gist first"),
+        }
+    }
+
+    /// Prints the URL of the synced Gist
+    fn print_gist_url(&self) -> color_eyre::Result<()> {
+        self.color_print(&format!("{}\n", self.synced_gist_url()?))
+    }
+
+    /// Opens the synced Gist in a browser, or prints its URL if `print` is set,
+    /// stdout isn't a terminal, or the browser couldn't be opened
+    fn open_gist(&self, print: bool) -> color_eyre::Result<()> {
+        let gist_url = self.synced_gist_url()?;
+        if !print && io::stdout().is_terminal() {
+            match utils::open_url(&gist_url) {
+                Ok(()) => return Ok(()),
+                Err(e) => log::warn!("Couldn't open a browser: {e}"),
+            }
+        }
+        self.color_print(&format!("{gist_url}\n"))
+    }
+
     fn themes(&mut self, cmd: ThemeCommand) -> color_eyre::Result<()> {
         match cmd {
             ThemeCommand::Set { theme } => {
@@ -485,10 +1840,13 @@ impl TheWay {
                     theme
                 } else {
                     let themes = self.highlighter.get_themes();
+                    let current_theme = self.highlighter.get_theme_name();
+                    // Type to narrow down the list
                     let theme_index =
-                        Select::with_theme(&dialoguer::theme::ColorfulTheme::default())
+                        FuzzySelect::with_theme(&dialoguer::theme::ColorfulTheme::default())
                             .with_prompt("Choose a syntax highlighting theme:")
                             .items(&themes[..])
+                            .default(themes.iter().position(|t| *t == current_theme).unwrap_or(0))
                             .interact()?;
                     themes[theme_index].clone()
                 };
@@ -528,4 +1886,25 @@ impl TheWay {
         )?;
         Ok(())
     }
+
+    /// Whether to use exact search, `--exact`/`--fuzzy` take precedence over the configuration
+    fn exact_search(&self, exact: bool, fuzzy: bool) -> bool {
+        exact || (!fuzzy && self.config.search_exact)
+    }
+
+    /// Whether to pick tags in a multi-select, `--pick-tags` or the configuration turn it on
+    fn pick_tags(&self, pick_tags: bool) -> bool {
+        pick_tags || self.config.pick_tags
+    }
+
+    /// Like `color_print` but to stderr, for messages that shouldn't end up in piped output
+    pub(crate) fn color_eprint(&self, input: &str) -> color_eyre::Result<()> {
+        utils::smart_eprint(
+            &[(self.highlighter.main_style, input.to_string())],
+            false,
+            self.colorize,
+            self.plain,
+        )?;
+        Ok(())
+    }
 }
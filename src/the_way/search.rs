@@ -8,6 +8,7 @@ use skim::{
     AnsiString, DisplayContext, FuzzyAlgorithm, ItemPreview, MatchEngineFactory, MatchRange,
     Matches, PreviewContext, Skim, SkimItem, SkimItemReceiver, SkimItemSender,
 };
+use strum_macros::Display;
 use syntect::highlighting::Style;
 
 use crate::errors::LostTheWay;
@@ -35,18 +36,22 @@ struct SearchCode {
     selection_style: Style,
     /// Highlighted code
     code_highlight: String,
+    /// Raw code, piped to `preview_cmd`
+    code: String,
+    /// External command rendering the preview
+    preview_cmd: Option<String>,
     /// Use exact search
     exact: bool,
 }
 
 impl SkimItem for SearchCode {
-    fn text(&self) -> Cow<str> {
+    fn text(&self) -> Cow<'_, str> {
         AnsiString::parse(&self.code_highlight).into_inner()
     }
 }
 
 impl SkimItem for SearchSnippet {
-    fn text(&self) -> Cow<str> {
+    fn text(&self) -> Cow<'_, str> {
         AnsiString::parse(&self.text_highlight).into_inner()
             + AnsiString::parse(&self.code.code_highlight).into_inner()
     }
@@ -81,6 +86,12 @@ impl SkimItem for SearchSnippet {
     }
 
     fn preview(&self, context: PreviewContext) -> ItemPreview {
+        // Use external preview command if set, fall back to built-in highlighting if it fails
+        if let Some(preview_cmd) = &self.code.preview_cmd {
+            if let Ok(preview) = utils::pipe_to_command(preview_cmd, &self.code.code) {
+                return ItemPreview::AnsiText(preview);
+            }
+        }
         if context.selected_indices.contains(&context.current_index) {
             let fuzzy_engine = ExactOrFuzzyEngineFactory::builder()
                 .exact_mode(self.code.exact)
@@ -125,55 +136,96 @@ impl SkimItem for SearchSnippet {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Default)]
 pub(crate) enum SkimCommand {
     Copy,
     Delete,
     Edit,
     View,
+    /// Plain `search`, with every action
+    #[default]
     All,
 }
 
-impl SkimCommand {
-    pub fn keys(&self) -> Vec<&'static str> {
-        match self {
-            SkimCommand::Copy | SkimCommand::Delete | SkimCommand::Edit | SkimCommand::View => {
-                vec!["Enter"]
-            }
-            SkimCommand::All => vec!["Enter", "shift-left", "shift-right"],
-        }
-    }
+/// What happens to the selected snippets when a key is pressed
+#[derive(Debug, Clone, Copy, Display)]
+enum SearchAction {
+    #[strum(serialize = "copy")]
+    Copy,
+    #[strum(serialize = "print")]
+    Print,
+    #[strum(serialize = "delete")]
+    Delete,
+    #[strum(serialize = "edit")]
+    Edit,
+    #[strum(serialize = "view")]
+    View,
+}
 
-    pub fn names(&self) -> Vec<&'static str> {
-        match self {
-            SkimCommand::Copy => vec!["copy"],
-            SkimCommand::Delete => vec!["delete"],
-            SkimCommand::Edit => vec!["edit"],
-            SkimCommand::View => vec!["view"],
-            SkimCommand::All => vec!["copy", "delete", "edit"],
-        }
+/// A key (as named in skim's bindings) and the action it triggers
+struct KeyAction {
+    name: &'static str,
+    key: Key,
+    action: SearchAction,
+}
+
+impl KeyAction {
+    fn new(name: &'static str, key: Key, action: SearchAction) -> Self {
+        Self { name, key, action }
     }
 }
 
+/// How a search behaves, built as a struct literal with `..Default::default()` for unset options
+#[derive(Debug, Default)]
 pub(crate) struct SearchOptions {
     /// Search command
-    command: SkimCommand,
+    pub(crate) command: SkimCommand,
     /// Use exact search
-    exact: bool,
+    pub(crate) exact: bool,
     /// Use stdout
-    stdout: bool,
+    pub(crate) stdout: bool,
+    /// Comment header before code printed to stdout
+    pub(crate) with_header: bool,
+    /// Copy shell snippets without filling their parameters
+    pub(crate) no_fill: bool,
     /// Force delete
-    force: bool,
+    pub(crate) force: bool,
+    /// Leave out actions changing snippets
+    pub(crate) read_only: bool,
 }
 
 impl SearchOptions {
-    pub fn new(command: SkimCommand, exact: bool, stdout: bool, force: bool) -> Self {
-        Self {
-            command,
-            exact,
-            stdout,
-            force,
+    /// Actions available in this search, used for both the key bindings and the header.
+    /// Enter copies to the clipboard or prints depending on `--stdout`, ctrl-y and ctrl-o always do one of them
+    fn key_actions(&self) -> Vec<KeyAction> {
+        let copy = if self.stdout {
+            SearchAction::Print
+        } else {
+            SearchAction::Copy
+        };
+        let mut key_actions = match self.command {
+            SkimCommand::Copy => vec![
+                KeyAction::new("Enter", Key::Enter, copy),
+                KeyAction::new("ctrl-y", Key::Ctrl('y'), SearchAction::Copy),
+                KeyAction::new("ctrl-o", Key::Ctrl('o'), SearchAction::Print),
+            ],
+            SkimCommand::Delete => vec![KeyAction::new("Enter", Key::Enter, SearchAction::Delete)],
+            SkimCommand::Edit => vec![KeyAction::new("Enter", Key::Enter, SearchAction::Edit)],
+            SkimCommand::View => vec![KeyAction::new("Enter", Key::Enter, SearchAction::View)],
+            SkimCommand::All => vec![
+                KeyAction::new("Enter", Key::Enter, copy),
+                KeyAction::new("ctrl-y", Key::Ctrl('y'), SearchAction::Copy),
+                KeyAction::new("ctrl-o", Key::Ctrl('o'), SearchAction::Print),
+                KeyAction::new("shift-left", Key::ShiftLeft, SearchAction::Delete),
+                KeyAction::new("shift-right", Key::ShiftRight, SearchAction::Edit),
+            ],
+        };
+        if self.read_only {
+            key_actions.retain(|key_action| {
+                !matches!(key_action.action, SearchAction::Delete | SearchAction::Edit)
+            });
         }
+        key_actions
     }
 }
 
@@ -205,33 +257,40 @@ impl TheWay {
                     code_fragments,
                     selection_style,
                     code_highlight,
+                    code: snippet.code.clone(),
+                    preview_cmd: self.config.search_preview_cmd.clone(),
                     exact: search_options.exact,
                 },
                 text_highlight: utils::highlight_strings(
-                    &snippet.pretty_print_header(&self.highlighter, language),
+                    &snippet.pretty_print_header(&self.highlighter, language, None),
                     false,
                 ),
                 index: snippet.index,
             });
         }
-        let bind = search_options
-            .command
-            .keys()
-            .into_iter()
-            .map(|s| format!("{s}:accept"))
+        let key_actions = search_options.key_actions();
+        let bind = key_actions
+            .iter()
+            .map(|key_action| format!("{}:accept", key_action.name))
             .collect::<Vec<_>>();
         let header = format!(
             "Press {}",
-            search_options
-                .command
-                .keys()
-                .into_iter()
-                .zip(search_options.command.names().into_iter())
-                .map(|(key, name)| format!("{key} to {name}"))
+            key_actions
+                .iter()
+                .map(|key_action| format!("{} to {}", key_action.name, key_action.action))
                 .collect::<Vec<_>>()
                 .join(", "),
         );
 
+        log::debug!(
+            "Searching {} snippets with {} matching",
+            search_snippets.len(),
+            if search_options.exact {
+                "exact"
+            } else {
+                "fuzzy"
+            }
+        );
         let options = SkimOptionsBuilder::default()
             .height(Some("100%"))
             .preview(Some(""))
@@ -252,36 +311,45 @@ impl TheWay {
         drop(tx_item); // so that skim could know when to stop waiting for more items.
 
         if let Some(output) = Skim::run_with(&options, Some(rx_item)) {
-            let key = output.final_key;
+            let Some(action) = key_actions
+                .iter()
+                .find(|key_action| key_action.key == output.final_key)
+                .map(|key_action| key_action.action)
+            else {
+                return Ok(());
+            };
             for item in &output.selected_items {
                 let snippet: &SearchSnippet = (*item)
                     .as_any()
                     .downcast_ref::<SearchSnippet>()
                     .ok_or(LostTheWay::SearchError)?;
 
-                match (search_options.command, key) {
-                    (SkimCommand::Copy, Key::Enter) => {
-                        self.copy(snippet.index, search_options.stdout)?;
+                match action {
+                    SearchAction::Copy => {
+                        self.copy(
+                            snippet.index,
+                            false,
+                            search_options.with_header,
+                            search_options.no_fill,
+                        )?;
                     }
-                    (SkimCommand::Delete, Key::Enter) => {
-                        self.delete(snippet.index, search_options.force)?;
-                    }
-                    (SkimCommand::Edit, Key::Enter) => {
-                        self.edit(snippet.index)?;
-                    }
-                    (SkimCommand::View, Key::Enter) => {
-                        self.view(snippet.index)?;
+                    SearchAction::Print => {
+                        self.copy(
+                            snippet.index,
+                            true,
+                            search_options.with_header,
+                            search_options.no_fill,
+                        )?;
                     }
-                    (SkimCommand::All, Key::Enter) => {
-                        self.copy(snippet.index, search_options.stdout)?;
-                    }
-                    (SkimCommand::All, Key::ShiftLeft) => {
+                    SearchAction::Delete => {
                         self.delete(snippet.index, search_options.force)?;
                     }
-                    (SkimCommand::All, Key::ShiftRight) => {
-                        self.edit(snippet.index)?;
+                    SearchAction::Edit => {
+                        self.edit(snippet.index, false, false)?;
+                    }
+                    SearchAction::View => {
+                        self.view(snippet.index, None, false)?;
                     }
-                    _ => (),
                 }
             }
         }
@@ -1,16 +1,87 @@
 //! Snippet information and methods
 use std::borrow::Cow;
-use std::collections::{BTreeSet, HashMap};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::hash::Hash;
 use std::io;
 
 use chrono::{DateTime, Utc};
+use color_eyre::Help;
 use regex::Regex;
 use syntect::highlighting::Style;
 
-use crate::language::{CodeHighlight, Language};
+use crate::errors::LostTheWay;
+use crate::language::{self, CodeHighlight, Language};
 use crate::utils;
 
+/// Descriptions are never shortened to fewer characters than this to fit the terminal
+const MIN_DESCRIPTION_WIDTH: usize = 10;
+
+/// Matches <param> or <param=value> placeholders in shell snippets
+fn parameter_regex() -> color_eyre::Result<Regex> {
+    Ok(Regex::new("<(?P<name>[^<>=]+)(?:=(?P<default>[^<>]*))?>")?)
+}
+
+/// Matches {{name}} or {{name=default}} placeholders in templates, whatever their language.
+/// Names are identifiers, so other uses of braces in code aren't placeholders
+fn template_regex() -> color_eyre::Result<Regex> {
+    Ok(Regex::new(
+        r"\{\{(?P<name>[A-Za-z_][A-Za-z0-9_-]*)(?:=(?P<default>[^{}]*))?\}\}",
+    )?)
+}
+
+/// Restyles the parts of highlighted text that are parameters to fill with `highlight_style`,
+/// splitting pieces where needed
+fn highlight_parameters<S: AsRef<str>>(
+    highlighted: &[(Style, S)],
+    highlight_style: Style,
+) -> color_eyre::Result<Vec<(Style, String)>> {
+    let text = highlighted
+        .iter()
+        .map(|(_, piece)| piece.as_ref())
+        .collect::<String>();
+    let parameters = parameter_regex()?
+        .find_iter(&text)
+        .map(|parameter| parameter.range())
+        .collect::<Vec<_>>();
+    let mut restyled = Vec::new();
+    let mut start = 0;
+    for (style, piece) in highlighted {
+        let end = start + piece.as_ref().len();
+        let mut position = start;
+        while position < end {
+            let (style, stop) = match parameters
+                .iter()
+                .find(|parameter| parameter.contains(&position))
+            {
+                Some(parameter) => (highlight_style, parameter.end.min(end)),
+                None => (
+                    *style,
+                    parameters
+                        .iter()
+                        .map(|parameter| parameter.start)
+                        .find(|&parameter_start| parameter_start > position)
+                        .unwrap_or(end)
+                        .min(end),
+                ),
+            };
+            restyled.push((style, text[position..stop].to_owned()));
+            position = stop;
+        }
+        start = end;
+    }
+    Ok(restyled)
+}
+
+/// Snippet information given up front, e.g. on the command line, which isn't prompted for
+#[derive(Debug, Default)]
+pub(crate) struct GivenFields {
+    pub(crate) description: Option<String>,
+    pub(crate) language: Option<String>,
+    /// Space separated
+    pub(crate) tags: Option<String>,
+    pub(crate) code: Option<String>,
+}
+
 /// Stores information about a quote
 #[derive(Serialize, Deserialize, Debug, Eq, Clone)]
 pub struct Snippet {
@@ -35,12 +106,42 @@ pub struct Snippet {
     /// Time of last update
     #[serde(default = "Utc::now")]
     pub updated: DateTime<Utc>,
+    /// Starred snippets, listed first
+    #[serde(default)]
+    pub favorite: bool,
+    /// Private snippets are never synced to the Gist
+    #[serde(default)]
+    pub private: bool,
+}
+
+/// Layout version stored as the first byte of each snippet record, before the bincode-serialized
+/// snippet and its CRC32 checksum. Bump it (keeping a way to read older versions) when `Snippet` changes
+pub(crate) const SNIPPET_LAYOUT_VERSION: u8 = 1;
+
+/// Snippet as stored before records started with a layout version
+#[derive(Deserialize)]
+struct UnversionedFields {
+    index: usize,
+    description: String,
+    language: String,
+    code: String,
+    extension: String,
+    tags: Vec<String>,
+    date: DateTime<Utc>,
+    updated: DateTime<Utc>,
+}
+
+/// Result of checking a snippet read from the database against its checksum
+#[derive(Debug, Eq, PartialEq)]
+pub(crate) enum Checksum {
+    Valid,
+    Mismatch,
 }
 
 impl PartialEq for Snippet {
     fn eq(&self, other: &Self) -> bool {
         self.description == other.description
-            && self.language.to_ascii_lowercase() == other.language.to_ascii_lowercase()
+            && self.language.eq_ignore_ascii_case(&other.language)
             && self.code.trim() == other.code.trim()
             && self.tags.iter().collect::<BTreeSet<_>>()
                 == other.tags.iter().collect::<BTreeSet<_>>()
@@ -78,6 +179,8 @@ impl Snippet {
             date,
             updated,
             code,
+            favorite: false,
+            private: false,
         }
     }
 
@@ -89,31 +192,36 @@ impl Snippet {
         self.extension = Language::get_extension(language_name, languages);
     }
 
-    /// Queries user for new snippet info
+    /// Queries user for new snippet info, `tags` (space separated) skips the tag prompt
     pub(crate) fn from_user(
         index: usize,
         languages: &HashMap<String, Language>,
         used_tags: Vec<String>,
         used_languages: Vec<String>,
         old_snippet: Option<&Self>,
+        given: GivenFields,
+        pick_tags: bool,
     ) -> color_eyre::Result<Self> {
         let (old_description, old_language, old_tags, old_date, old_code) = match old_snippet {
             Some(s) => (
                 Some(s.description.as_str()),
                 Some(s.language.as_str()),
-                Some(s.tags.join(" ")),
+                Some(s.tags.as_slice()),
                 Some(s.date.date_naive().format("%Y-%m-%d").to_string()),
                 Some(s.code.as_str()),
             ),
             None => (None, None, None, None, None),
         };
-        let description = utils::user_input(
-            "Description",
-            old_description,
-            true,
-            false,
-            utils::TheWayCompletion::Empty,
-        )?;
+        let description = match given.description {
+            Some(description) => description,
+            None => utils::user_input(
+                "Description",
+                old_description,
+                true,
+                false,
+                utils::TheWayCompletion::Empty,
+            )?,
+        };
         let mut all_languages = used_languages;
         let mut unused_languages = languages
             .keys()
@@ -123,18 +231,25 @@ impl Snippet {
         all_languages.extend(unused_languages);
 
         let language_completions = utils::TheWayCompletion::Language(all_languages);
-        let language =
-            utils::user_input("Language", old_language, true, false, language_completions)?
-                .to_ascii_lowercase();
+        let language = match given.language {
+            Some(language) => language,
+            None => utils::user_input("Language", old_language, true, false, language_completions)?,
+        }
+        .to_ascii_lowercase();
         let extension = Language::get_extension(&language, languages);
-        let tag_completions = utils::TheWayCompletion::Tag(used_tags);
-        let tags = utils::user_input(
-            "Tags (space separated)",
-            old_tags.as_deref(),
-            true,
-            true,
-            tag_completions,
-        )?;
+        let tags = match given.tags {
+            Some(tags) => tags,
+            None if pick_tags && !used_tags.is_empty() => {
+                utils::pick_tags(used_tags, old_tags.unwrap_or_default())?
+            }
+            None => utils::user_input(
+                "Tags (space separated)",
+                old_tags.map(|tags| tags.join(" ")).as_deref(),
+                true,
+                true,
+                utils::TheWayCompletion::Tag(used_tags),
+            )?,
+        };
         let date = match old_date {
             Some(_) => utils::parse_date(&utils::user_input(
                 "Date",
@@ -146,7 +261,9 @@ impl Snippet {
             None => Utc::now(),
         };
 
-        let code = if let Some(old) = old_code {
+        let code = if let Some(code) = given.code {
+            code
+        } else if let Some(old) = old_code {
             if utils::confirm("Edit snippet? [y/N]", false)? {
                 utils::external_editor_input(old_code, &extension)?
             } else {
@@ -165,6 +282,10 @@ impl Snippet {
             }
             input
         };
+        if code.trim().is_empty() {
+            let error: color_eyre::Result<Self> = Err(LostTheWay::EmptyCode.into());
+            return error.suggestion("Close the editor without saving to cancel instead");
+        }
         Ok(Self::new(
             index,
             description,
@@ -181,23 +302,40 @@ impl Snippet {
     pub(crate) fn cmd_from_user(
         index: usize,
         code: Option<&str>,
+        description: Option<String>,
+        tags: Option<String>,
         all_tags: Vec<String>,
     ) -> color_eyre::Result<Self> {
-        let code = utils::user_input("Command", code, true, false, utils::TheWayCompletion::Empty)?;
-        let description = utils::user_input(
-            "Description",
-            None,
-            true,
-            false,
-            utils::TheWayCompletion::Empty,
-        )?;
-        let tags = utils::user_input(
-            "Tags (space separated)",
-            None,
-            true,
-            true,
-            utils::TheWayCompletion::Tag(all_tags),
-        )?;
+        // The command is still offered for editing if anything else has to be asked for
+        let code = match code {
+            Some(code) if description.is_some() && tags.is_some() => code.to_owned(),
+            code => {
+                utils::user_input("Command", code, true, false, utils::TheWayCompletion::Empty)?
+            }
+        };
+        if code.trim().is_empty() {
+            return Err(LostTheWay::EmptyCode.into());
+        }
+        let description = match description {
+            Some(description) => description,
+            None => utils::user_input(
+                "Description",
+                None,
+                true,
+                false,
+                utils::TheWayCompletion::Empty,
+            )?,
+        };
+        let tags = match tags {
+            Some(tags) => tags,
+            None => utils::user_input(
+                "Tags (space separated)",
+                None,
+                true,
+                true,
+                utils::TheWayCompletion::Tag(all_tags),
+            )?,
+        };
         Ok(Self::new(
             index,
             description,
@@ -210,21 +348,83 @@ impl Snippet {
         ))
     }
 
-    /// write snippet to database
+    /// write snippet to database, as its layout version, the serialized snippet and a CRC32 checksum
     pub(crate) fn to_bytes(&self) -> color_eyre::Result<Vec<u8>> {
-        Ok(bincode::serialize(&self)?)
+        let mut bytes = vec![SNIPPET_LAYOUT_VERSION];
+        bincode::serialize_into(&mut bytes, &self)?;
+        let checksum = crc32fast::hash(&bytes);
+        bytes.extend_from_slice(&checksum.to_le_bytes());
+        Ok(bytes)
     }
 
-    /// read snippet from database
-    pub(crate) fn from_bytes(bytes: &[u8]) -> color_eyre::Result<Self> {
-        Ok(bincode::deserialize(bytes)?)
+    /// read snippet from database, checking the CRC32 checksum stored after it
+    pub(crate) fn from_bytes(bytes: &[u8]) -> color_eyre::Result<(Self, Checksum)> {
+        let (record, stored) = bytes.split_at(bytes.len().saturating_sub(4));
+        let snippet = match record.split_first() {
+            Some((&SNIPPET_LAYOUT_VERSION, serialized)) => bincode::deserialize(serialized)?,
+            Some((version, _)) => {
+                return Err(LostTheWay::SnippetLayoutError {
+                    message: format!("Unknown snippet layout version {version}"),
+                })
+                .suggestion("Was it stored by a newer version of the-way?")
+            }
+            None => {
+                return Err(LostTheWay::SnippetLayoutError {
+                    message: "Empty snippet record".into(),
+                }
+                .into())
+            }
+        };
+        let checksum = match <[u8; 4]>::try_from(stored) {
+            Ok(stored) if u32::from_le_bytes(stored) == crc32fast::hash(record) => Checksum::Valid,
+            _ => Checksum::Mismatch,
+        };
+        Ok((snippet, checksum))
     }
 
-    /// Read snippets from a JSON stream and return consumable iterator
+    /// read snippet stored before records started with a layout version, as the bincode-serialized snippet
+    pub(crate) fn from_unversioned_bytes(bytes: &[u8]) -> color_eyre::Result<Self> {
+        let fields: UnversionedFields = bincode::deserialize(bytes)?;
+        Ok(Self {
+            index: fields.index,
+            description: fields.description,
+            language: fields.language,
+            code: fields.code,
+            extension: fields.extension,
+            tags: fields.tags,
+            date: fields.date,
+            updated: fields.updated,
+            favorite: false,
+            private: false,
+        })
+    }
+
+    /// Read snippets from a JSON stream and return consumable iterator.
+    /// The stream can hold snippet objects (one per line or pretty-printed) and arrays of them
     pub(crate) fn read(
         json_reader: &mut dyn io::Read,
     ) -> impl Iterator<Item = serde_json::Result<Self>> + '_ {
-        serde_json::Deserializer::from_reader(json_reader).into_iter::<Self>()
+        serde_json::Deserializer::from_reader(json_reader)
+            .into_iter::<serde_json::Value>()
+            .flat_map(|value| {
+                let values = match value {
+                    Ok(serde_json::Value::Array(values)) => values.into_iter().map(Ok).collect(),
+                    value => vec![value],
+                };
+                values
+                    .into_iter()
+                    .map(|value| value.and_then(serde_json::from_value))
+            })
+    }
+
+    /// Reads the snippets in one JSON value from a stream, either a snippet object or an array of them
+    pub(crate) fn from_json_value(value: serde_json::Value) -> serde_json::Result<Vec<Self>> {
+        match value {
+            serde_json::Value::Array(values) => {
+                values.into_iter().map(serde_json::from_value).collect()
+            }
+            value => Ok(vec![serde_json::from_value(value)?]),
+        }
     }
 
     /// Appends a snippet to a JSON object/file
@@ -233,6 +433,42 @@ impl Snippet {
         Ok(())
     }
 
+    /// Writes snippets as a single YAML document listing them
+    pub(crate) fn to_yaml(
+        snippets: &[Self],
+        yaml_writer: &mut dyn io::Write,
+    ) -> color_eyre::Result<()> {
+        serde_yaml::to_writer(yaml_writer, snippets)?;
+        Ok(())
+    }
+
+    /// Writes snippets as a TOML array of tables under the `snippets` key
+    pub(crate) fn to_toml(
+        snippets: &[Self],
+        toml_writer: &mut dyn io::Write,
+    ) -> color_eyre::Result<()> {
+        #[derive(Serialize)]
+        struct Snippets<'a> {
+            snippets: &'a [Snippet],
+        }
+        toml_writer.write_all(toml::to_string(&Snippets { snippets })?.as_bytes())?;
+        Ok(())
+    }
+
+    /// Reads snippets from a YAML list of them, as written by `to_yaml`
+    pub(crate) fn from_yaml(yaml: &str) -> color_eyre::Result<Vec<Self>> {
+        Ok(serde_yaml::from_str(yaml)?)
+    }
+
+    /// Reads snippets from a TOML array of tables under the `snippets` key, as written by `to_toml`
+    pub(crate) fn from_toml(toml: &str) -> color_eyre::Result<Vec<Self>> {
+        #[derive(Deserialize)]
+        struct Snippets {
+            snippets: Vec<Snippet>,
+        }
+        Ok(toml::from_str::<Snippets>(toml)?.snippets)
+    }
+
     /// Filters snippets in date range
     pub(crate) fn filter_in_date_range(
         snippets: Vec<Self>,
@@ -250,9 +486,53 @@ impl Snippet {
         from_date <= self.date && self.date < to_date
     }
 
-    /// Check if a snippet has a particular tag associated with it
+    /// Check if a snippet has a particular tag associated with it, ignoring case
     pub(crate) fn has_tag(&self, tag: &str) -> bool {
-        self.tags.contains(&tag.into())
+        let tag = tag.to_lowercase();
+        self.tags
+            .iter()
+            .any(|snippet_tag| snippet_tag.to_lowercase() == tag)
+    }
+
+    /// Lowercases tags, dropping any that become duplicates
+    pub(crate) fn lowercase_tags(&mut self) {
+        for tag in &mut self.tags {
+            *tag = tag.to_lowercase();
+        }
+        self.dedup_tags();
+    }
+
+    /// Adds tags the snippet doesn't have yet
+    pub(crate) fn add_tags(&mut self, tags: &[String]) {
+        for tag in tags {
+            if !self.tags.contains(tag) {
+                self.tags.push(tag.clone());
+            }
+        }
+    }
+
+    /// Replaces a tag (ignoring case) with another, dropping it if the snippet already has the new one
+    pub(crate) fn rename_tag(&mut self, old: &str, new: &str) {
+        let old = old.to_lowercase();
+        for tag in &mut self.tags {
+            if tag.to_lowercase() == old {
+                *tag = new.to_owned();
+            }
+        }
+        self.dedup_tags();
+    }
+
+    /// Drops repeated tags, keeping the first occurrence
+    pub(crate) fn dedup_tags(&mut self) {
+        let mut seen = HashSet::new();
+        self.tags.retain(|tag| seen.insert(tag.clone()));
+    }
+
+    /// Check if a snippet's description, tags or code match a regex
+    pub(crate) fn matches(&self, regex: &Regex) -> bool {
+        regex.is_match(&self.description)
+            || self.tags.iter().any(|tag| regex.is_match(tag))
+            || regex.is_match(&self.code)
     }
 
     /// Highlights the title: "■ #index. description | language :tag1:tag2:\n"
@@ -260,36 +540,143 @@ impl Snippet {
     /// language uses `accent_style`
     /// tags use `dim_style`
     /// everything else is in `main_style`
+    /// The description is shortened (ending with "…") to fit the title in `max_width` characters
     pub(crate) fn pretty_print_header(
         &self,
         highlighter: &CodeHighlight,
         language: &Language,
+        max_width: Option<usize>,
     ) -> Vec<(Style, String)> {
-        let mut colorized = Vec::new();
         let block = CodeHighlight::highlight_block(language.color);
-        colorized.push(block);
-        let text = format!("#{}. {} ", self.index, self.description);
-        colorized.push((highlighter.main_style, text));
-        let text = format!("| {} ", self.language);
-        colorized.push((highlighter.accent_style, text));
-        let text = format!(":{}:\n", self.tags.join(":"));
-        colorized.push((highlighter.tag_style, text));
-        colorized
+        let index = format!("#{}. ", self.index);
+        let language_text = format!("| {} ", self.language);
+        let mut tags = self.tags.clone();
+        tags.sort();
+        let tags_text = format!(":{}:", tags.join(":"));
+        let mut description = Cow::Borrowed(self.description.as_str());
+        if let Some(max_width) = max_width {
+            let rest = [&block.1, &index, " ", &language_text, &tags_text]
+                .iter()
+                .map(|text| text.chars().count())
+                .sum::<usize>();
+            let available = max_width.saturating_sub(rest).max(MIN_DESCRIPTION_WIDTH);
+            if description.chars().count() > available {
+                description = Cow::Owned(format!(
+                    "{}…",
+                    description.chars().take(available - 1).collect::<String>()
+                ));
+            }
+        }
+        vec![
+            block,
+            (highlighter.main_style, format!("{index}{description} ")),
+            (highlighter.accent_style, language_text),
+            (highlighter.tag_style, format!("{tags_text}\n")),
+        ]
     }
 
+    /// Highlights the header and code, showing the parameters of shell snippets in `tag_style`
     pub(crate) fn pretty_print(
         &self,
         highlighter: &CodeHighlight,
         language: &Language,
+        max_width: Option<usize>,
     ) -> color_eyre::Result<Vec<(Style, String)>> {
         let mut colorized = vec![(Style::default(), String::from("\n"))];
-        colorized.extend_from_slice(&self.pretty_print_header(highlighter, language));
+        colorized.extend_from_slice(&self.pretty_print_header(highlighter, language, max_width));
         colorized.push((Style::default(), String::from("\n")));
-        colorized.extend_from_slice(&highlighter.highlight_code(&self.code, &self.extension)?);
+        let code = highlighter.highlight_code(&self.code, &self.extension)?;
+        if self.is_shell_snippet() {
+            colorized.extend(highlight_parameters(&code, highlighter.tag_style)?);
+        } else {
+            colorized.extend(code);
+        }
         colorized.push((Style::default(), String::from("\n\n")));
         Ok(colorized)
     }
 
+    /// Like `pretty_print` but prints the code line by line as it's highlighted.
+    /// With `with_dates`, a line with the creation and update dates (in `tag_style`) follows the header
+    pub(crate) fn pretty_print_streaming(
+        &self,
+        highlighter: &CodeHighlight,
+        language: &Language,
+        max_width: Option<usize>,
+        with_dates: bool,
+        printer: &mut utils::SmartPrinter,
+    ) -> color_eyre::Result<()> {
+        printer.print(&[(Style::default(), "\n")])?;
+        printer.print(&self.pretty_print_header(highlighter, language, max_width))?;
+        if with_dates {
+            printer.print(&[(highlighter.tag_style, self.dates_line().as_str())])?;
+        }
+        printer.print(&[(Style::default(), "\n")])?;
+        let shell = self.is_shell_snippet();
+        highlighter.highlight_code_lines(&self.code, &self.extension, |line| {
+            if shell {
+                printer.print(&highlight_parameters(line, highlighter.tag_style)?)
+            } else {
+                printer.print(line)
+            }
+        })?;
+        printer.print(&[(Style::default(), "\n\n")])?;
+        Ok(())
+    }
+
+    /// "created <date>, updated <date>" line
+    fn dates_line(&self) -> String {
+        format!(
+            "created {}, updated {}\n",
+            self.date.date_naive().format("%Y-%m-%d"),
+            self.updated.date_naive().format("%Y-%m-%d")
+        )
+    }
+
+    /// Comment line with the snippet's index and description, in the snippet's language
+    pub(crate) fn comment_header(&self) -> String {
+        let (start, end) = language::comment_delimiters(&self.language);
+        format!("{start} #{}. {}{end}\n", self.index, self.description)
+    }
+
+    /// Markdown section with an H3 heading, the code in a fenced block, and a line of tags
+    pub(crate) fn to_markdown(&self) -> String {
+        // Fences must be longer than any run of backticks in the code
+        let mut fence = "```".to_owned();
+        while self.code.contains(&fence) {
+            fence.push('`');
+        }
+        let mut markdown = format!(
+            "### #{}. {}\n\n{fence}{}\n{}",
+            self.index, self.description, self.language, self.code
+        );
+        if !self.code.ends_with('\n') {
+            markdown.push('\n');
+        }
+        markdown.push_str(&fence);
+        markdown.push('\n');
+        if !self.tags.is_empty() {
+            markdown.push_str(&format!("\nTags: {}\n", self.tags.join(", ")));
+        }
+        markdown
+    }
+
+    /// HTML section with an H3 heading, the syntax highlighted code, and a line of tags
+    pub(crate) fn to_html(&self, highlighter: &CodeHighlight) -> color_eyre::Result<String> {
+        let mut html = format!(
+            "<h3>#{}. {}</h3>\n{}\n",
+            self.index,
+            utils::escape_html(&self.description),
+            highlighter.highlight_code_html(&self.code, &self.extension)?
+        );
+        if !self.tags.is_empty() {
+            html.push_str(&format!(
+                "<p>Tags: {}</p>\n",
+                utils::escape_html(&self.tags.join(", "))
+            ));
+        }
+        Ok(html)
+    }
+
     fn is_shell_snippet(&self) -> bool {
         // sh, bash, csh, tcsh, shell, zsh, fish
         matches!(
@@ -299,29 +686,49 @@ impl Snippet {
     }
 
     /// If snippet is a shell snippet, interactively fill parameters
-    pub(crate) fn fill_snippet(&self, highlight_style: Style) -> color_eyre::Result<Cow<str>> {
+    pub(crate) fn fill_snippet(&self, highlight_style: Style) -> color_eyre::Result<Cow<'_, str>> {
         // other languages, return as is
         if !self.is_shell_snippet() {
             return Ok(Cow::Borrowed(self.code.as_str()));
         }
-        // Matches the param or param=value **inside** the angular brackets
-        let re1 = Regex::new("<(?P<parameter>[^<>]+)>")?;
-        // Matches <param> or <param=value>
-        let re2 = Regex::new("(?P<match><[^<>]+>)")?;
-
-        // Highlight parameters to fill
-        eprintln!(
-            "{}",
-            re2.replace_all(&self.code, |caps: &regex::Captures| {
-                utils::highlight_string(&caps["match"], highlight_style)
-            })
-        );
+        self.fill_placeholders(&parameter_regex()?, &HashMap::new(), highlight_style)
+    }
+
+    /// Fills {{name}} and {{name=default}} placeholders in the code (whatever the language)
+    /// with the given values, asking the user for the rest
+    pub(crate) fn fill_template(
+        &self,
+        values: &HashMap<String, String>,
+        highlight_style: Style,
+    ) -> color_eyre::Result<Cow<'_, str>> {
+        self.fill_placeholders(&template_regex()?, values, highlight_style)
+    }
+
+    /// Fills placeholders matched by `placeholder` (with `name` and optional `default` groups)
+    /// with the given values, asking the user for the rest
+    fn fill_placeholders(
+        &self,
+        placeholder: &Regex,
+        values: &HashMap<String, String>,
+        highlight_style: Style,
+    ) -> color_eyre::Result<Cow<'_, str>> {
+        let mut filled_parameters = values.clone();
+        let missing = placeholder
+            .captures_iter(&self.code)
+            .any(|capture| !filled_parameters.contains_key(&capture["name"]));
+        if values.is_empty() || missing {
+            // Highlight parameters to fill. Part of the prompt, so always shown whatever the log level
+            eprintln!(
+                "{}",
+                placeholder.replace_all(&self.code, |caps: &regex::Captures| {
+                    utils::highlight_string(&caps[0], highlight_style)
+                })
+            );
+        }
         // Ask user to fill in (unique) parameters
-        let mut filled_parameters = HashMap::new();
-        for capture in re1.captures_iter(&self.code) {
-            let mut parts = capture["parameter"].split('=');
-            let parameter_name = parts.next().unwrap().to_owned();
-            let default = parts.next();
+        for capture in placeholder.captures_iter(&self.code) {
+            let parameter_name = capture["name"].to_owned();
+            let default = capture.name("default").map(|default| default.as_str());
             if let std::collections::hash_map::Entry::Vacant(e) =
                 filled_parameters.entry(parameter_name.clone())
             {
@@ -337,12 +744,10 @@ impl Snippet {
         }
 
         // Replace parameters in code
-        Ok(re2.replace_all(&self.code, |caps: &regex::Captures| {
-            let parameter = caps["match"][1..caps["match"].len() - 1]
-                .split('=')
-                .next()
-                .unwrap();
-            &filled_parameters[parameter]
-        }))
+        Ok(
+            placeholder.replace_all(&self.code, |caps: &regex::Captures| {
+                filled_parameters[&caps["name"]].clone()
+            }),
+        )
     }
 }
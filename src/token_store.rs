@@ -0,0 +1,93 @@
+//! Keeping the GitHub access token in the OS keyring instead of the configuration file
+use crate::configuration::TheWayConfig;
+
+/// Keyring entry holding the token
+#[cfg(feature = "keyring")]
+const KEYRING_USER: &str = "github_access_token";
+
+/// Somewhere outside the configuration file to keep the GitHub access token
+pub trait TokenStore {
+    /// The stored token, if there is one
+    fn get(&self) -> color_eyre::Result<Option<String>>;
+    /// Stores the token, replacing any previous one
+    fn set(&self, token: &str) -> color_eyre::Result<()>;
+}
+
+/// The OS keyring: macOS Keychain, Windows Credential Manager,
+/// or the Secret Service (GNOME Keyring, KWallet, ...) on Linux and the BSDs, which keeps it across reboots.
+/// Only available when the-way is built with the "keyring" feature
+pub struct Keyring;
+
+/// Suggestion for keyring errors, most likely from a missing Secret Service on Linux
+#[cfg(feature = "keyring")]
+const KEYRING_SUGGESTION: &str = "On Linux, make sure a Secret Service provider \
+    (like GNOME Keyring or KWallet) is running, or set use_keyring = false in the configuration file";
+
+#[cfg(feature = "keyring")]
+impl TokenStore for Keyring {
+    fn get(&self) -> color_eyre::Result<Option<String>> {
+        use color_eyre::Help;
+        match keyring::Entry::new(crate::utils::NAME, KEYRING_USER)?.get_password() {
+            Ok(token) => Ok(Some(token)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(e).suggestion(KEYRING_SUGGESTION),
+        }
+    }
+
+    fn set(&self, token: &str) -> color_eyre::Result<()> {
+        use color_eyre::Help;
+        keyring::Entry::new(crate::utils::NAME, KEYRING_USER)?
+            .set_password(token)
+            .suggestion(KEYRING_SUGGESTION)
+    }
+}
+
+#[cfg(not(feature = "keyring"))]
+impl TokenStore for Keyring {
+    fn get(&self) -> color_eyre::Result<Option<String>> {
+        Ok(None)
+    }
+
+    fn set(&self, _token: &str) -> color_eyre::Result<()> {
+        use color_eyre::Help;
+        Err(crate::errors::LostTheWay::ConfigError {
+            message: "the-way was built without keyring support".into(),
+        })
+        .suggestion(
+            "Reinstall with `cargo install the-way --features keyring`, \
+            or set use_keyring = false in the configuration file",
+        )
+    }
+}
+
+/// GitHub access token from $THE_WAY_GITHUB_TOKEN (given as `env_token`), the configuration file,
+/// or (with `use_keyring` set) the token store, in that order
+pub fn resolve_github_token(
+    env_token: Option<String>,
+    config: &TheWayConfig,
+    store: &dyn TokenStore,
+) -> color_eyre::Result<Option<String>> {
+    if let Some(token) = env_token.or_else(|| config.github_access_token.clone()) {
+        return Ok(Some(token));
+    }
+    if config.use_keyring {
+        return store.get();
+    }
+    Ok(None)
+}
+
+/// Saves a GitHub access token to the token store if `use_keyring` is set,
+/// keeping it out of the configuration file, and to the configuration file otherwise
+pub fn save_github_token(
+    config: &mut TheWayConfig,
+    token: &str,
+    store: &dyn TokenStore,
+) -> color_eyre::Result<()> {
+    if config.use_keyring {
+        store.set(token)?;
+        config.github_access_token = None;
+    } else {
+        config.github_access_token = Some(token.to_owned());
+    }
+    Ok(())
+}
@@ -1,12 +1,13 @@
 use std::collections::HashSet;
 use std::io::{IsTerminal, Write};
+use std::ops::RangeInclusive;
 use std::process::{Command, Stdio};
 use std::str;
 
 use chrono::{DateTime, NaiveDateTime, Utc};
 use chrono_english::{parse_date_string, Dialect};
 use color_eyre::Help;
-use dialoguer::{Completion, Confirm, Editor, Input};
+use dialoguer::{Completion, Confirm, Editor, Input, MultiSelect};
 use syntect::highlighting::Style;
 use syntect::util::as_24_bit_terminal_escaped;
 
@@ -15,6 +16,9 @@ use crate::errors::LostTheWay;
 /// To clear ANSI styling
 pub const END_ANSI: &str = "\x1b[0m";
 
+/// Placeholder in `copy_cmd` replaced by the path to a file containing the snippet
+const FILE_PLACEHOLDER: &str = "{file}";
+
 /// language color box
 pub const BOX: &str = "\u{25a0}";
 
@@ -39,6 +43,35 @@ pub(crate) fn get_default_copy_cmd() -> Option<String> {
     }
 }
 
+/// Defines the default commands for opening a URL in a browser, according to the detected OS.
+pub(crate) fn get_default_open_cmd() -> Option<&'static str> {
+    if cfg!(target_os = "linux") {
+        Some("xdg-open")
+    } else if cfg!(target_os = "macos") {
+        Some("open")
+    } else if cfg!(target_os = "android") {
+        Some("termux-open-url")
+    } else {
+        None
+    }
+}
+
+/// Open a URL in the default browser
+pub fn open_url(url: &str) -> color_eyre::Result<()> {
+    let open_cmd = get_default_open_cmd().ok_or(LostTheWay::OutOfCheeseError {
+        message: "No command found for opening a browser on this OS".into(),
+    })?;
+    Command::new(open_cmd)
+        .arg(url)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| LostTheWay::OutOfCheeseError {
+            message: format!("{e}: is {open_cmd} available?"),
+        })?;
+    Ok(())
+}
+
 /// Set clipboard contents to text
 /// See [issue](https://github.com/aweinstock314/rust-clipboard/issues/28#issuecomment-534295371)
 pub fn copy_to_clipboard(copy_cmd_field: &Option<String>, text: &str) -> color_eyre::Result<()> {
@@ -63,11 +96,45 @@ pub fn copy_to_clipboard(copy_cmd_field: &Option<String>, text: &str) -> color_e
                 // Should never fails due to previous checking
                 _ => unreachable!(),
             };
-            eprintln!("The `copy_cmd` field is empty, defaulting to `{cmd}`");
+            log::info!("The `copy_cmd` field is empty, defaulting to `{cmd}`");
             (cmd, args)
         }
     };
 
+    // Commands with a {file}/{} placeholder get a path to a file with the text instead of stdin
+    if copy_args
+        .iter()
+        .any(|arg| arg.contains(FILE_PLACEHOLDER) || arg == "{}")
+    {
+        let mut file = tempfile::NamedTempFile::new()?;
+        file.write_all(text.as_bytes())?;
+        file.flush()?;
+        let path = file.path().to_string_lossy();
+        let status = Command::new(copy_cmd)
+            .args(copy_args.iter().map(|arg| {
+                if arg == "{}" {
+                    path.to_string()
+                } else {
+                    arg.replace(FILE_PLACEHOLDER, &path)
+                }
+            }))
+            .status()
+            .map_err(|e| LostTheWay::ClipboardError {
+                message: format!(
+                    "{e}: is {copy_cmd} available? Also check your `copy_cmd` settings ({})",
+                    copy_cmd_field.as_ref().unwrap()
+                ),
+            })?;
+        if !status.success() {
+            return Err(LostTheWay::ClipboardError {
+                message: format!("{copy_cmd} exited with {status}"),
+            }
+            .into());
+        }
+        // The temporary file is deleted when dropped
+        return Ok(());
+    }
+
     let mut child = Command::new(copy_cmd)
         .args(copy_args)
         .stdin(Stdio::piped())
@@ -95,14 +162,65 @@ pub fn copy_to_clipboard(copy_cmd_field: &Option<String>, text: &str) -> color_e
     Ok(())
 }
 
+/// Runs a command (split by whitespace, like `copy_cmd`, so quoted arguments aren't supported)
+/// with `input` piped to its stdin and returns its stdout.
+/// Input is written from a separate thread so a command streaming its output back can't deadlock on full pipes
+pub fn pipe_to_command(command: &str, input: &str) -> color_eyre::Result<String> {
+    let mut parts = command.split_whitespace();
+    let program = parts.next().ok_or(LostTheWay::ConfigError {
+        message: "Empty command".into(),
+    })?;
+    let mut child = Command::new(program)
+        .args(parts)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let mut stdin = child.stdin.take().ok_or(LostTheWay::OutOfCheeseError {
+        message: format!("Could not access stdin of {program}"),
+    })?;
+    let input = input.to_owned();
+    // Dropping stdin at the end of the thread closes it
+    let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let output = child.wait_with_output()?;
+    // A command that exits without reading all its input is fine, as long as it succeeded
+    let _ = writer.join();
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        Err(LostTheWay::OutOfCheeseError {
+            message: format!("{program} exited with {}", output.status),
+        }
+        .into())
+    }
+}
+
 /// Splits input by space
 pub fn split_tags(input: &str) -> Vec<String> {
+    let mut seen = HashSet::new();
     input
         .split(' ')
         .map(|word| word.trim().to_owned())
+        .filter(|tag| seen.insert(tag.clone()))
         .collect::<Vec<String>>()
 }
 
+/// Escapes text for use in HTML element content
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
 /// Converts an array of bytes to a string
 pub fn u8_to_str(input: &[u8]) -> color_eyre::Result<String> {
     Ok(str::from_utf8(input)?.to_owned())
@@ -130,13 +248,42 @@ pub fn make_indices_string(index_list: &[usize]) -> color_eyre::Result<Vec<u8>>
 
 /// Makes a date from a string, can be colloquial like "next Friday"
 pub fn parse_date(date_string: &str) -> color_eyre::Result<DateTime<Utc>> {
-    if date_string.to_ascii_lowercase() == "today" {
+    if date_string.eq_ignore_ascii_case("today") {
         Ok(Utc::now())
     } else {
         Ok(parse_date_string(date_string, Utc::now(), Dialect::Uk)?)
     }
 }
 
+/// Splits a "name=value" argument at the first "="
+pub fn parse_key_value(argument: &str) -> Result<(String, String), String> {
+    argument
+        .split_once('=')
+        .map(|(name, value)| (name.to_owned(), value.to_owned()))
+        .ok_or_else(|| format!("expected NAME=VALUE, got {argument:?}"))
+}
+
+/// Parses a snippet index, which starts at 1
+pub fn parse_snippet_index(argument: &str) -> Result<usize, String> {
+    match argument.parse::<usize>() {
+        Ok(0) => Err("snippet indices start at 1".to_owned()),
+        Ok(index) => Ok(index),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Parses an "<start>-<end>" argument into the indices it covers, None if it isn't a range.
+/// Only digits are allowed on either side, so descriptions like "+1-2" aren't ranges
+pub fn parse_index_range(argument: &str) -> Option<RangeInclusive<usize>> {
+    let (start, end) = argument.split_once('-')?;
+    let is_number = |part: &str| !part.is_empty() && part.bytes().all(|byte| byte.is_ascii_digit());
+    if !(is_number(start) && is_number(end)) {
+        return None;
+    }
+    let (start, end) = (start.parse::<usize>().ok()?, end.parse::<usize>().ok()?);
+    (start <= end).then_some(start..=end)
+}
+
 /// Some(date) => date
 /// None => minimum possible date
 pub fn date_start(from_date: Option<DateTime<Utc>>) -> DateTime<Utc> {
@@ -191,6 +338,33 @@ pub fn user_input(
     }
 }
 
+/// Lets the user check existing tags (with `checked` ones checked already), then type in new ones.
+/// Returns all tags space separated
+pub fn pick_tags(used_tags: Vec<String>, checked: &[String]) -> color_eyre::Result<String> {
+    let theme = dialoguer::theme::ColorfulTheme::default();
+    let defaults = used_tags
+        .iter()
+        .map(|tag| checked.contains(tag))
+        .collect::<Vec<_>>();
+    let mut tags = MultiSelect::with_theme(&theme)
+        .with_prompt("Tags (space to toggle, enter to accept)")
+        .items(&used_tags)
+        .defaults(&defaults)
+        .interact()?
+        .into_iter()
+        .map(|i| used_tags[i].clone())
+        .collect::<Vec<_>>();
+    let new_tags = user_input(
+        "New tags (space separated)",
+        None,
+        false,
+        true,
+        TheWayCompletion::Tag(used_tags),
+    )?;
+    tags.extend(split_tags(&new_tags));
+    Ok(tags.join(" "))
+}
+
 /// Get a yes/no answer from the user
 pub fn confirm(prompt: &str, default: bool) -> color_eyre::Result<bool> {
     let theme = dialoguer::theme::ColorfulTheme::default();
@@ -201,9 +375,14 @@ pub fn confirm(prompt: &str, default: bool) -> color_eyre::Result<bool> {
         .interact()?)
 }
 
-/// Make an indicatif spinner with given message
+/// Make an indicatif spinner with given message.
+/// The spinner is hidden if stderr is not a TTY, to keep redirected output clean
 pub fn get_spinner(message: &str) -> indicatif::ProgressBar {
-    let spinner = indicatif::ProgressBar::new_spinner();
+    let spinner = if std::io::stderr().is_terminal() {
+        indicatif::ProgressBar::new_spinner()
+    } else {
+        indicatif::ProgressBar::hidden()
+    };
     spinner.set_message(message.to_owned());
     spinner
 }
@@ -247,19 +426,86 @@ pub fn smart_print(
     write!(
         grep_cli::stdout(termcolor::ColorChoice::Auto),
         "{}",
-        if !plain & (std::io::stdout().is_terminal() | colorize) {
-            highlight_strings(inputs, bg)
-        } else {
-            inputs
-                .iter()
-                .map(|(_, s)| s.to_string())
-                .collect::<Vec<_>>()
-                .join("")
-        }
+        maybe_highlight(inputs, bg, std::io::stdout().is_terminal(), colorize, plain)
+    )?;
+    Ok(())
+}
+
+/// Print to stderr with the same colorization rules as `smart_print`
+pub fn smart_eprint(
+    inputs: &[(Style, String)],
+    bg: bool,
+    colorize: bool,
+    plain: bool,
+) -> color_eyre::Result<()> {
+    write!(
+        std::io::stderr(),
+        "{}",
+        maybe_highlight(inputs, bg, std::io::stderr().is_terminal(), colorize, plain)
     )?;
     Ok(())
 }
 
+/// Prints piece by piece to stdout with the same colorization rules as `smart_print`,
+/// so large outputs don't have to be collected in memory first
+pub struct SmartPrinter {
+    writer: grep_cli::StandardStream,
+    color: bool,
+}
+
+impl SmartPrinter {
+    pub fn new(colorize: bool, plain: bool) -> Self {
+        Self {
+            writer: grep_cli::stdout(termcolor::ColorChoice::Auto),
+            color: !plain & (std::io::stdout().is_terminal() | colorize),
+        }
+    }
+
+    pub fn print<S: AsRef<str>>(&mut self, inputs: &[(Style, S)]) -> color_eyre::Result<()> {
+        for (style, s) in inputs {
+            if self.color {
+                write!(
+                    self.writer,
+                    "{}",
+                    as_24_bit_terminal_escaped(&[(*style, s.as_ref())], false)
+                )?;
+            } else {
+                write!(self.writer, "{}", s.as_ref())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Width of the terminal stdout is connected to, $COLUMNS takes precedence
+pub fn terminal_width() -> Option<usize> {
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.parse().ok())
+        .or_else(|| {
+            terminal_size::terminal_size().map(|(terminal_size::Width(width), _)| width.into())
+        })
+}
+
+/// Highlight text if not plain and either in a TTY or forced to colorize
+fn maybe_highlight(
+    inputs: &[(Style, String)],
+    bg: bool,
+    is_terminal: bool,
+    colorize: bool,
+    plain: bool,
+) -> String {
+    if !plain & (is_terminal | colorize) {
+        highlight_strings(inputs, bg)
+    } else {
+        inputs
+            .iter()
+            .map(|(_, s)| s.to_string())
+            .collect::<Vec<_>>()
+            .join("")
+    }
+}
+
 #[derive(Debug)]
 pub enum TheWayCompletion {
     Language(Vec<String>),
@@ -287,7 +533,7 @@ impl Completion for TheWayCompletion {
                     .split(' ')
                     .map(|s| s.to_string())
                     .collect::<HashSet<_>>();
-                let last_input = input.split(' ').last().unwrap_or("");
+                let last_input = input.split(' ').next_back().unwrap_or("");
                 let last_space = input.rfind(' ').unwrap_or(0);
                 let matches = tags
                     .iter()
@@ -1,6 +1,9 @@
-use std::collections::HashSet;
-use std::fs;
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, Read, Write};
+use std::net::{TcpListener, TcpStream};
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::{fs, io};
 
 use assert_cmd::Command;
 use chrono::Utc;
@@ -9,8 +12,16 @@ use expectrl::Regex;
 use predicates::prelude::*;
 use tempfile::{tempdir, TempDir};
 use the_way::configuration::TheWayConfig;
-use the_way::gist::{Gist, GistClient, GistContent, UpdateGistPayload};
+use the_way::gist::{
+    rate_limit_message, rate_limit_wait, CreateGistPayload, Gist, GistClient, GistContent,
+    UpdateGistPayload,
+};
+use the_way::gitlab::{self, GitLabClient};
+use the_way::language::get_languages;
+use the_way::the_way::filter::Filters;
 use the_way::the_way::snippet::Snippet;
+use the_way::the_way::TheWay;
+use the_way::token_store::{resolve_github_token, save_github_token, TokenStore};
 
 fn setup_the_way() -> color_eyre::Result<(TempDir, PathBuf)> {
     let temp_dir = tempdir()?;
@@ -28,6 +39,172 @@ themes_dir = \"{}\"",
     Ok((temp_dir, config_file))
 }
 
+/// Minimal stand-in for the GitHub Gist API, serving Gists from memory on a background thread.
+/// Point the-way at it with $THE_WAY_GITHUB_API_URL
+struct StubGistApi {
+    url: String,
+    gists: Arc<Mutex<HashMap<String, serde_json::Value>>>,
+    /// Method, path and body of each request received
+    requests: Arc<Mutex<Vec<(String, String, String)>>>,
+}
+
+impl StubGistApi {
+    fn start() -> color_eyre::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let url = format!("http://{}", listener.local_addr()?);
+        let gists = Arc::new(Mutex::new(HashMap::new()));
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let (server_gists, server_requests) = (Arc::clone(&gists), Arc::clone(&requests));
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                // A failed request only fails the command under test
+                let _ = stub_gist_response(stream, &server_gists, &server_requests);
+            }
+        });
+        Ok(Self {
+            url,
+            gists,
+            requests,
+        })
+    }
+
+    /// Adds a Gist with the given (file name, content) pairs, returns its URL
+    fn add_gist(&self, description: &str, files: &[(&str, &str)]) -> String {
+        let mut gists = self.gists.lock().unwrap();
+        let id = format!("{:032x}", gists.len() + 1);
+        let mut gist = serde_json::json!({
+            "html_url": format!("https://gist.github.com/{id}"),
+            "id": id,
+            "created_at": "2020-01-02T03:04:05Z",
+            "updated_at": "2021-06-07T08:09:10Z",
+            "description": description,
+            "files": {},
+        });
+        for (file_name, content) in files {
+            gist["files"][*file_name] = stub_gist_file(file_name, content);
+        }
+        let url = gist["html_url"].as_str().unwrap().to_owned();
+        gists.insert(id, gist);
+        url
+    }
+
+    /// The Gist with this URL, as the API would return it
+    fn gist(&self, url: &str) -> Option<serde_json::Value> {
+        let id = url.rsplit('/').next()?;
+        self.gists.lock().unwrap().get(id).cloned()
+    }
+
+    /// Adds or replaces a file of the Gist with this URL
+    fn add_file(&self, url: &str, file_name: &str, content: &str) {
+        let id = url.rsplit('/').next().unwrap();
+        self.gists.lock().unwrap().get_mut(id).unwrap()["files"][file_name] =
+            stub_gist_file(file_name, content);
+    }
+
+    /// Method, path and body of each request received so far
+    fn requests(&self) -> Vec<(String, String, String)> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+/// A Gist file as the API returns it, with its language guessed from the extension
+fn stub_gist_file(file_name: &str, content: &str) -> serde_json::Value {
+    let language = match file_name.rsplit('.').next() {
+        Some("rs") => "Rust",
+        Some("py") => "Python",
+        Some("sh") => "Shell",
+        Some("md") => "Markdown",
+        _ => "Text",
+    };
+    serde_json::json!({"content": content, "language": language})
+}
+
+/// Answers a single request to the stub Gist API
+fn stub_gist_response(
+    mut stream: TcpStream,
+    gists: &Mutex<HashMap<String, serde_json::Value>>,
+    requests: &Mutex<Vec<(String, String, String)>>,
+) -> io::Result<()> {
+    let mut reader = io::BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_owned();
+    let path = parts.next().unwrap_or_default().to_owned();
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        reader.read_line(&mut header)?;
+        if header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or_default();
+            }
+        }
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    let body = String::from_utf8_lossy(&body).into_owned();
+    requests
+        .lock()
+        .unwrap()
+        .push((method.clone(), path.clone(), body.clone()));
+
+    let mut gists = gists.lock().unwrap();
+    let id = path.trim_start_matches("/gists").trim_start_matches('/');
+    let (status, response) = match (method.as_str(), gists.get_mut(id)) {
+        ("GET", Some(gist)) => ("200 OK", gist.to_string()),
+        ("PATCH", Some(gist)) => {
+            let payload: serde_json::Value = serde_json::from_str(&body)?;
+            for (file_name, file) in payload["files"].as_object().into_iter().flatten() {
+                let files = gist["files"].as_object_mut().unwrap();
+                match file["content"].as_str() {
+                    Some(content) => {
+                        files.insert(file_name.clone(), stub_gist_file(file_name, content));
+                    }
+                    None => {
+                        files.remove(file_name);
+                    }
+                }
+            }
+            gist["updated_at"] = Utc::now().to_rfc3339().into();
+            ("200 OK", gist.to_string())
+        }
+        ("DELETE", Some(_)) => {
+            gists.remove(id);
+            ("204 No Content", String::new())
+        }
+        ("POST", None) if id.is_empty() => {
+            let payload: serde_json::Value = serde_json::from_str(&body)?;
+            let id = format!("{:032x}", gists.len() + 1);
+            let mut gist = serde_json::json!({
+                "html_url": format!("https://gist.github.com/{id}"),
+                "id": id,
+                "created_at": Utc::now().to_rfc3339(),
+                "updated_at": Utc::now().to_rfc3339(),
+                "description": payload["description"],
+                "files": {},
+            });
+            for (file_name, file) in payload["files"].as_object().into_iter().flatten() {
+                gist["files"][file_name] =
+                    stub_gist_file(file_name, file["content"].as_str().unwrap_or_default());
+            }
+            let response = gist.to_string();
+            gists.insert(id, gist);
+            ("201 Created", response)
+        }
+        _ => ("404 Not Found", r#"{"message":"Not Found"}"#.to_owned()),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: application/json\r\n\
+        Content-Length: {}\r\nConnection: close\r\n\r\n{response}",
+        response.len()
+    )
+}
+
 #[test]
 fn it_works() -> color_eyre::Result<()> {
     let (temp_dir, config_file) = setup_the_way()?;
@@ -42,6 +219,28 @@ fn it_works() -> color_eyre::Result<()> {
     Ok(())
 }
 
+#[test]
+fn version() -> color_eyre::Result<()> {
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.arg("--version")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(env!("CARGO_PKG_VERSION")));
+
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["config", "get", "--verbose"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(config_file.to_str().unwrap()))
+        .stdout(predicate::str::contains(
+            temp_dir.path().join("db").to_str().unwrap(),
+        ));
+    temp_dir.close()?;
+    Ok(())
+}
+
 #[test]
 fn change_config_file() -> color_eyre::Result<()> {
     // Test nonexistent file
@@ -105,7 +304,7 @@ fn add_snippet_interactive(
     index: usize,
 ) -> color_eyre::Result<()> {
     let executable = env!("CARGO_BIN_EXE_the-way");
-    p.send_line(&format!("{executable} new"))?;
+    p.send_line(format!("{executable} new"))?;
     p.expect("Description")?;
     p.send_line(&snippet.description)?;
     p.expect("Language")?;
@@ -125,14 +324,14 @@ fn add_snippet_interactive(
 
 fn add_two_snippets_interactive(config_file: &Path) -> color_eyre::Result<()> {
     let mut p = spawn_bash()?;
-    p.send_line(&format!(
+    p.send_line(format!(
         "export THE_WAY_CONFIG={}",
         config_file.to_string_lossy()
     ))?;
 
     let executable = env!("CARGO_BIN_EXE_the-way");
     p.expect_prompt()?;
-    p.send_line(&format!("{executable} config get"))?;
+    p.send_line(format!("{executable} config get"))?;
     p.expect(config_file.to_string_lossy().as_ref())?;
     add_snippet_interactive(
         &mut p,
@@ -167,13 +366,13 @@ fn add_two_snippets_interactive(config_file: &Path) -> color_eyre::Result<()> {
 
 fn change_snippet_interactive(config_file: &Path) -> color_eyre::Result<()> {
     let mut p = spawn_bash()?;
-    p.send_line(&format!(
+    p.send_line(format!(
         "export THE_WAY_CONFIG={}",
         config_file.to_string_lossy()
     ))?;
     let executable = env!("CARGO_BIN_EXE_the-way");
     p.expect_prompt()?;
-    p.send_line(&format!("{executable} config get"))?;
+    p.send_line(format!("{executable} config get"))?;
     p.expect(config_file.to_string_lossy().as_ref())?;
     add_snippet_interactive(
         &mut p,
@@ -189,7 +388,7 @@ fn change_snippet_interactive(config_file: &Path) -> color_eyre::Result<()> {
         ),
         1,
     )?;
-    p.send_line(&format!("{executable} edit 1"))?;
+    p.send_line(format!("{executable} edit 1"))?;
     p.expect("Description")?;
     p.send_line("test description 2")?;
     p.expect("Language")?;
@@ -202,24 +401,71 @@ fn change_snippet_interactive(config_file: &Path) -> color_eyre::Result<()> {
     p.send_line("")?;
     p.expect("Snippet #1 changed")?;
     p.expect_prompt()?;
-    p.send_line(&format!("{executable} view 1"))?;
+    p.send_line(format!("{executable} view 1"))?;
     p.expect("test description 2")?;
     Ok(())
 }
 
+fn abort_edit_interactive(config_file: &Path) -> color_eyre::Result<()> {
+    let mut p = spawn_bash()?;
+    p.send_line(format!(
+        "export THE_WAY_CONFIG={}",
+        config_file.to_string_lossy()
+    ))?;
+    let executable = env!("CARGO_BIN_EXE_the-way");
+    p.expect_prompt()?;
+    add_snippet_interactive(
+        &mut p,
+        &Snippet::new(
+            1,
+            "test description 1".to_string(),
+            "rust".to_string(),
+            "rs".to_string(),
+            "tag1 tag2",
+            Utc::now(),
+            Utc::now(),
+            "code".to_string(),
+        ),
+        1,
+    )?;
+    // `true` exits without touching the file, like closing the editor without saving,
+    // `truncate` saves it empty
+    for (editor, error) in [
+        ("true", "Editor closed without saving"),
+        ("'truncate -s 0'", "You can't record a snippet without code"),
+    ] {
+        p.send_line(format!("EDITOR={editor} {executable} edit 1"))?;
+        p.expect("Description")?;
+        p.send_line("test description 2")?;
+        p.expect("Language")?;
+        p.send_line("")?;
+        p.expect("Tags")?;
+        p.send_line("")?;
+        p.expect("Date")?;
+        p.send_line("")?;
+        p.expect("Edit snippet")?;
+        p.send_line("y")?;
+        p.expect(error)?;
+        p.expect_prompt()?;
+    }
+    p.send_line(format!("{executable} view 1"))?;
+    p.expect("test description 1")?;
+    Ok(())
+}
+
 fn add_two_cmd_snippets_interactive(config_file: &Path) -> color_eyre::Result<()> {
     let mut p = spawn_bash()?;
-    p.send_line(&format!(
+    p.send_line(format!(
         "export THE_WAY_CONFIG={}",
         config_file.to_string_lossy()
     ))?;
 
     let executable = env!("CARGO_BIN_EXE_the-way");
     p.expect_prompt()?;
-    p.send_line(&format!("{executable} config get"))?;
+    p.send_line(format!("{executable} config get"))?;
     p.expect(config_file.to_string_lossy().as_ref())?;
     // as argument
-    p.send_line(&format!("{executable} cmd \"shell snippet 1\""))?;
+    p.send_line(format!("{executable} cmd \"shell snippet 1\""))?;
     p.expect("Command")?;
     p.send_line("\n")?;
     p.expect("Description")?;
@@ -229,7 +475,7 @@ fn add_two_cmd_snippets_interactive(config_file: &Path) -> color_eyre::Result<()
     p.expect("Snippet #1 added")?;
     p.expect_prompt()?;
     // interactively
-    p.send_line(&format!("{executable} cmd"))?;
+    p.send_line(format!("{executable} cmd"))?;
     p.expect("Command")?;
     p.send_line("shell snippet 2")?;
     p.expect("Description")?;
@@ -267,6 +513,15 @@ fn change_snippet() -> color_eyre::Result<()> {
     Ok(())
 }
 
+#[test]
+fn abort_edit() -> color_eyre::Result<()> {
+    let (temp_dir, config_file) = setup_the_way()?;
+    assert!(abort_edit_interactive(&config_file).is_ok());
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
 #[test]
 fn import_single_show() -> color_eyre::Result<()> {
     let contents = r#"{"description":"test description","language":"rust","tags":["tag1","tag2"],"code":"some\ntest\ncode\n"}"#;
@@ -341,6 +596,80 @@ fn import_gist() -> color_eyre::Result<()> {
     Ok(())
 }
 
+#[test]
+fn import_multiple_gists() -> color_eyre::Result<()> {
+    let api = StubGistApi::start()?;
+    let first_url = api.add_gist(
+        "first",
+        &[("hello.rs", "fn main() {}"), ("hello.py", "print('hello')")],
+    );
+    let second_url = api.add_gist("second", &[("hello.sh", "echo hello")]);
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .env("THE_WAY_GITHUB_API_URL", &api.url)
+        .arg("import")
+        .args([
+            "-g",
+            &first_url,
+            "-g",
+            &second_url,
+            "-g",
+            "https://gist.github.com/missing",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Imported 3 snippets"))
+        .stderr(predicate::str::contains(
+            "Couldn't import from https://gist.github.com/missing",
+        ));
+    let mut cmd = Command::cargo_bin("the-way")?;
+    let output = cmd
+        .env("THE_WAY_CONFIG", &config_file)
+        .args(["list", "--porcelain"])
+        .output()?;
+    let output = String::from_utf8(output.stdout)?;
+    assert_eq!(output.lines().count(), 3);
+    let (first_id, second_id) = (
+        first_url.rsplit('/').next().unwrap(),
+        second_url.rsplit('/').next().unwrap(),
+    );
+    assert!(output.contains(&format!("{first_id} - hello.rs")));
+    assert!(output.contains(&format!("{first_id} - hello.py")));
+    assert!(output.contains(&format!("{second_id} - hello.sh")));
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn import_multiple_gists_failures() -> color_eyre::Result<()> {
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args([
+            "import",
+            "-g",
+            "https://example.com/not-a-gist",
+            "-g",
+            "https://example.com/also-not-a-gist",
+        ])
+        .assert()
+        .failure()
+        .stderr(
+            predicate::str::contains("Couldn't import from https://example.com/not-a-gist")
+                .and(predicate::str::contains(
+                    "Couldn't import from https://example.com/also-not-a-gist",
+                ))
+                .and(predicate::str::contains(
+                    "Couldn't import from any of the given Gists",
+                )),
+        );
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
 // This test is ignored because it tries to fetch a real Gist and runs into
 // Github rate limits when ran by CI.
 #[ignore]
@@ -542,19 +871,20 @@ fn copy_shell_script() -> color_eyre::Result<()> {
     Ok(())
 }
 
+#[cfg(target_os = "macos")]
 fn copy_shell_script_interactive(config_file: &Path) -> color_eyre::Result<()> {
     let mut p = spawn_bash()?;
-    p.send_line(&format!(
+    p.send_line(format!(
         "export THE_WAY_CONFIG={}",
         config_file.to_string_lossy()
     ))?;
 
     let executable = env!("CARGO_BIN_EXE_the-way");
     p.expect_prompt()?;
-    p.send_line(&format!("{executable} config get"))?;
+    p.send_line(format!("{executable} config get"))?;
     p.expect(config_file.to_string_lossy().as_ref())?;
     // add a shell snippet
-    p.send_line(&format!(
+    p.send_line(format!(
         "{} cmd \"shell snippet <param1=value1> code <param2> <param1>\"",
         executable
     ))?;
@@ -567,7 +897,7 @@ fn copy_shell_script_interactive(config_file: &Path) -> color_eyre::Result<()> {
     p.expect("Snippet #1 added")?;
     p.expect_prompt()?;
     // Test interactive copy
-    p.send_line(&format!("{executable} cp 1"))?;
+    p.send_line(format!("{executable} cp 1"))?;
     p.expect("param1")?;
     p.send_line("\n")?;
     p.expect("param2")?;
@@ -602,7 +932,7 @@ fn make_gist(config_file: &Path, client: &GistClient) -> color_eyre::Result<Gist
 
     // get gist_id from config
     std::env::set_var("THE_WAY_CONFIG", config_file);
-    let config = TheWayConfig::load();
+    let config = TheWayConfig::load(None);
     assert!(config.is_ok());
     let config = config?;
     assert!(config.gist_id.is_some());
@@ -719,21 +1049,38 @@ fn sync_edit(config_file: &Path, gist: &Gist, client: &GistClient) -> color_eyre
 fn sync_date() -> color_eyre::Result<()> {
     let (temp_dir, config_file) = setup_the_way()?;
     let token = &std::env::var("THE_WAY_GITHUB_TOKEN")?;
-    let client = GistClient::new(Some(token))?;
+    let client = GistClient::new(None, Some(token))?;
 
     // make Gist with 3 snippets
     let gist = make_gist(&config_file, &client)?;
     // make edits: edit snippet_1 in Gist, delete snippet_2 locally, delete snippet_3 in Gist, add snippet_4 to Gist
     sync_edit(&config_file, &gist, &client)?;
 
+    // dry run shows the same counts as the sync, without changing the Gist
+    let edited = client.get_gist(&gist.id)?.updated_at;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    let dry_run = cmd
+        .env("THE_WAY_CONFIG", &config_file)
+        .env("THE_WAY_GITHUB_TOKEN", token)
+        .args(["sync", "--dry-run", "date"])
+        .output()?;
+    assert!(dry_run.status.success());
+    let dry_run = String::from_utf8(dry_run.stdout)?;
+    assert_eq!(client.get_gist(&gist.id)?.updated_at, edited);
+
     // sync - downloads snippet_1 locally + deletes snippet_2 in Gist + adds snippet_3 to Gist + deletes snippet_4 from Gist
     let mut cmd = Command::cargo_bin("the-way")?;
-    cmd.env("THE_WAY_CONFIG", &config_file)
+    let output = cmd
+        .env("THE_WAY_CONFIG", &config_file)
         .env("THE_WAY_GITHUB_TOKEN", token)
         .arg("sync")
         .arg("date")
-        .assert()
-        .success();
+        .output()?;
+    assert!(output.status.success());
+    let output = String::from_utf8(output.stdout)?;
+    for line in dry_run.lines().filter(|line| line.contains("snippet(s)")) {
+        assert!(output.contains(line));
+    }
 
     // get Gist
     let gist = client.get_gist(&gist.id);
@@ -784,13 +1131,13 @@ fn sync_date() -> color_eyre::Result<()> {
 
     // check adding new snippet locally has index 4
     let mut p = spawn_bash()?;
-    p.send_line(&format!(
+    p.send_line(format!(
         "export THE_WAY_CONFIG={}",
         config_file.to_string_lossy()
     ))?;
     let executable = env!("CARGO_BIN_EXE_the-way");
     p.expect_prompt()?;
-    p.send_line(&format!("{executable} config get"))?;
+    p.send_line(format!("{executable} config get"))?;
     p.expect(config_file.to_string_lossy().as_ref())?;
     add_snippet_interactive(
         &mut p,
@@ -829,7 +1176,7 @@ fn sync_date() -> color_eyre::Result<()> {
 fn sync_local() -> color_eyre::Result<()> {
     let (temp_dir, config_file) = setup_the_way()?;
     let token = &std::env::var("THE_WAY_GITHUB_TOKEN")?;
-    let client = GistClient::new(Some(token))?;
+    let client = GistClient::new(None, Some(token))?;
 
     // make Gist with 3 snippets
     let gist = make_gist(&config_file, &client)?;
@@ -841,6 +1188,7 @@ fn sync_local() -> color_eyre::Result<()> {
     cmd.env("THE_WAY_CONFIG", &config_file)
         .env("THE_WAY_GITHUB_TOKEN", token)
         .arg("sync")
+        .arg("-f")
         .arg("local")
         .assert()
         .success();
@@ -896,13 +1244,13 @@ fn sync_local() -> color_eyre::Result<()> {
 
     // check adding new snippet locally has index 4
     let mut p = spawn_bash()?;
-    p.send_line(&format!(
+    p.send_line(format!(
         "export THE_WAY_CONFIG={}",
         config_file.to_string_lossy()
     ))?;
     let executable = env!("CARGO_BIN_EXE_the-way");
     p.expect_prompt()?;
-    p.send_line(&format!("{executable} config get"))?;
+    p.send_line(format!("{executable} config get"))?;
     p.expect(config_file.to_string_lossy().as_ref())?;
     add_snippet_interactive(
         &mut p,
@@ -934,6 +1282,139 @@ fn sync_local() -> color_eyre::Result<()> {
     Ok(())
 }
 
+#[test]
+/// Tests that `the-way sync local` won't wipe the Gist from a nearly empty database without --force
+fn sync_local_wipe_guard() -> color_eyre::Result<()> {
+    let contents = (1..=5)
+        .map(|index| {
+            format!(
+                r#"{{"description":"snippet {index}","language":"python","code":"code {index}"}}"#
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+    let api = StubGistApi::start()?;
+    assert!(stub_sync(&api, &config_file, &["local"])?.status.success());
+    let gist_url = format!("https://gist.github.com/{:032x}", 1);
+
+    // keep only one snippet locally
+    for index in 2..=5 {
+        let mut cmd = Command::cargo_bin("the-way")?;
+        cmd.env("THE_WAY_CONFIG", &config_file)
+            .args(["del", "--force", &index.to_string()])
+            .assert()
+            .success();
+    }
+    let mut p = spawn_bash()?;
+    p.send_line(format!(
+        "export THE_WAY_CONFIG={} THE_WAY_GITHUB_API_URL={} THE_WAY_GITHUB_TOKEN=stub-token",
+        config_file.to_string_lossy(),
+        api.url
+    ))?;
+    let executable = env!("CARGO_BIN_EXE_the-way");
+    p.expect_prompt()?;
+    p.send_line(format!("{executable} sync local"))?;
+    p.expect("This will delete 4 snippets from the Gist — continue?")?;
+    p.send("n")?;
+    p.expect("Doing nothing")?;
+    p.expect_prompt()?;
+    assert_eq!(
+        api.gist(&gist_url).unwrap()["files"]
+            .as_object()
+            .unwrap()
+            .len(),
+        6
+    );
+
+    assert!(stub_sync(&api, &config_file, &["--force", "local"])?
+        .status
+        .success());
+    assert_eq!(
+        api.gist(&gist_url).unwrap()["files"]
+            .as_object()
+            .unwrap()
+            .len(),
+        2
+    );
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+/// Tests that `the-way sync local` asks before deleting some snippets from the Gist,
+/// and deletes exactly what it showed
+fn sync_local_partial_delete() -> color_eyre::Result<()> {
+    let contents = [
+        r#"{"description":"first","language":"rust","code":"code\nthe\nfirst\n"}"#,
+        r#"{"description":"second","language":"rust","code":"code\nthe\nsecond\n"}"#,
+        r#"{"description":"third","language":"rust","code":"code\nthe\nthird\n"}"#,
+    ]
+    .join("\n");
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+    let api = StubGistApi::start()?;
+    let url = add_stub_the_way_gist(&api, &config_file)?;
+
+    let mut p = spawn_bash()?;
+    p.send_line(format!(
+        "export THE_WAY_CONFIG={} THE_WAY_GITHUB_API_URL={} THE_WAY_GITHUB_TOKEN=stub-token",
+        config_file.to_string_lossy(),
+        api.url
+    ))?;
+    let executable = env!("CARGO_BIN_EXE_the-way");
+    p.expect_prompt()?;
+    p.send_line(format!("{executable} sync local"))?;
+    p.expect(
+        "Will upload 2, delete 1 from the Gist, download 0, add 0 locally and delete 0 locally",
+    )?;
+    p.expect("Delete 1 snippets from the Gist and 0 snippets locally?")?;
+    p.send("n")?;
+    p.expect("Doing nothing")?;
+    p.expect_prompt()?;
+    assert!(api.requests().iter().all(|(method, _, _)| method == "GET"));
+    assert!(api.gist(&url).unwrap()["files"]
+        .as_object()
+        .unwrap()
+        .contains_key("snippet_4.rs"));
+
+    // The sync sends what the summary showed: 2 uploads, 1 deletion and the index
+    assert!(stub_sync(&api, &config_file, &["--force", "local"])?
+        .status
+        .success());
+    let updates = api
+        .requests()
+        .into_iter()
+        .filter(|(method, _, _)| method != "GET")
+        .collect::<Vec<_>>();
+    assert_eq!(updates.len(), 1);
+    let body: serde_json::Value = serde_json::from_str(&updates[0].2)?;
+    let files = body["files"].as_object().unwrap();
+    let mut file_names = files.keys().map(String::as_str).collect::<Vec<_>>();
+    file_names.sort_unstable();
+    assert_eq!(
+        file_names,
+        ["index.md", "snippet_1.rs", "snippet_2.rs", "snippet_4.rs"]
+    );
+    assert!(files["snippet_4.rs"].is_null());
+    assert_eq!(files["snippet_2.rs"]["content"], "code\nthe\nsecond\n");
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
 #[ignore]
 #[test]
 /// Tests `the-way sync gist` functionality. Needs to have the environment variable $THE_WAY_GITHUB_TOKEN set!
@@ -942,7 +1423,7 @@ fn sync_gist() -> color_eyre::Result<()> {
     let (temp_dir, config_file) = setup_the_way()?;
 
     let token = &std::env::var("THE_WAY_GITHUB_TOKEN")?;
-    let client = GistClient::new(Some(token))?;
+    let client = GistClient::new(None, Some(token))?;
 
     // make Gist with 3 snippets
     let gist = make_gist(&config_file, &client)?;
@@ -1026,13 +1507,13 @@ fn sync_gist() -> color_eyre::Result<()> {
 
     // check adding new snippet locally has index 5
     let mut p = spawn_bash()?;
-    p.send_line(&format!(
+    p.send_line(format!(
         "export THE_WAY_CONFIG={}",
         config_file.to_string_lossy()
     ))?;
     let executable = env!("CARGO_BIN_EXE_the-way");
     p.expect_prompt()?;
-    p.send_line(&format!("{executable} config get"))?;
+    p.send_line(format!("{executable} config get"))?;
     p.expect(config_file.to_string_lossy().as_ref())?;
     add_snippet_interactive(
         &mut p,
@@ -1063,3 +1544,4397 @@ fn sync_gist() -> color_eyre::Result<()> {
     temp_dir.close()?;
     Ok(())
 }
+
+#[test]
+fn bundle() -> color_eyre::Result<()> {
+    let contents = r#"{"description":"test description","language":"rust","tags":["tag1","tag2"],"code":"some\ntest\ncode\n"}"#;
+    let theme_contents = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>name</key>
+    <string>Bundled</string>
+    <key>settings</key>
+    <array>
+        <dict>
+            <key>settings</key>
+            <dict>
+                <key>background</key>
+                <string>#000000</string>
+                <key>foreground</key>
+                <string>#FFFFFF</string>
+            </dict>
+        </dict>
+    </array>
+</dict>
+</plist>"#;
+    let (temp_dir, config_file) = setup_the_way()?;
+    let theme_file = temp_dir.path().join("bundled-theme.tmTheme");
+    fs::write(&theme_file, theme_contents)?;
+
+    // import snippet and add + set custom theme
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("themes")
+        .arg("add")
+        .arg(&theme_file)
+        .assert()
+        .success();
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("themes")
+        .arg("set")
+        .arg("bundled-theme")
+        .assert()
+        .success();
+
+    // export bundle
+    let bundle_file = temp_dir.path().join("bundle.tar.gz");
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("export")
+        .arg("--bundle")
+        .arg(&bundle_file)
+        .assert()
+        .success();
+
+    // restore into a fresh config
+    let (new_temp_dir, new_config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &new_config_file)
+        .arg("import")
+        .arg("--bundle")
+        .arg(&bundle_file)
+        .assert()
+        .stdout(predicate::str::contains("Imported 1 snippets"));
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &new_config_file)
+        .arg("view")
+        .arg("1")
+        .assert()
+        .stdout(predicate::str::contains("test description"));
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &new_config_file)
+        .arg("themes")
+        .arg("get")
+        .assert()
+        .stdout(predicate::str::contains("bundled-theme"));
+    assert!(new_temp_dir
+        .path()
+        .join("themes")
+        .join("bundled-theme.tmTheme")
+        .exists());
+    drop(new_config_file);
+    new_temp_dir.close()?;
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn search_preview_cmd() -> color_eyre::Result<()> {
+    // Large enough to fill the pipes both ways if input isn't written while output is read
+    let contents = format!(
+        r#"{{"description":"test description","language":"rust","tags":["tag1","tag2"],"code":"some\ntest\ncode\n{}"}}"#,
+        "filler\\n".repeat(20_000)
+    );
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut config_contents = fs::read_to_string(&config_file)?;
+    config_contents.push_str("\nsearch_preview_cmd = 'sed s/code/previewed/'");
+    fs::write(&config_file, config_contents)?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+
+    let mut p = spawn_bash()?;
+    p.send_line(format!(
+        "export THE_WAY_CONFIG={}",
+        config_file.to_string_lossy()
+    ))?;
+    let executable = env!("CARGO_BIN_EXE_the-way");
+    p.expect_prompt()?;
+    p.send_line(format!("{executable} search"))?;
+    p.expect("previewed")?;
+    // Escape to quit search
+    p.send("\x1b")?;
+    p.expect_prompt()?;
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn new_with_tag_flags() -> color_eyre::Result<()> {
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut p = spawn_bash()?;
+    p.send_line(format!(
+        "export THE_WAY_CONFIG={}",
+        config_file.to_string_lossy()
+    ))?;
+    let executable = env!("CARGO_BIN_EXE_the-way");
+    p.expect_prompt()?;
+    p.send_line(format!("{executable} new -t rust -t async"))?;
+    p.expect("Description")?;
+    p.send_line("test description")?;
+    p.expect("Language")?;
+    p.send_line("rust")?;
+    p.expect("Code snippet")?;
+    p.send_line("code")?;
+    p.expect("Snippet #1 added")?;
+    p.expect_prompt()?;
+
+    let file = temp_dir.path().join("snippets.json");
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("export")
+        .arg(file.to_str().unwrap())
+        .assert()
+        .success();
+    let snippets = serde_json::Deserializer::from_reader(fs::File::open(&file)?)
+        .into_iter::<Snippet>()
+        .collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(snippets.len(), 1);
+    assert_eq!(snippets[0].tags, vec!["rust", "async"]);
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn warn_unsupported_language() -> color_eyre::Result<()> {
+    let supported =
+        r#"{"description":"test description 1","language":"rust","code":"some\ntest\ncode\n"}"#;
+    let unsupported =
+        r#"{"description":"test description 2","language":"kotlin","code":"some\ntest\ncode\n"}"#;
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(supported)
+        .assert()
+        .stderr(predicate::str::contains("No syntax highlighting").not());
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(unsupported)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "No syntax highlighting available for kotlin",
+        ));
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn no_spinner_without_tty() -> color_eyre::Result<()> {
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .arg("-g")
+        .arg("https://example.com/not-a-gist")
+        .assert()
+        .failure()
+        .stderr(
+            predicate::str::contains("Fetching gist")
+                .or(predicate::str::contains("Error fetching gist"))
+                .not(),
+        );
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn gist_timestamps() -> color_eyre::Result<()> {
+    let api = StubGistApi::start()?;
+    let gist_url = api.add_gist("test gist", &[("test.py", "print('hello')")]);
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .env("THE_WAY_GITHUB_API_URL", &api.url)
+        .args(["import", "--gist-url", &gist_url])
+        .assert()
+        .success();
+    let requests = api.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].0, "GET");
+    assert_eq!(
+        requests[0].1,
+        gist_url.replace("https://gist.github.com", "/gists")
+    );
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    let output = cmd
+        .env("THE_WAY_CONFIG", &config_file)
+        .arg("export")
+        .output()?;
+    let snippet: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(snippet["date"], "2020-01-02T03:04:05Z");
+    assert_eq!(snippet["updated"], "2021-06-07T08:09:10Z");
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn gist_truncated_files() -> color_eyre::Result<()> {
+    let mut gist: Gist = serde_json::from_str(
+        r#"{
+        "html_url": "https://gist.github.com/e5deab8d78ce838f22f160c9b14daf17",
+        "id": "e5deab8d78ce838f22f160c9b14daf17",
+        "created_at": "2020-01-02T03:04:05Z",
+        "updated_at": "2021-06-07T08:09:10Z",
+        "description": "test gist",
+        "files": {
+            "small.py": {"content": "print('hello')", "language": "Python"},
+            "large.py": {
+                "content": "print('cut",
+                "language": "Python",
+                "truncated": true,
+                "raw_url": "https://gist.githubusercontent.com/raw/large.py"
+            }
+        }
+    }"#,
+    )?;
+    let fetched = std::cell::RefCell::new(Vec::new());
+    gist.fetch_truncated(|raw_url| {
+        fetched.borrow_mut().push(raw_url.to_owned());
+        Ok("print('cut short')".to_owned())
+    })?;
+    assert_eq!(
+        fetched.into_inner(),
+        ["https://gist.githubusercontent.com/raw/large.py"]
+    );
+    assert_eq!(gist.files["large.py"].content, "print('cut short')");
+    assert!(!gist.files["large.py"].truncated);
+    assert_eq!(gist.files["small.py"].content, "print('hello')");
+    Ok(())
+}
+
+#[test]
+fn supported_languages() -> color_eyre::Result<()> {
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("languages")
+        .arg("--supported")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Rust\n").and(predicate::str::contains("Python\n")));
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn filter_multiple_patterns() -> color_eyre::Result<()> {
+    let contents = [
+        r#"{"description":"desc one","language":"rust","code":"TODO first"}"#,
+        r#"{"description":"desc two","language":"rust","code":"FIXME second"}"#,
+        r#"{"description":"desc three","language":"rust","code":"TODO FIXME third"}"#,
+        r#"{"description":"desc four","language":"rust","code":"fourth"}"#,
+    ]
+    .join("\n");
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+
+    // any
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["list", "-p", "TODO", "-p", "FIXME"])
+        .assert()
+        .stdout(
+            predicate::str::contains("desc one")
+                .and(predicate::str::contains("desc two"))
+                .and(predicate::str::contains("desc three"))
+                .and(predicate::str::contains("desc four").not()),
+        );
+
+    // all
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["list", "-p", "TODO", "-p", "FIXME", "--all-patterns"])
+        .assert()
+        .stdout(
+            predicate::str::contains("desc three")
+                .and(predicate::str::contains("desc one").not())
+                .and(predicate::str::contains("desc two").not())
+                .and(predicate::str::contains("desc four").not()),
+        );
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn sync_url() -> color_eyre::Result<()> {
+    let (temp_dir, config_file) = setup_the_way()?;
+    // No Gist synced yet
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["sync", "--url"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No Gist URL stored"));
+
+    // The URL is stored by syncing, and printed without going online
+    let api = StubGistApi::start()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(r#"{"description":"first","language":"rust","tags":[],"code":"code"}"#)
+        .assert()
+        .success();
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .env("THE_WAY_GITHUB_API_URL", &api.url)
+        .env("THE_WAY_GITHUB_TOKEN", "stub-token")
+        .args(["sync", "local"])
+        .assert()
+        .success();
+    let num_requests = api.requests().len();
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .env("THE_WAY_GITHUB_API_URL", &api.url)
+        .env_remove("THE_WAY_GITHUB_TOKEN")
+        .args(["sync", "--url"])
+        .assert()
+        .success()
+        .stdout(format!("https://gist.github.com/{:032x}\n", 1));
+    assert_eq!(api.requests().len(), num_requests);
+
+    // A sync source and --url can't be combined
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["sync", "--url", "local"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Choose one of a sync source, --adopt or --url",
+        ));
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn gist_open_print() -> color_eyre::Result<()> {
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["gist", "open", "--print"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No Gist URL stored"));
+
+    // The URL is made from the Gist ID if it wasn't stored
+    let mut config_contents = fs::read_to_string(&config_file)?;
+    config_contents.push_str("\ngist_id = 'abc123'\n");
+    fs::write(&config_file, config_contents)?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .env_remove("THE_WAY_GITHUB_TOKEN")
+        .args(["gist", "open", "--print"])
+        .assert()
+        .success()
+        .stdout("https://gist.github.com/abc123\n");
+    // Without a terminal the URL is printed too
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["gist", "open"])
+        .assert()
+        .success()
+        .stdout("https://gist.github.com/abc123\n");
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn clear_refuses_foreign_db_dir() -> color_eyre::Result<()> {
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("list")
+        .assert()
+        .success();
+    let important_file = temp_dir.path().join("db").join("important.txt");
+    fs::write(&important_file, "don't delete me")?;
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["clear", "--force"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "doesn't look like a the-way database",
+        ));
+    assert!(important_file.exists());
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["clear", "--force", "--force-unsafe"])
+        .assert()
+        .success();
+    assert!(!important_file.exists());
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn copy_confirmation_plain() -> color_eyre::Result<()> {
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut config_contents = fs::read_to_string(&config_file)?;
+    config_contents.push_str("\ncopy_cmd = 'true'\n");
+    fs::write(&config_file, config_contents)?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(r#"{"description":"test","language":"rust","code":"code"}"#)
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["--plain", "cp", "1"])
+        .assert()
+        .success()
+        .stderr("Snippet #1 copied to clipboard\n");
+
+    // Escapes only when forced
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["--colorize", "cp", "1"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("\x1b["));
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn import_export_gzip() -> color_eyre::Result<()> {
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(r#"{"description":"gzipped","language":"rust","code":"fn main() {}"}"#)
+        .assert()
+        .success();
+    let export_file = temp_dir.path().join("snippets.json.gz");
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("export")
+        .arg(&export_file)
+        .assert()
+        .success();
+    assert!(fs::read(&export_file)?.starts_with(&[0x1f, 0x8b]));
+
+    let (temp_dir_2, config_file_2) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file_2)
+        .arg("import")
+        .arg(&export_file)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Imported 1 snippets"));
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file_2)
+        .arg("view")
+        .arg("1")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("gzipped").and(predicate::str::contains("fn main() {}")));
+    drop(config_file);
+    drop(config_file_2);
+    temp_dir.close()?;
+    temp_dir_2.close()?;
+    Ok(())
+}
+
+#[test]
+fn search_exact_config() -> color_eyre::Result<()> {
+    let contents = r#"{"description":"test description","language":"rust","code":"code"}"#;
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut config_contents = fs::read_to_string(&config_file)?;
+    config_contents.push_str("\nsearch_exact = true");
+    fs::write(&config_file, config_contents)?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+
+    let mut p = spawn_bash()?;
+    p.send_line(format!(
+        "export THE_WAY_CONFIG={}",
+        config_file.to_string_lossy()
+    ))?;
+    let executable = env!("CARGO_BIN_EXE_the-way");
+    let output_file = temp_dir.path().join("output.txt");
+    p.expect_prompt()?;
+    // Opens the search in exact mode
+    p.send_line(format!(
+        "{executable} -vv cp --stdout > {}",
+        output_file.to_string_lossy()
+    ))?;
+    p.expect("Searching 1 snippets with exact matching")?;
+    p.expect("test description")?;
+    p.send("\x03")?;
+    p.expect_prompt()?;
+    assert_eq!(fs::read_to_string(&output_file)?, "");
+    // --fuzzy overrides the configuration
+    p.send_line(format!(
+        "{executable} -vv cp --stdout --fuzzy > {}",
+        output_file.to_string_lossy()
+    ))?;
+    p.expect("Searching 1 snippets with fuzzy matching")?;
+    p.expect("test description")?;
+    p.send("tsdc")?;
+    p.send("\r")?;
+    p.expect_prompt()?;
+    assert_eq!(fs::read_to_string(&output_file)?, "code\n");
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn view_by_description() -> color_eyre::Result<()> {
+    let contents = [
+        r#"{"description":"list files","language":"sh","code":"ls -la"}"#,
+        r#"{"description":"disk usage","language":"sh","code":"du -sh"}"#,
+    ]
+    .join("\n");
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["view", "disk usage"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("du -sh").and(predicate::str::contains("ls -la").not()));
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["cp", "--stdout", "list files"])
+        .assert()
+        .success()
+        .stdout("ls -la\n");
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["view", "list"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("described as \"list\""));
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn view_large_snippet() -> color_eyre::Result<()> {
+    let code = (0..20_000)
+        .map(|i| format!("let x{i} = {i};"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let snippet = format!(r#"{{"description":"large","language":"rust","code":"{code}"}}"#)
+        .replace('\n', "\\n");
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(snippet)
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    let output = cmd
+        .env("THE_WAY_CONFIG", &config_file)
+        .args(["view", "1"])
+        .output()?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout)?;
+    assert!(stdout.contains(&code));
+    assert!(!stdout.contains('\x1b'));
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn list_porcelain() -> color_eyre::Result<()> {
+    let contents = [
+        r#"{"description":"first snippet","language":"rust","tags":["tag1","tag2"],"code":"code"}"#,
+        r#"{"description":"second\tsnippet","language":"python","code":"code"}"#,
+    ]
+    .join("\n");
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["--colorize", "list", "--porcelain"])
+        .assert()
+        .success()
+        .stdout("1\trust\ttag1,tag2\tfirst snippet\n2\tpython\t\tsecond snippet\n");
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn config_default_standard_location() -> color_eyre::Result<()> {
+    let temp_dir = tempdir()?;
+    let config_home = temp_dir.path().join("config");
+    let data_home = temp_dir.path().join("data");
+    let default_config_file = config_home.join("the-way").join("default-config.toml");
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env_remove("THE_WAY_CONFIG")
+        .env("XDG_CONFIG_HOME", &config_home)
+        .env("XDG_DATA_HOME", &data_home)
+        .args(["config", "default"])
+        .assert()
+        .success()
+        .stdout("");
+    let config_contents = fs::read_to_string(&default_config_file)?;
+    assert!(config_contents.contains(&*data_home.to_string_lossy()));
+    assert!(config_contents.contains("e.g. 'wl-copy' on Wayland"));
+
+    // Loading writes the same configuration if it's missing
+    fs::remove_file(&default_config_file)?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env_remove("THE_WAY_CONFIG")
+        .env("XDG_CONFIG_HOME", &config_home)
+        .env("XDG_DATA_HOME", &data_home)
+        .arg("list")
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(&default_config_file)?, config_contents);
+
+    // Doesn't overwrite an existing configuration without --force
+    fs::write(&default_config_file, "theme = 'changed'")?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env_remove("THE_WAY_CONFIG")
+        .env("XDG_CONFIG_HOME", &config_home)
+        .env("XDG_DATA_HOME", &data_home)
+        .args(["config", "default"])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("theme = 'base16-ocean.dark'")
+                .and(predicate::str::contains("copy_cmd"))
+                .and(predicate::str::contains("e.g. 'wl-copy' on Wayland")),
+        );
+    assert_eq!(
+        fs::read_to_string(&default_config_file)?,
+        "theme = 'changed'"
+    );
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env_remove("THE_WAY_CONFIG")
+        .env("XDG_CONFIG_HOME", &config_home)
+        .env("XDG_DATA_HOME", &data_home)
+        .args(["config", "default", "--force"])
+        .assert()
+        .success();
+    assert!(fs::read_to_string(&default_config_file)?.contains("theme = 'base16-ocean.dark'"));
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn config_default_to_file() -> color_eyre::Result<()> {
+    let temp_dir = tempdir()?;
+    let config_file = temp_dir.path().join("new-config.toml");
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env_remove("THE_WAY_CONFIG")
+        .args(["config", "default"])
+        .arg(&config_file)
+        .assert()
+        .success()
+        .stdout("");
+    let config_contents = fs::read_to_string(&config_file)?;
+    assert!(config_contents.starts_with(
+        "theme = 'base16-ocean.dark'\ndb_dir = 'the_way_db'\nthemes_dir = 'the_way_themes'\n"
+    ));
+    assert!(config_contents.contains("copy_cmd"));
+
+    // Only overwrites an existing file with --force
+    fs::write(&config_file, "theme = 'changed'")?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env_remove("THE_WAY_CONFIG")
+        .args(["config", "default"])
+        .arg(&config_file)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "already exists, use --force to overwrite it",
+        ));
+    assert_eq!(fs::read_to_string(&config_file)?, "theme = 'changed'");
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env_remove("THE_WAY_CONFIG")
+        .args(["config", "default", "--force"])
+        .arg(&config_file)
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(&config_file)?, config_contents);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn filter_index_range() -> color_eyre::Result<()> {
+    let contents = (1..=4)
+        .map(|i| {
+            format!(r#"{{"description":"snippet number {i}","language":"rust","code":"code"}}"#)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["list", "--porcelain", "--after", "2"])
+        .assert()
+        .success()
+        .stdout("3\trust\t\tsnippet number 3\n4\trust\t\tsnippet number 4\n");
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["list", "--porcelain", "--after", "1", "--before", "4"])
+        .assert()
+        .success()
+        .stdout("2\trust\t\tsnippet number 2\n3\trust\t\tsnippet number 3\n");
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn export_index_range() -> color_eyre::Result<()> {
+    let contents = (1..=12)
+        .map(|i| {
+            format!(r#"{{"description":"snippet number {i}","language":"rust","code":"code"}}"#)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+
+    let export_file = temp_dir.path().join("batch.json");
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["export", "--after", "8", "--before", "12"])
+        .arg(&export_file)
+        .assert()
+        .success();
+    let exported = serde_json::Deserializer::from_slice(&fs::read(&export_file)?)
+        .into_iter::<serde_json::Value>()
+        .map(|snippet| {
+            Ok(snippet?["description"]
+                .as_str()
+                .unwrap_or_default()
+                .to_owned())
+        })
+        .collect::<color_eyre::Result<Vec<_>>>()?;
+    assert_eq!(
+        exported,
+        ["snippet number 9", "snippet number 10", "snippet number 11"]
+    );
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn import_detect_language() -> color_eyre::Result<()> {
+    let contents = [
+        r##"{"description":"python script","language":"text","code":"#!/usr/bin/env python\nprint('hi')\n"}"##,
+        r#"{"description":"plain notes","language":"text","code":"just some notes\n"}"#,
+    ]
+    .join("\n");
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["import", "--detect-language"])
+        .write_stdin(contents.clone())
+        .assert()
+        .success();
+    // Without the flag languages are kept as is
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["list", "--porcelain"])
+        .assert()
+        .success()
+        .stdout(
+            "1\tpython\t\tpython script\n\
+            2\ttext\t\tplain notes\n\
+            3\ttext\t\tpython script\n\
+            4\ttext\t\tplain notes\n",
+        );
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn copy_cmd_file_placeholder() -> color_eyre::Result<()> {
+    let (temp_dir, config_file) = setup_the_way()?;
+    let copied_file = temp_dir.path().join("copied.txt");
+    let mut config_contents = fs::read_to_string(&config_file)?;
+    config_contents.push_str(&format!(
+        "\ncopy_cmd = 'cp {{file}} {}'\n",
+        copied_file.to_string_lossy()
+    ));
+    fs::write(&config_file, config_contents)?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(r#"{"description":"test","language":"rust","code":"fn main() {}\n"}"#)
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["cp", "1"])
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(&copied_file)?, "fn main() {}\n");
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn normalize_tags() -> color_eyre::Result<()> {
+    let contents = [
+        r#"{"description":"first","language":"rust","tags":["Rust","cli"],"code":"code"}"#,
+        r#"{"description":"second","language":"rust","tags":["rust"],"code":"code"}"#,
+    ]
+    .join("\n");
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents.clone())
+        .assert()
+        .success();
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("tags")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Rust (1)").and(predicate::str::contains("rust (1)")));
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["tags", "normalize"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Normalized tags of 1 snippets"));
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("tags")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("rust (2)").and(predicate::str::contains("Rust").not()));
+
+    // New snippets are lowercased with lowercase_tags set
+    let mut config_contents = fs::read_to_string(&config_file)?;
+    config_contents.push_str("\nlowercase_tags = true\n");
+    fs::write(&config_file, config_contents)?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("tags")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("rust (4)").and(predicate::str::contains("Rust").not()));
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["view", "3"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(":cli:rust:"));
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+/// Tests that tag keys stored in their original case by older versions are lowercased when the database is opened
+fn older_tag_keys() -> color_eyre::Result<()> {
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(r#"{"description":"first","language":"rust","tags":["Rust"],"code":"code"}"#)
+        .assert()
+        .success();
+    let db_dir = temp_dir.path().join("db");
+    {
+        let db = sled::open(&db_dir)?;
+        db.remove("lowercase_tag_keys")?;
+        let tags = db.open_tree("tag_to_snippet")?;
+        tags.clear()?;
+        tags.insert("Rust", "1")?;
+        db.flush()?;
+    }
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["list", "--porcelain", "-t", "Rust"])
+        .assert()
+        .success()
+        .stdout("1\trust\tRust\tfirst\n");
+    let db = sled::open(&db_dir)?;
+    let tags = db.open_tree("tag_to_snippet")?;
+    assert_eq!(tags.get("rust")?.as_deref(), Some(b"1".as_slice()));
+    assert!(!tags.contains_key("Rust")?);
+    drop(db);
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn language_set_color() -> color_eyre::Result<()> {
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(r#"{"description":"first","language":"rust","code":"code"}"#)
+        .assert()
+        .success();
+    // GitHub's color for Rust
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["view", "1", "--colorize"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\x1b[38;2;222;165;132m\u{25a0}"));
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["languages", "set-color", "Rust", "#ff0000"])
+        .assert()
+        .success();
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["view", "1", "--colorize"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\x1b[38;2;255;0;0m\u{25a0}"));
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["languages", "set-color", "rust", "red"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid color \\\"red\\\""));
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn rename_tag() -> color_eyre::Result<()> {
+    let contents = [
+        r#"{"description":"first","language":"python","tags":["py"],"code":"code"}"#,
+        r#"{"description":"second","language":"python","tags":["py","python"],"code":"code"}"#,
+        r#"{"description":"third","language":"rust","tags":["rust"],"code":"code"}"#,
+    ]
+    .join("\n");
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["tags", "rename", "PY", "python"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Renamed tag \"PY\" to \"python\" on 2 snippets",
+        ));
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["list", "--porcelain", "-t", "py"])
+        .assert()
+        .success()
+        .stdout("");
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["list", "--porcelain", "-t", "python"])
+        .assert()
+        .success()
+        .stdout("1\tpython\tpython\tfirst\n2\tpython\tpython\tsecond\n");
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("tags")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("python (2)").and(predicate::str::contains("py (").not()));
+    // The old tag is gone
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["tags", "rename", "py", "python"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "You haven't tagged anything as \"py\" yet.",
+        ));
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn tags_ignore_case() -> color_eyre::Result<()> {
+    let contents = [
+        r#"{"description":"first","language":"rust","tags":["Rust"],"code":"code"}"#,
+        r#"{"description":"second","language":"python","tags":["python"],"code":"code"}"#,
+    ]
+    .join("\n");
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+    for (args, expected) in [
+        (vec!["-t", "rust"], "1\trust\tRust\tfirst\n"),
+        (vec!["-t", "RUST"], "1\trust\tRust\tfirst\n"),
+        // Together with a language filter, tags are matched on the snippets themselves
+        (vec!["-t", "Rust", "-l", "rust"], "1\trust\tRust\tfirst\n"),
+    ] {
+        let mut cmd = Command::cargo_bin("the-way")?;
+        cmd.env("THE_WAY_CONFIG", &config_file)
+            .args(["list", "--porcelain"])
+            .args(args)
+            .assert()
+            .success()
+            .stdout(expected);
+    }
+    // Removing a tag ignores case too
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["edit", "1", "--remove-tag", "RUST", "--add-tag", "Systems"])
+        .assert()
+        .success();
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["list", "--porcelain", "-t", "systems"])
+        .assert()
+        .success()
+        .stdout("1\trust\tSystems\tfirst\n");
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn change_theme_interactive() -> color_eyre::Result<()> {
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut p = spawn_bash()?;
+    p.send_line(format!(
+        "export THE_WAY_CONFIG={}",
+        config_file.to_string_lossy()
+    ))?;
+    let executable = env!("CARGO_BIN_EXE_the-way");
+    p.expect_prompt()?;
+    // An explicit theme doesn't open the picker
+    p.send_line(format!("{executable} themes set InspiredGitHub"))?;
+    p.expect("Theme changed to InspiredGitHub")?;
+    p.expect_prompt()?;
+    // Type to narrow down themes
+    p.send_line(format!("{executable} themes set"))?;
+    p.expect("Choose a syntax highlighting theme")?;
+    p.send("mocha")?;
+    p.send("\r")?;
+    p.expect("Theme changed to base16-mocha.dark")?;
+    p.expect_prompt()?;
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn invalid_pattern() -> color_eyre::Result<()> {
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["list", "-p", "foo(bar"])
+        .assert()
+        .failure()
+        .stderr(
+            predicate::str::contains("\"foo(bar\" is not a valid regular expression")
+                .and(predicate::str::contains("Escape them with a backslash")),
+        );
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn filter_fixed_strings() -> color_eyre::Result<()> {
+    let contents = [
+        r#"{"description":"call","language":"rust","code":"foo(bar)"}"#,
+        r#"{"description":"sum","language":"rust","code":"a+b"}"#,
+        r#"{"description":"repeat","language":"rust","code":"aab"}"#,
+    ]
+    .join("\n");
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["list", "--porcelain", "-F", "-p", "foo(bar"])
+        .assert()
+        .success()
+        .stdout("1\trust\t\tcall\n");
+    // a+b is a regex matching "aab" but not "a+b"
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["list", "--porcelain", "-p", "a+b"])
+        .assert()
+        .success()
+        .stdout("3\trust\t\trepeat\n");
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["list", "--porcelain", "--fixed-strings", "-p", "a+b"])
+        .assert()
+        .success()
+        .stdout("2\trust\t\tsum\n");
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn filter_ignore_case() -> color_eyre::Result<()> {
+    let contents = [
+        r#"{"description":"say hello","language":"rust","code":"a+b"}"#,
+        r#"{"description":"goodbye","language":"rust","code":"A+B"}"#,
+    ]
+    .join("\n");
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["list", "--porcelain", "-p", "HELLO"])
+        .assert()
+        .success()
+        .stdout("");
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["list", "--porcelain", "-i", "-p", "HELLO"])
+        .assert()
+        .success()
+        .stdout("1\trust\t\tsay hello\n");
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["list", "--porcelain", "--ignore-case", "-F", "-p", "a+b"])
+        .assert()
+        .success()
+        .stdout("1\trust\t\tsay hello\n2\trust\t\tgoodbye\n");
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn copy_with_header() -> color_eyre::Result<()> {
+    let contents = [
+        r#"{"description":"list files","language":"bash","code":"ls -la"}"#,
+        r#"{"description":"hello world","language":"c","code":"printf(\"hello\");"}"#,
+    ]
+    .join("\n");
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["cp", "--stdout", "--with-header", "1"])
+        .assert()
+        .success()
+        .stdout("# #1. list files\nls -la\n");
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["cp", "--stdout", "--with-header", "2"])
+        .assert()
+        .success()
+        .stdout("// #2. hello world\nprintf(\"hello\");\n");
+    // Headerless by default
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["cp", "--stdout", "2"])
+        .assert()
+        .success()
+        .stdout("printf(\"hello\");\n");
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn adopt_plain_gist() -> color_eyre::Result<()> {
+    let api = StubGistApi::start()?;
+    let gist_url = api.add_gist(
+        "my notes",
+        &[
+            ("notes.txt", "some notes"),
+            ("snippet_2.py", "print('mine')"),
+        ],
+    );
+    let gist_id = gist_url.rsplit('/').next().unwrap();
+    let contents = r#"{"description":"first","language":"rust","tags":["tag1"],"code":"fn main() {}"}
+{"description":"second","language":"python","tags":[],"code":"print('hello')"}"#;
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+
+    // Refuses to replace snippet_2.py
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .env("THE_WAY_GITHUB_API_URL", &api.url)
+        .env("THE_WAY_GITHUB_TOKEN", "stub-token")
+        .args(["sync", "--adopt", gist_id])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("snippet_2.py"));
+    assert_eq!(
+        api.gist(&gist_url).unwrap()["files"]["snippet_2.py"]["content"],
+        "print('mine')"
+    );
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .env("THE_WAY_GITHUB_API_URL", &api.url)
+        .env("THE_WAY_GITHUB_TOKEN", "stub-token")
+        .args(["sync", "--adopt", gist_id, "--overwrite"])
+        .assert()
+        .success();
+    let gist = api.gist(&gist_url).unwrap();
+    let mut file_names = gist["files"]
+        .as_object()
+        .unwrap()
+        .keys()
+        .collect::<Vec<_>>();
+    file_names.sort();
+    // notes.txt is left alone
+    assert_eq!(
+        file_names,
+        ["index.md", "notes.txt", "snippet_1.rs", "snippet_2.py"]
+    );
+    assert_eq!(gist["files"]["snippet_2.py"]["content"], "print('hello')");
+    assert!(gist["files"]["index.md"]["content"]
+        .as_str()
+        .unwrap()
+        .contains(&format!("* [first]({gist_url}#file-snippet_1-rs) :tag1:")));
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn view_related() -> color_eyre::Result<()> {
+    let contents = [
+        r#"{"description":"list files","language":"sh","code":"ls -la","tags":["files"]}"#,
+        r#"{"description":"disk usage","language":"sh","code":"du -sh","tags":["files","disk"]}"#,
+        r#"{"description":"greet","language":"sh","code":"echo hi","tags":["misc"]}"#,
+    ]
+    .join("\n");
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["view", "1", "--related"])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("#2. disk usage")
+                .and(predicate::str::contains("du -sh").not())
+                .and(predicate::str::contains("greet").not()),
+        );
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["view", "2", "--related", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("#1. list files"));
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn max_code_bytes() -> color_eyre::Result<()> {
+    let contents = [
+        r#"{"description":"small","language":"sh","code":"ls"}"#,
+        r#"{"description":"large","language":"sh","code":"echo 'this is too long'"}"#,
+    ]
+    .join("\n");
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut config_contents = fs::read_to_string(&config_file)?;
+    config_contents.push_str("\nmax_code_bytes = 10");
+    fs::write(&config_file, config_contents)?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents.clone())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("\"large\" has 23 bytes of code"));
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["list", "--porcelain"])
+        .assert()
+        .success()
+        .stdout("");
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["import", "--force"])
+        .write_stdin(contents)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Imported 2 snippets"));
+
+    // Editing a snippet past the limit warns
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .env("EDITOR", "sed -i s/ls/ls-with-a-long-name/")
+        .env_remove("VISUAL")
+        .args(["edit", "--code-only", "1"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "SnippetTooLarge: \"small\" has 19 bytes of code, more than max_code_bytes (10). \
+            Consider storing large files in a Gist instead.",
+        ));
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn gist_index_line_template() -> color_eyre::Result<()> {
+    let api = StubGistApi::start()?;
+    let contents = r#"{"description":"print [things] (nicely)","language":"python","tags":["tag1","tag2"],"code":"print('hello')"}
+{"description":"main","language":"rust","tags":[],"code":"fn main() {}"}"#;
+    let synced_index = |template: Option<&str>| -> color_eyre::Result<(String, String)> {
+        let (temp_dir, config_file) = setup_the_way()?;
+        if let Some(template) = template {
+            let mut config = fs::read_to_string(&config_file)?;
+            config.push_str(&format!("\ngist_index_line = {template:?}\n"));
+            fs::write(&config_file, config)?;
+        }
+        let mut cmd = Command::cargo_bin("the-way")?;
+        cmd.env("THE_WAY_CONFIG", &config_file)
+            .arg("import")
+            .write_stdin(contents)
+            .assert()
+            .success();
+        let mut cmd = Command::cargo_bin("the-way")?;
+        cmd.env("THE_WAY_CONFIG", &config_file)
+            .env("THE_WAY_GITHUB_API_URL", &api.url)
+            .env("THE_WAY_GITHUB_TOKEN", "stub-token")
+            .args(["sync", "local"])
+            .assert()
+            .success();
+        let config: toml::Value = toml::from_str(&fs::read_to_string(&config_file)?)?;
+        let gist_url = config["gist_url"].as_str().unwrap().to_owned();
+        let index = api.gist(&gist_url).unwrap()["files"]["index.md"]["content"]
+            .as_str()
+            .unwrap()
+            .to_owned();
+        temp_dir.close()?;
+        Ok((gist_url, index))
+    };
+    let imported = |gist_url: &str, template: &str| -> color_eyre::Result<_> {
+        let (temp_dir, config_file) = setup_the_way()?;
+        let mut config = fs::read_to_string(&config_file)?;
+        config.push_str(&format!("\ngist_index_line = {template:?}\n"));
+        fs::write(&config_file, config)?;
+        let mut cmd = Command::cargo_bin("the-way")?;
+        let output = cmd
+            .env("THE_WAY_CONFIG", &config_file)
+            .env("THE_WAY_GITHUB_API_URL", &api.url)
+            .args(["import", "-w", gist_url])
+            .output()?;
+        let mut cmd = Command::cargo_bin("the-way")?;
+        let listed = cmd
+            .env("THE_WAY_CONFIG", &config_file)
+            .args(["list", "--porcelain"])
+            .output()?;
+        temp_dir.close()?;
+        Ok((output, String::from_utf8(listed.stdout)?))
+    };
+
+    // Default format is unchanged
+    let default = "* [{description}]({url}#file-{file}) {tags}";
+    let (gist_url, index) = synced_index(None)?;
+    assert!(index.contains(&format!(
+        "* [print [things] (nicely)]({gist_url}#file-snippet_1-py) :tag1:tag2:\n"
+    )));
+    assert!(index.contains(&format!("* [main]({gist_url}#file-snippet_2-rs)\n")));
+
+    let custom = "- {tags} **{description}** <{url}#file-{file}>";
+    let (custom_gist_url, custom_index) = synced_index(Some(custom))?;
+    assert!(custom_index.contains(&format!(
+        "- :tag1:tag2: **print [things] (nicely)** <{custom_gist_url}#file-snippet_1-py>\n"
+    )));
+    for (gist_url, template) in [(gist_url.as_str(), default), (&custom_gist_url, custom)] {
+        let (output, listed) = imported(gist_url, template)?;
+        assert!(output.status.success());
+        assert_eq!(
+            listed,
+            "1\tpython\ttag1,tag2\tprint [things] (nicely)\n2\trust\t\tmain\n"
+        );
+    }
+
+    // Both the description and file are needed to read the index back
+    let (output, _) = imported(&gist_url, "* {description}")?;
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr)?.contains("is missing {description} or {file}"));
+    Ok(())
+}
+
+#[test]
+fn dedup_tags() -> color_eyre::Result<()> {
+    let contents = r#"{"description":"tagged twice","language":"rust","tags":["rust","rust","async"],"code":"code"}"#;
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["list", "--porcelain", "-t", "rust"])
+        .assert()
+        .success()
+        .stdout("1\trust\trust,async\ttagged twice\n");
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn import_json_layouts() -> color_eyre::Result<()> {
+    let pretty = r#"{
+    "description": "pretty",
+    "language": "rust",
+    "code": "fn main() {}"
+}"#;
+    let array = r#"[
+    {"description": "first in array", "language": "sh", "code": "ls"},
+    {"description": "second in array", "language": "sh", "code": "pwd"}
+]"#;
+    let stream = [
+        r#"{"description":"first in stream","language":"sh","code":"ls"}"#,
+        r#"{"description":"second in stream","language":"sh","code":"pwd"}"#,
+    ]
+    .join("\n");
+    for (contents, descriptions) in [
+        (pretty.to_string(), vec!["pretty"]),
+        (array.to_string(), vec!["first in array", "second in array"]),
+        (stream, vec!["first in stream", "second in stream"]),
+    ] {
+        let (temp_dir, config_file) = setup_the_way()?;
+        let mut cmd = Command::cargo_bin("the-way")?;
+        cmd.env("THE_WAY_CONFIG", &config_file)
+            .arg("import")
+            .write_stdin(contents)
+            .assert()
+            .success()
+            .stdout(predicate::str::contains(format!(
+                "Imported {} snippets",
+                descriptions.len()
+            )));
+
+        let mut cmd = Command::cargo_bin("the-way")?;
+        let output = cmd
+            .env("THE_WAY_CONFIG", &config_file)
+            .args(["list", "--porcelain"])
+            .output()?;
+        let listed = String::from_utf8(output.stdout)?
+            .lines()
+            .map(|line| line.rsplit('\t').next().unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(listed, descriptions);
+        drop(config_file);
+        temp_dir.close()?;
+    }
+    Ok(())
+}
+
+#[test]
+fn export_plain() -> color_eyre::Result<()> {
+    let contents = [
+        r#"{"description":"first","language":"python","code":"def f():\n    return 1\n"}"#,
+        r#"{"description":"second","language":"sh","code":"ls -la"}"#,
+    ]
+    .join("\n");
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["export", "--format", "plain"])
+        .assert()
+        .success()
+        .stdout("def f():\n    return 1\n---\nls -la\n");
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args([
+            "export",
+            "--format",
+            "plain",
+            "--delimiter",
+            "=====",
+            "--with-header",
+        ])
+        .assert()
+        .success()
+        .stdout("# #1. first\ndef f():\n    return 1\n=====\n# #2. second\nls -la\n");
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn export_markdown() -> color_eyre::Result<()> {
+    let contents = [
+        r#"{"description":"first","language":"python","tags":["tag1","tag2"],"code":"def f():\n    return 1\n"}"#,
+        r#"{"description":"second","language":"markdown","code":"```sh\nls -la\n```"}"#,
+    ]
+    .join("\n");
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["export", "--format", "markdown"])
+        .assert()
+        .success()
+        .stdout(
+            "### #1. first\n\n```python\ndef f():\n    return 1\n```\n\nTags: tag1, tag2\n\n\
+             ### #2. second\n\n````markdown\n```sh\nls -la\n```\n````\n",
+        );
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn export_html() -> color_eyre::Result<()> {
+    let contents = r#"{"description":"first <snippet>","language":"rust","tags":["tag1"],"code":"fn main() {}\n"}"#;
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["export", "--format", "html"])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::starts_with("<!DOCTYPE html>")
+                .and(predicate::str::contains(
+                    "<h3>#1. first &lt;snippet&gt;</h3>",
+                ))
+                .and(predicate::str::contains("<pre style=\""))
+                .and(predicate::str::contains("<span style=\""))
+                .and(predicate::str::contains("<p>Tags: tag1</p>"))
+                .and(predicate::str::ends_with("</html>\n")),
+        );
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn search_header_actions() -> color_eyre::Result<()> {
+    let contents = r#"{"description":"test description","language":"rust","code":"code"}"#;
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+
+    let mut p = spawn_bash()?;
+    p.send_line(format!(
+        "export THE_WAY_CONFIG={}",
+        config_file.to_string_lossy()
+    ))?;
+    let executable = env!("CARGO_BIN_EXE_the-way");
+    p.expect_prompt()?;
+    // Wide enough to show the whole header
+    p.send_line("stty cols 200")?;
+    p.expect_prompt()?;
+    p.send_line(format!("{executable} search"))?;
+    p.expect(
+        "Press Enter to copy, ctrl-y to copy, ctrl-o to print, \
+        shift-left to delete, shift-right to edit",
+    )?;
+    p.send("\x03")?;
+    p.expect_prompt()?;
+    // Read-only search leaves out the keys changing snippets
+    p.send_line(format!("{executable} search --read-only"))?;
+    let header = p.expect(Regex("Press [^\x1b]*"))?;
+    assert_eq!(
+        header.get(0),
+        Some(&b"Press Enter to copy, ctrl-y to copy, ctrl-o to print"[..])
+    );
+    p.send("\x03")?;
+    p.expect_prompt()?;
+    // Enter prints instead of copying with --stdout
+    p.send_line(format!("{executable} search --stdout > /dev/null"))?;
+    p.expect("Press Enter to print, ctrl-y to copy, ctrl-o to print")?;
+    p.send("\x03")?;
+    p.expect_prompt()?;
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn the_way_gist_skips_bad_filenames() -> color_eyre::Result<()> {
+    let api = StubGistApi::start()?;
+    let gist_url = api.add_gist(
+        "The Way Code Snippets",
+        &[
+            (
+                "index.md",
+                "# Is it not written...\n* [hello](https://gist.github.com/stub#file-snippet_1-py) :tag1:\n",
+            ),
+            ("snippet_1.py", "print('hello')"),
+            ("notes.txt", "some notes"),
+        ],
+    );
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .env("THE_WAY_GITHUB_API_URL", &api.url)
+        .args(["import", "-w", &gist_url, "--strict"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("notes.txt"));
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .env("THE_WAY_GITHUB_API_URL", &api.url)
+        .args(["import", "-w", &gist_url])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "skipped 1 (bad filenames: notes.txt)",
+        ));
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["list", "--porcelain"])
+        .assert()
+        .success()
+        .stdout("1\tpython\ttag1\thello\n");
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["view", "1", "--raw"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("print('hello')"));
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn the_way_gist_empty_index() -> color_eyre::Result<()> {
+    let api = StubGistApi::start()?;
+    let gist_url = api.add_gist(
+        "The Way Code Snippets",
+        &[
+            ("index.md", "# Is it not written...\n\n"),
+            ("snippet_1.py", "print('hello')"),
+        ],
+    );
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .env("THE_WAY_GITHUB_API_URL", &api.url)
+        .args(["import", "-w", &gist_url])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Gist index is empty — is this a the-way gist?",
+        ));
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn import_extra_tags() -> color_eyre::Result<()> {
+    let api = StubGistApi::start()?;
+    let gist_url = api.add_gist(
+        "test gist",
+        &[("test.py", "print('hello')"), ("test.sh", "echo hello")],
+    );
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .env("THE_WAY_GITHUB_API_URL", &api.url)
+        .args(["import", "--gist-url", &gist_url, "--tag", "myproj"])
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    let output = cmd
+        .env("THE_WAY_CONFIG", &config_file)
+        .args(["list", "--porcelain"])
+        .output()?;
+    let listed = String::from_utf8(output.stdout)?;
+    assert_eq!(listed.lines().count(), 2);
+    for line in listed.lines() {
+        assert_eq!(line.split('\t').nth(2), Some("gist,myproj"));
+    }
+    drop(config_file);
+    temp_dir.close()?;
+
+    let contents = r#"{"description":"tagged","language":"sh","tags":["shell"],"code":"ls"}"#;
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["import", "--tag", "myproj", "--tag", "shell"])
+        .write_stdin(contents)
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["list", "--porcelain"])
+        .assert()
+        .success()
+        .stdout("1\tsh\tshell,myproj\ttagged\n");
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn edit_batch_metadata() -> color_eyre::Result<()> {
+    let contents = [
+        r#"{"description":"first","language":"sh","tags":["old"],"code":"ls"}"#,
+        r#"{"description":"second","language":"sh","tags":["old","keep"],"code":"pwd"}"#,
+        r#"{"description":"third","language":"sh","tags":["old"],"code":"cd"}"#,
+    ]
+    .join("\n");
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args([
+            "edit",
+            "--add-tag",
+            "new",
+            "--remove-tag",
+            "old",
+            "1",
+            "second",
+        ])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("Snippet #1 changed")
+                .and(predicate::str::contains("Snippet #2 changed")),
+        );
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["edit", "--language", "Bash", "3"])
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["list", "--porcelain"])
+        .assert()
+        .success()
+        .stdout("1\tsh\tnew\tfirst\n2\tsh\tkeep,new\tsecond\n3\tbash\told\tthird\n");
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn import_dry_run() -> color_eyre::Result<()> {
+    let contents = [
+        r#"{"description":"first","language":"sh","code":"ls"}"#,
+        r#"{"description":"second","language":"python","code":"print(1)"}"#,
+    ]
+    .join("\n");
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["import", "--dry-run"])
+        .write_stdin(contents.clone())
+        .assert()
+        .success()
+        .stdout("Would import 2 snippets\nfirst | sh\nsecond | python\n");
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["list", "--porcelain"])
+        .assert()
+        .success()
+        .stdout("");
+
+    // Indices start from 1 as nothing was imported before
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["list", "--porcelain"])
+        .assert()
+        .success()
+        .stdout("1\tsh\t\tfirst\n2\tpython\t\tsecond\n");
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn truncate_long_description() -> color_eyre::Result<()> {
+    let description = "a very long description that won't fit in a narrow terminal";
+    let contents =
+        format!(r#"{{"description":"{description}","language":"sh","tags":["tag"],"code":"ls"}}"#);
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    let output = cmd
+        .env("THE_WAY_CONFIG", &config_file)
+        .env("COLUMNS", "40")
+        .arg("list")
+        .output()?;
+    let output = String::from_utf8(output.stdout)?;
+    let header = output.lines().find(|line| line.contains("#1.")).unwrap();
+    assert_eq!(header, "\u{25a0} #1. a very long descriptio… | sh :tag:");
+    assert_eq!(header.chars().count(), 40);
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .env("COLUMNS", "40")
+        .args(["list", "--no-truncate"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(description));
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+/// Adds a the-way Gist to the stub API and points the configuration at it:
+/// snippet 1 edited, snippet 2 deleted, snippet 4 added
+fn add_stub_the_way_gist(api: &StubGistApi, config_file: &Path) -> color_eyre::Result<String> {
+    let url = api.add_gist(
+        "The Way Code Snippets",
+        &[
+            ("snippet_1.rs", "code\nthe\nfirstx\n"),
+            ("snippet_3.rs", "code\nthe\nthird\n"),
+            ("snippet_4.rs", "code\nthe\nfourth\n"),
+        ],
+    );
+    let index = format!(
+        "# Is it not written...\n\
+        * [first]({url}#file-snippet_1-rs)\n\
+        * [third]({url}#file-snippet_3-rs)\n\
+        * [fourth]({url}#file-snippet_4-rs)\n"
+    );
+    api.add_file(&url, "index.md", &index);
+    let mut config: toml::Value = toml::from_str(&fs::read_to_string(config_file)?)?;
+    config
+        .as_table_mut()
+        .unwrap()
+        .insert("gist_id".into(), url.rsplit('/').next().unwrap().into());
+    fs::write(config_file, toml::to_string(&config)?)?;
+    Ok(url)
+}
+
+/// Runs `the-way sync` with `args` against the stub API, returning its output
+fn stub_sync(
+    api: &StubGistApi,
+    config_file: &Path,
+    args: &[&str],
+) -> color_eyre::Result<std::process::Output> {
+    let mut cmd = Command::cargo_bin("the-way")?;
+    Ok(cmd
+        .env("THE_WAY_CONFIG", config_file)
+        .env("THE_WAY_GITHUB_API_URL", &api.url)
+        .env("THE_WAY_GITHUB_TOKEN", "stub-token")
+        .arg("sync")
+        .args(args)
+        .output()?)
+}
+
+#[test]
+fn sync_plan() -> color_eyre::Result<()> {
+    let contents = [
+        r#"{"description":"first","language":"rust","code":"code\nthe\nfirst\n"}"#,
+        r#"{"description":"second","language":"rust","code":"code\nthe\nsecond\n"}"#,
+        r#"{"description":"third","language":"rust","code":"code\nthe\nthird\n"}"#,
+    ]
+    .join("\n");
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+    let api = StubGistApi::start()?;
+    let url = add_stub_the_way_gist(&api, &config_file)?;
+
+    // Local as source: upload snippets 1 and 2, delete snippet 4 from the Gist
+    let dry_run = stub_sync(&api, &config_file, &["--dry-run", "local"])?;
+    assert!(dry_run.status.success());
+    let dry_run = String::from_utf8(dry_run.stdout)?;
+    assert!(dry_run.contains(
+        "Will upload 2, delete 1 from the Gist, download 0, add 0 locally and delete 0 locally"
+    ));
+    // Gist as source: download snippet 1, delete snippet 2 and add snippet 4 locally
+    let output = stub_sync(&api, &config_file, &["--dry-run", "gist"])?;
+    assert!(output.status.success());
+    let output = String::from_utf8(output.stdout)?;
+    assert!(output.contains(
+        "Will upload 0, delete 0 from the Gist, download 1, add 1 locally and delete 1 locally"
+    ));
+    let gist_lines = [
+        "1 snippet(s) downloaded from Gist",
+        "1 snippet(s) added locally",
+        "1 snippet(s) deleted locally",
+        "1 snippet(s) up to date",
+    ];
+    for line in gist_lines {
+        assert!(output.contains(line));
+    }
+    // Dry runs don't change the Gist
+    assert!(api.requests().iter().all(|(method, _, _)| method == "GET"));
+
+    // Deleting local snippets is confirmed first, so nothing changes without a terminal
+    assert!(!stub_sync(&api, &config_file, &["gist"])?.status.success());
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["list", "--porcelain"])
+        .assert()
+        .success()
+        .stdout("1\trust\t\tfirst\n2\trust\t\tsecond\n3\trust\t\tthird\n");
+
+    // The sync does what the dry run showed
+    let output = stub_sync(&api, &config_file, &["--force", "gist"])?;
+    assert!(output.status.success());
+    let output = String::from_utf8(output.stdout)?;
+    for line in gist_lines {
+        assert!(output.contains(line));
+    }
+    // The Gist's index already lists snippets 1, 3 and 4, so the Gist is left alone
+    assert!(api.requests().iter().all(|(method, _, _)| method == "GET"));
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["list", "--porcelain"])
+        .assert()
+        .success()
+        .stdout("1\trust\t\tfirst\n3\trust\t\tthird\n4\trust\t\tfourth\n");
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["view", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("firstx"));
+    let output = stub_sync(&api, &config_file, &["--dry-run", "gist"])?;
+    assert!(String::from_utf8(output.stdout)?.contains("3 snippet(s) up to date"));
+
+    // Deleting snippets from the Gist is confirmed first, so nothing changes without a terminal
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["del", "--force", "4"])
+        .assert()
+        .success();
+    assert!(!stub_sync(&api, &config_file, &["local"])?.status.success());
+    let output = stub_sync(&api, &config_file, &["--force", "local"])?;
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout)?.contains("1 snippet(s) deleted from Gist"));
+    let gist = api.gist(&url).unwrap();
+    let files = gist["files"].as_object().unwrap();
+    assert_eq!(
+        files.keys().map(String::as_str).collect::<HashSet<_>>(),
+        HashSet::from(["index.md", "snippet_1.rs", "snippet_3.rs"])
+    );
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn library_export_import_round_trip() -> color_eyre::Result<()> {
+    let contents = r#"{"description":"first","language":"rust","tags":["a"],"code":"fn main() {}"}
+{"description":"second","language":"python","tags":["b", "c"],"code":"print('hi')"}"#;
+    let languages = get_languages(include_str!("../src/languages.yml"))?;
+
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut the_way = TheWay::new(TheWayConfig::load_from(&config_file)?, languages.clone())?;
+    assert_eq!(the_way.import_from_reader(contents.as_bytes())?, 2);
+    let mut exported = Vec::new();
+    the_way.export_to_writer(&Filters::default(), &mut exported)?;
+    drop(the_way);
+
+    let (other_temp_dir, other_config_file) = setup_the_way()?;
+    let mut other = TheWay::new(TheWayConfig::load_from(&other_config_file)?, languages)?;
+    assert_eq!(other.import_from_reader(io::Cursor::new(&exported))?, 2);
+    let mut round_tripped = Vec::new();
+    other.export_to_writer(&Filters::default(), &mut round_tripped)?;
+    drop(other);
+
+    let snippets = serde_json::Deserializer::from_slice(&round_tripped)
+        .into_iter::<Snippet>()
+        .collect::<Result<Vec<_>, _>>()?;
+    let descriptions = snippets
+        .iter()
+        .map(|snippet| snippet.description.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(descriptions, ["first", "second"]);
+    assert_eq!(exported, round_tripped);
+    temp_dir.close()?;
+    other_temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn copy_filtered() -> color_eyre::Result<()> {
+    let contents = [
+        r#"{"description":"one","language":"sh","tags":["boilerplate"],"code":"echo one"}"#,
+        r#"{"description":"two","language":"sh","tags":["other"],"code":"echo two"}"#,
+        r#"{"description":"three","language":"sh","tags":["boilerplate"],"code":"echo three\n"}"#,
+    ]
+    .join("\n");
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["cp", "--tags", "boilerplate", "--stdout"])
+        .assert()
+        .success()
+        .stdout("echo one\n\necho three\n");
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["cp", "--tags", "missing", "--stdout"])
+        .assert()
+        .failure();
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn snippet_checksum() -> color_eyre::Result<()> {
+    let contents = r#"{"description":"greet","language":"sh","code":"echo hello"}"#;
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["--strict-checksums", "view", "1"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("ChecksumMismatch").not());
+
+    // Change the stored code behind the-way's back
+    let db_dir = temp_dir.path().join("db");
+    {
+        let snippets = sled::open(&db_dir)?.open_tree("snippets")?;
+        let mut bytes = snippets.get("1")?.unwrap().to_vec();
+        let start = bytes
+            .windows(5)
+            .position(|window| window == b"hello")
+            .unwrap();
+        bytes[start] = b'j';
+        snippets.insert("1", bytes)?;
+        snippets.flush()?;
+    }
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["view", "1"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "ChecksumMismatch: Snippet #1 was corrupted or changed outside the-way",
+        ));
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["--strict-checksums", "view", "1"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("ChecksumMismatch"));
+
+    // Snippets stored by versions without checksums are read as before
+    let record = unversioned_record(1, "greet", "sh", ".sh", "echo jello")?;
+    store_unversioned(&db_dir, &[(1, record)])?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["--strict-checksums", "view", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("echo jello"));
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn private_snippets() -> color_eyre::Result<()> {
+    let contents = [
+        r#"{"description":"first","language":"rust","code":"code\nthe\nfirst\n"}"#,
+        r#"{"description":"second","language":"rust","code":"code\nthe\nsecond\n"}"#,
+        r#"{"description":"third","language":"rust","code":"code\nthe\nthird\n"}"#,
+    ]
+    .join("\n");
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+
+    // Store the snippets the way versions before private snippets did, then star 1
+    store_unversioned(
+        &temp_dir.path().join("db"),
+        &[
+            (
+                1,
+                unversioned_record(1, "first", "rust", ".rs", "code\nthe\nfirst\n")?,
+            ),
+            (
+                2,
+                unversioned_record(2, "second", "rust", ".rs", "code\nthe\nsecond\n")?,
+            ),
+            (
+                3,
+                unversioned_record(3, "third", "rust", ".rs", "code\nthe\nthird\n")?,
+            ),
+        ],
+    )?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["star", "1"])
+        .assert()
+        .success();
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["--strict-checksums", "list", "--porcelain", "--favorites"])
+        .assert()
+        .success()
+        .stdout("1\trust\t\tfirst\n");
+
+    for index in ["2", "third", "3"] {
+        let mut cmd = Command::cargo_bin("the-way")?;
+        cmd.env("THE_WAY_CONFIG", &config_file)
+            .args(["private", index])
+            .assert()
+            .success();
+    }
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["private", "3"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Snippet #3 is private"));
+
+    let api = StubGistApi::start()?;
+    let url = add_stub_the_way_gist(&api, &config_file)?;
+    // Local as source: snippet 2 isn't added and snippet 3 is taken out of the Gist
+    let output = stub_sync(&api, &config_file, &["--dry-run", "local"])?;
+    assert!(String::from_utf8(output.stdout)?.contains(
+        "Will upload 1, delete 2 from the Gist, download 0, add 0 locally and delete 0 locally"
+    ));
+    // Gist as source: snippet 2 isn't deleted and snippet 3 isn't replaced
+    let output = stub_sync(&api, &config_file, &["--dry-run", "gist"])?;
+    assert!(String::from_utf8(output.stdout)?.contains(
+        "Will upload 0, delete 0 from the Gist, download 1, add 1 locally and delete 0 locally"
+    ));
+    assert!(stub_sync(&api, &config_file, &["gist"])?.status.success());
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["list", "--porcelain"])
+        .assert()
+        .success()
+        .stdout("1\trust\t\tfirst\n2\trust\t\tsecond\n3\trust\t\tthird\n4\trust\t\tfourth\n");
+    assert!(stub_sync(&api, &config_file, &["--force", "local"])?
+        .status
+        .success());
+    let gist = api.gist(&url).unwrap();
+    let files = gist["files"].as_object().unwrap();
+    assert_eq!(
+        files.keys().map(String::as_str).collect::<HashSet<_>>(),
+        HashSet::from(["index.md", "snippet_1.rs", "snippet_4.rs"])
+    );
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+/// A snippet record as stored before records started with a layout version: the bincode-serialized snippet
+fn unversioned_record(
+    index: usize,
+    description: &str,
+    language: &str,
+    extension: &str,
+    code: &str,
+) -> color_eyre::Result<Vec<u8>> {
+    let now = Utc::now();
+    Ok(bincode::serialize(&(
+        index,
+        description,
+        language,
+        code,
+        extension,
+        Vec::<String>::new(),
+        now,
+        now,
+    ))?)
+}
+
+/// Replaces the stored snippets with unversioned records, like a database written before
+/// records started with a layout version
+fn store_unversioned(db_dir: &Path, records: &[(usize, Vec<u8>)]) -> color_eyre::Result<()> {
+    let db = sled::open(db_dir)?;
+    db.remove("snippet_layout")?;
+    let snippets = db.open_tree("snippets")?;
+    snippets.clear()?;
+    for (index, bytes) in records {
+        snippets.insert(index.to_string(), bytes.as_slice())?;
+    }
+    db.flush()?;
+    Ok(())
+}
+
+#[test]
+fn unversioned_snippets() -> color_eyre::Result<()> {
+    let contents = [
+        r#"{"description":"first","language":"rust","code":"code"}"#,
+        r#"{"description":"second","language":"rust","code":"code"}"#,
+        r#"{"description":"third","language":"rust","code":"code"}"#,
+    ]
+    .join("\n");
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+
+    // Store the snippets the way released versions did, with one that can't be read
+    let db_dir = temp_dir.path().join("db");
+    store_unversioned(
+        &db_dir,
+        &[
+            (
+                1,
+                unversioned_record(1, "first", "rust", ".rs", "first code")?,
+            ),
+            (2, b"garbage".to_vec()),
+            (
+                3,
+                unversioned_record(3, "third", "rust", ".rs", "third code")?,
+            ),
+        ],
+    )?;
+
+    // The unreadable snippet is set aside, the others are migrated
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["--strict-checksums", "list", "--porcelain", "-l", "rust"])
+        .assert()
+        .success()
+        .stdout("1\trust\t\tfirst\n3\trust\t\tthird\n")
+        .stderr(predicate::str::contains(
+            "Couldn't read snippet #2 stored by an older version",
+        ));
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["--strict-checksums", "cp", "1", "--stdout"])
+        .assert()
+        .success()
+        .stdout("first code\n");
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("export")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("third code"));
+
+    // Records are versioned from now on
+    let db = sled::open(&db_dir)?;
+    for item in &db.open_tree("snippets")? {
+        assert_eq!(item?.1.first(), Some(&1));
+    }
+    assert_eq!(
+        db.open_tree("unreadable_snippets")?.get("2")?.as_deref(),
+        Some(b"garbage".as_slice())
+    );
+    drop(db);
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn favorites() -> color_eyre::Result<()> {
+    let contents = [
+        r#"{"description":"first","language":"rust","code":"code"}"#,
+        r#"{"description":"second","language":"rust","code":"code"}"#,
+        r#"{"description":"third","language":"python","code":"code"}"#,
+    ]
+    .join("\n");
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+
+    // Store the snippets the way versions before favorites did
+    store_unversioned(
+        &temp_dir.path().join("db"),
+        &[
+            (1, unversioned_record(1, "first", "rust", ".rs", "code")?),
+            (
+                2,
+                unversioned_record(2, "second", "rust", ".rs", "old code")?,
+            ),
+            (3, unversioned_record(3, "third", "python", ".py", "code")?),
+        ],
+    )?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["--strict-checksums", "view", "2"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("old code"));
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["list", "--porcelain", "--favorites"])
+        .assert()
+        .success()
+        .stdout("");
+
+    for index in ["2", "third"] {
+        let mut cmd = Command::cargo_bin("the-way")?;
+        cmd.env("THE_WAY_CONFIG", &config_file)
+            .args(["star", index])
+            .assert()
+            .success();
+    }
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["--strict-checksums", "list", "--porcelain", "--favorites"])
+        .assert()
+        .success()
+        .stdout("2\trust\t\tsecond\n3\tpython\t\tthird\n");
+    // Favorites are listed first, in the chosen order
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["list", "--porcelain", "--sort", "language"])
+        .assert()
+        .success()
+        .stdout("3\tpython\t\tthird\n2\trust\t\tsecond\n1\trust\t\tfirst\n");
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["list", "--porcelain", "-l", "rust", "--favorites"])
+        .assert()
+        .success()
+        .stdout("2\trust\t\tsecond\n");
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["unstar", "3"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Snippet #3 unstarred"));
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["list", "--porcelain", "--favorites"])
+        .assert()
+        .success()
+        .stdout("2\trust\t\tsecond\n");
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn scaffold() -> color_eyre::Result<()> {
+    // Angle brackets and single braces aren't placeholders
+    let contents = r#"{"description":"arg parser","language":"python","code":"import argparse\n\nparser = argparse.ArgumentParser(prog='{{name}}')\nparser.add_argument('--{{flag=verbose}}')\nprint('{{name}}', '<b>{x}</b>', {})\n"}"#;
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+    let expected = "import argparse\n\nparser = argparse.ArgumentParser(prog='tool')\nparser.add_argument('--debug')\nprint('tool', '<b>{x}</b>', {})\n";
+
+    let out = temp_dir.path().join("tool.py");
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args([
+            "scaffold",
+            "arg parser",
+            "--set",
+            "name=tool",
+            "--set",
+            "flag=debug",
+            "--out",
+        ])
+        .arg(&out)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Snippet #1 scaffolded to"));
+    assert_eq!(fs::read_to_string(&out)?, expected);
+
+    // Won't overwrite without --force
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args([
+            "scaffold",
+            "1",
+            "--set",
+            "name=other",
+            "--set",
+            "flag=x",
+            "--out",
+        ])
+        .arg(&out)
+        .assert()
+        .failure();
+    assert_eq!(fs::read_to_string(&out)?, expected);
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["scaffold", "1", "--set", "name=tool", "--set", "flag=debug"])
+        .assert()
+        .success()
+        .stdout(expected);
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn view_dates() -> color_eyre::Result<()> {
+    let contents = r#"{"description":"greet","language":"sh","code":"echo hello","date":"2024-01-02T10:00:00Z","updated":"2024-03-04T10:00:00Z"}"#;
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["view", "1", "--dates"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "greet | sh ::\ncreated 2024-01-02, updated 2024-03-04\n\necho hello",
+        ));
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["view", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("created").not());
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn view_raw() -> color_eyre::Result<()> {
+    let code = "#!/bin/bash\necho <name=world>\n\tindented\n";
+    let contents =
+        serde_json::json!({"description":"greet","language":"sh","code":code}).to_string();
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["--colorize", "view", "1", "--raw"])
+        .assert()
+        .success()
+        .stdout(code);
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn copy_no_fill() -> color_eyre::Result<()> {
+    let contents =
+        r#"{"description":"greet","language":"sh","code":"echo <name> <greeting=hello>"}"#;
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+    // Filling would prompt, which fails without a terminal
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["cp", "1", "--stdout"])
+        .assert()
+        .failure();
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["cp", "1", "--stdout", "--no-fill"])
+        .assert()
+        .success()
+        .stdout("echo <name> <greeting=hello>\n")
+        .stderr("");
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["cp", "-l", "sh", "--stdout", "--no-fill"])
+        .assert()
+        .success()
+        .stdout("echo <name> <greeting=hello>\n");
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn view_parameters() -> color_eyre::Result<()> {
+    let contents = r#"{"description":"greet","language":"bash","code":"echo <name=world> done"}
+{"description":"tag","language":"python","code":"print('<b>')"}"#;
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+    // base16-ocean.dark's tag color
+    let parameter_style = "\x1b[38;2;53;60;71m";
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["--colorize", "view", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "{parameter_style}<{parameter_style}name=world{parameter_style}>\x1b[38;2;192;197;206m done"
+        )));
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["--colorize", "view", "2"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!("{parameter_style}<")).not());
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn new_from_flags() -> color_eyre::Result<()> {
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args([
+            "new",
+            "-d",
+            "print hello",
+            "-l",
+            "Rust",
+            "-t",
+            "a b",
+            "--code",
+            "println!(\"hello\");",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Snippet #1 added"));
+    let code_file = temp_dir.path().join("hello.py");
+    fs::write(&code_file, "print('hello')\n")?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["new", "-d", "python hello", "-l", "python", "-t", "c"])
+        .arg("--file")
+        .arg(&code_file)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Snippet #2 added"));
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["view", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "#1. print hello | rust :a:b:\n\nprintln!(\"hello\");",
+        ));
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["view", "2", "--raw"])
+        .assert()
+        .success()
+        .stdout("print('hello')\n");
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["new", "-d", "empty", "-l", "sh", "-t", "", "--code", " "])
+        .assert()
+        .failure();
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn cmd_from_flags() -> color_eyre::Result<()> {
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["cmd", "ls -la <dir=.>", "-d", "list", "-t", "shell fs"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Snippet #1 added"));
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["list", "--porcelain"])
+        .assert()
+        .success()
+        .stdout("1\tsh\tshell,fs\tlist\n");
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["view", "1", "--raw"])
+        .assert()
+        .success()
+        .stdout("ls -la <dir=.>");
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn import_skip_duplicates() -> color_eyre::Result<()> {
+    let contents = [
+        r#"{"description":"first","language":"sh","tags":["a","b"],"code":"ls"}"#,
+        r#"{"description":"second","language":"python","code":"print(1)"}"#,
+    ]
+    .join("\n");
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["import", "--skip-duplicates"])
+        .write_stdin(contents.clone())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Imported 2 snippets, skipped 0 duplicates",
+        ));
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["import", "--skip-duplicates"])
+        .write_stdin(contents.clone())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Imported 0 snippets, skipped 2 duplicates",
+        ));
+    // Tags in a different order and extra whitespace around the code still match
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["import", "--skip-duplicates"])
+        .write_stdin(r#"{"description":"first","language":"sh","tags":["b","a"],"code":"ls\n"}"#)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Imported 0 snippets, skipped 1 duplicates",
+        ));
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Imported 2 snippets"));
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["list", "--porcelain"])
+        .assert()
+        .success()
+        .stdout(predicate::function(|stdout: &str| {
+            stdout.lines().count() == 4
+        }));
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn edit_code_only() -> color_eyre::Result<()> {
+    let contents = r#"{"description":"list files","language":"sh","tags":["fs"],"code":"ls -l","date":"2024-01-02T10:00:00Z","updated":"2024-01-02T10:00:00Z"}"#;
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+    // The editor is run with the file to edit as its last argument
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .env("EDITOR", "sed -i s/-l/-la/")
+        .env_remove("VISUAL")
+        .args(["edit", "--code-only", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Snippet #1 changed"));
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["list", "--porcelain"])
+        .assert()
+        .success()
+        .stdout("1\tsh\tfs\tlist files\n");
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["view", "1", "--raw"])
+        .assert()
+        .success()
+        .stdout("ls -la");
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["view", "1", "--dates"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("created 2024-01-02, updated 2024-01-02").not());
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn view_multiple() -> color_eyre::Result<()> {
+    let contents = (1..=6)
+        .map(|i| format!(r#"{{"description":"snippet {i}","language":"sh","code":"echo {i}"}}"#))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["view", "--oneline", "5", "2", "snippet 4"])
+        .assert()
+        .success()
+        .stdout("#5 snippet 5 (sh)\n#2 snippet 2 (sh)\n#4 snippet 4 (sh)\n");
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["view", "2-4"])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("echo 2")
+                .and(predicate::str::contains("echo 3"))
+                .and(predicate::str::contains("echo 4"))
+                .and(predicate::str::contains("echo 1").not())
+                .and(predicate::str::contains("echo 5").not()),
+        );
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["del", "-f", "3"])
+        .assert()
+        .success();
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["view", "--oneline", "2-4"])
+        .assert()
+        .success()
+        .stdout("#2 snippet 2 (sh)\n#4 snippet 4 (sh)\n")
+        .stderr(predicate::str::contains("Skipping #3"));
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["cp", "--stdout", "1-3", "6"])
+        .assert()
+        .success()
+        .stdout("echo 1\n\necho 2\n\necho 6\n");
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["view", "3-3"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "You haven't written that snippet: 3",
+        ));
+    // Ranges stop at the last snippet
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["view", "--oneline", "5-99999999999"])
+        .assert()
+        .success()
+        .stdout("#5 snippet 5 (sh)\n#6 snippet 6 (sh)\n")
+        .stderr("");
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["view", "100-99999999999"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "You haven't written that snippet: 100",
+        ));
+    // Only digits on either side make a range
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["view", "+1-2"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "You haven't written a snippet described as \"+1-2\"",
+        ));
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn github_api_url() -> color_eyre::Result<()> {
+    let client = GistClient::new(None, None)?;
+    assert_eq!(client.gists_url(), "https://api.github.com/gists");
+    let client = GistClient::new(Some("https://github.mycorp.com/api/v3/"), Some("token"))?;
+    assert_eq!(client.gists_url(), "https://github.mycorp.com/api/v3/gists");
+    Ok(())
+}
+
+#[test]
+fn gist_rate_limit() -> color_eyre::Result<()> {
+    let now = Utc::now();
+    let reset = now.timestamp() + 90;
+    let response: ureq::Response = format!(
+        "HTTP/1.1 403 Forbidden\r\n\
+        X-RateLimit-Remaining: 0\r\n\
+        X-RateLimit-Reset: {reset}\r\n\
+        \r\n\
+        {{\"message\": \"API rate limit exceeded\"}}"
+    )
+    .parse()?;
+    assert_eq!(rate_limit_wait(&response, now), Some(90));
+    let retry_at = (now + chrono::Duration::seconds(90)).format("%Y-%m-%d %H:%M:%S UTC");
+    assert_eq!(
+        rate_limit_message(90, now),
+        format!("GitHub API rate limit exceeded, try again in 90 seconds (after {retry_at})")
+    );
+
+    // Retry-After takes precedence
+    let response: ureq::Response = format!(
+        "HTTP/1.1 429 Too Many Requests\r\n\
+        Retry-After: 30\r\n\
+        X-RateLimit-Remaining: 0\r\n\
+        X-RateLimit-Reset: {reset}\r\n\
+        \r\n"
+    )
+    .parse()?;
+    assert_eq!(rate_limit_wait(&response, now), Some(30));
+
+    // A 403 with requests left (e.g. a bad token) isn't a rate limit
+    let response: ureq::Response = format!(
+        "HTTP/1.1 403 Forbidden\r\n\
+        X-RateLimit-Remaining: 59\r\n\
+        X-RateLimit-Reset: {reset}\r\n\
+        \r\n"
+    )
+    .parse()?;
+    assert_eq!(rate_limit_wait(&response, now), None);
+    Ok(())
+}
+
+/// Token store keeping the token in memory, standing in for the OS keyring
+#[derive(Default)]
+struct MockTokenStore(std::cell::RefCell<Option<String>>);
+
+impl TokenStore for MockTokenStore {
+    fn get(&self) -> color_eyre::Result<Option<String>> {
+        Ok(self.0.borrow().clone())
+    }
+
+    fn set(&self, token: &str) -> color_eyre::Result<()> {
+        *self.0.borrow_mut() = Some(token.to_owned());
+        Ok(())
+    }
+}
+
+#[test]
+fn github_token_resolution() -> color_eyre::Result<()> {
+    let (temp_dir, config_file) = setup_the_way()?;
+    let store = MockTokenStore::default();
+    store.set("keyring token")?;
+
+    // The keyring is only used if use_keyring is set
+    let mut config = TheWayConfig::load_from(&config_file)?;
+    assert_eq!(resolve_github_token(None, &config, &store)?, None);
+    save_github_token(&mut config, "config token", &store)?;
+    assert_eq!(store.get()?.as_deref(), Some("keyring token"));
+    // The environment comes before the configuration file
+    assert_eq!(
+        resolve_github_token(Some("env token".into()), &config, &store)?.as_deref(),
+        Some("env token")
+    );
+    assert_eq!(
+        resolve_github_token(None, &config, &store)?.as_deref(),
+        Some("config token")
+    );
+
+    // With use_keyring, the configuration file still comes before the keyring
+    let mut config_contents = fs::read_to_string(&config_file)?;
+    config_contents.push_str("\nuse_keyring = true\ngithub_access_token = 'config token'");
+    fs::write(&config_file, config_contents)?;
+    let mut config = TheWayConfig::load_from(&config_file)?;
+    assert_eq!(
+        resolve_github_token(None, &config, &store)?.as_deref(),
+        Some("config token")
+    );
+    // Saving moves the token from the configuration file to the keyring
+    save_github_token(&mut config, "new token", &store)?;
+    assert_eq!(store.get()?.as_deref(), Some("new token"));
+    assert_eq!(
+        resolve_github_token(None, &config, &MockTokenStore::default())?,
+        None
+    );
+    assert_eq!(
+        resolve_github_token(None, &config, &store)?.as_deref(),
+        Some("new token")
+    );
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn gist_create_payload() -> color_eyre::Result<()> {
+    let contents = [
+        r#"{"description":"first","language":"rust","code":"fn main() {}"}"#,
+        r#"{"description":"second","language":"sh","tags":["tag"],"code":"ls"}"#,
+        r#"{"description":"secret","language":"rust","code":"fn main() {}"}"#,
+    ]
+    .join("\n");
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["private", "3"])
+        .assert()
+        .success();
+    let api = StubGistApi::start()?;
+    assert!(stub_sync(&api, &config_file, &["local"])?.status.success());
+    // With gist_public set, a Gist that's gone is made again as a public one
+    let mut config: toml::Value = toml::from_str(&fs::read_to_string(&config_file)?)?;
+    config["gist_public"] = true.into();
+    config["gist_id"] = "missing".into();
+    fs::write(&config_file, toml::to_string(&config)?)?;
+    assert!(stub_sync(&api, &config_file, &["local"])?.status.success());
+
+    let payloads = api
+        .requests()
+        .into_iter()
+        .filter(|(method, _, _)| method == "POST")
+        .map(|(_, _, body)| serde_json::from_str::<serde_json::Value>(&body))
+        .collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(payloads.len(), 2);
+    assert_eq!(payloads[0]["public"], false);
+    assert_eq!(payloads[1]["public"], true);
+    for payload in &payloads {
+        let files = payload["files"].as_object().unwrap();
+        assert_eq!(
+            files.keys().map(String::as_str).collect::<HashSet<_>>(),
+            HashSet::from(["snippet_1.rs", "snippet_2.sh"])
+        );
+        assert_eq!(files["snippet_2.sh"]["content"], "ls");
+    }
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn gitlab_urls_and_payloads() -> color_eyre::Result<()> {
+    let client = GitLabClient::new(None, "token");
+    assert_eq!(client.snippets_url(), "https://gitlab.com/api/v4/snippets");
+    assert_eq!(
+        client.snippet_url("42"),
+        "https://gitlab.com/api/v4/snippets/42"
+    );
+    let client = GitLabClient::new(Some("https://gitlab.mycorp.com/"), "token");
+    assert_eq!(
+        client.raw_file_url("42", "main", "dir/snippet 1.sh"),
+        "https://gitlab.mycorp.com/api/v4/snippets/42/files/main/dir%2Fsnippet%201.sh/raw"
+    );
+
+    let create = CreateGistPayload {
+        description: "the-way snippets",
+        public: false,
+        files: HashMap::from([
+            (
+                "snippet_2.py".to_owned(),
+                GistContent {
+                    content: "print(1)",
+                },
+            ),
+            ("snippet_1.sh".to_owned(), GistContent { content: "ls" }),
+        ]),
+    };
+    assert_eq!(
+        gitlab::create_payload(&create),
+        serde_json::json!({
+            "title": "the-way snippets",
+            "description": "the-way snippets",
+            "visibility": "private",
+            "files": [
+                {"file_path": "snippet_1.sh", "content": "ls"},
+                {"file_path": "snippet_2.py", "content": "print(1)"},
+            ],
+        })
+    );
+
+    let update = UpdateGistPayload {
+        description: "the-way snippets",
+        files: HashMap::from([
+            (
+                "index.md".to_owned(),
+                Some(GistContent { content: "# Index" }),
+            ),
+            ("snippet_1.sh".to_owned(), None),
+            (
+                "snippet_3.rs".to_owned(),
+                Some(GistContent {
+                    content: "fn main() {}",
+                }),
+            ),
+            ("snippet_9.rs".to_owned(), None),
+        ]),
+    };
+    let existing_paths = HashSet::from(["index.md".to_owned(), "snippet_1.sh".to_owned()]);
+    assert_eq!(
+        gitlab::update_payload(&update, &existing_paths),
+        serde_json::json!({
+            "title": "the-way snippets",
+            "description": "the-way snippets",
+            "files": [
+                {"action": "update", "file_path": "index.md", "content": "# Index"},
+                {"action": "delete", "file_path": "snippet_1.sh"},
+                {"action": "create", "file_path": "snippet_3.rs", "content": "fn main() {}"},
+            ],
+        })
+    );
+    Ok(())
+}
+
+#[test]
+fn sync_gitlab_needs_token() -> color_eyre::Result<()> {
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut config = fs::read_to_string(&config_file)?;
+    config.push_str("\nsync_backend = \"gitlab\"\n");
+    fs::write(&config_file, config)?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .env_remove("THE_WAY_GITLAB_TOKEN")
+        .env("THE_WAY_GITHUB_TOKEN", "unused")
+        .args(["sync", "date"])
+        .assert()
+        .failure()
+        .stderr(
+            predicate::str::contains("No GitLab access token")
+                .and(predicate::str::contains("$THE_WAY_GITLAB_TOKEN")),
+        );
+
+    // The Gist and the GitLab snippet are kept apart
+    let mut config = fs::read_to_string(&config_file)?;
+    config.push_str("gist_id = 'abc'\ngist_url = 'https://gist.github.com/abc'\n");
+    fs::write(&config_file, &config)?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["sync", "--url"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No Gist URL stored"));
+    config.push_str(
+        "gitlab_snippet_id = '42'\ngitlab_snippet_url = 'https://gitlab.com/-/snippets/42'\n",
+    );
+    fs::write(&config_file, &config)?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["sync", "--url"])
+        .assert()
+        .success()
+        .stdout("https://gitlab.com/-/snippets/42\n");
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn subcommand_aliases() -> color_eyre::Result<()> {
+    let contents = r#"{"description":"greet","language":"sh","code":"echo hello"}"#;
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["l", "--porcelain"])
+        .assert()
+        .success()
+        .stdout("1\tsh\t\tgreet\n");
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["v", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("echo hello"));
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn verbose_logging() -> color_eyre::Result<()> {
+    let (temp_dir, config_file) = setup_the_way()?;
+    let db_dir = temp_dir.path().join("db");
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("list")
+        .assert()
+        .success()
+        .stderr("");
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["-vv", "list"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(format!(
+            "DEBUG the_way::the_way::database] Opening database at {}",
+            db_dir.display()
+        )));
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn config_flag() -> color_eyre::Result<()> {
+    let contents = r#"{"description":"greet","language":"sh","code":"echo hello"}"#;
+    let (temp_dir, config_file) = setup_the_way()?;
+    let (other_temp_dir, other_config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.arg("--config")
+        .arg(&config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["list", "--porcelain"])
+        .assert()
+        .success()
+        .stdout("1\tsh\t\tgreet\n");
+    // --config takes precedence over $THE_WAY_CONFIG
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["list", "--porcelain", "--config"])
+        .arg(&other_config_file)
+        .assert()
+        .success()
+        .stdout("");
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("--config")
+        .arg(&other_config_file)
+        .args(["config", "get"])
+        .assert()
+        .success()
+        .stdout(format!("{}\n", other_config_file.display()));
+    // Changes are stored to the file given with --config
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("--config")
+        .arg(&other_config_file)
+        .args(["themes", "set", "InspiredGitHub"])
+        .assert()
+        .success();
+    assert!(fs::read_to_string(&other_config_file)?.contains("theme = 'InspiredGitHub'"));
+    assert!(!fs::read_to_string(&config_file)?.contains("InspiredGitHub"));
+    temp_dir.close()?;
+    other_temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn expand_data_dirs() -> color_eyre::Result<()> {
+    let temp_dir = tempdir()?;
+    let home = temp_dir.path();
+    let config_file = home.join("the-way.toml");
+    fs::write(
+        &config_file,
+        "theme = 'base16-ocean.dark'\n\
+        db_dir = '.local/share/the_way_db'\n\
+        themes_dir = '~/themes'",
+    )?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .env("HOME", home)
+        .args(["config", "get", "--verbose"])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains(format!(
+                "db dir: {}",
+                home.join(".local/share/the_way_db").display()
+            ))
+            .and(predicate::str::contains(format!(
+                "themes dir: {}",
+                home.join("themes").display()
+            ))),
+        );
+    assert!(home.join(".local/share/the_way_db").exists());
+
+    // Storing the configuration keeps the paths as written
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .env("HOME", home)
+        .args(["themes", "set", "base16-mocha.dark"])
+        .assert()
+        .success();
+    let contents = fs::read_to_string(&config_file)?;
+    assert!(contents.contains("db_dir = '.local/share/the_way_db'"));
+    assert!(contents.contains("themes_dir = '~/themes'"));
+
+    let data_home = home.join("data");
+    fs::write(
+        &config_file,
+        format!(
+            "theme = 'base16-ocean.dark'\n\
+            db_dir = '$XDG_DATA_HOME/the_way_db'\n\
+            themes_dir = '{}'",
+            home.join("absolute_themes").display()
+        ),
+    )?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .env("HOME", home)
+        .env("XDG_DATA_HOME", &data_home)
+        .args(["config", "get", "--verbose"])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains(format!(
+                "db dir: {}",
+                data_home.join("the_way_db").display()
+            ))
+            .and(predicate::str::contains(format!(
+                "themes dir: {}",
+                home.join("absolute_themes").display()
+            ))),
+        );
+
+    // Only a leading variable is expanded
+    fs::write(
+        &config_file,
+        "theme = 'base16-ocean.dark'\n\
+        db_dir = 'data/$HOME/the_way_db'\n\
+        themes_dir = '$HOMEWARD/themes'",
+    )?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .env("HOME", home)
+        .args(["config", "get", "--verbose"])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains(format!(
+                "db dir: {}",
+                home.join("data/$HOME/the_way_db").display()
+            ))
+            .and(predicate::str::contains(format!(
+                "themes dir: {}",
+                home.join("$HOMEWARD/themes").display()
+            ))),
+        );
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn relative_themes_dir() -> color_eyre::Result<()> {
+    let temp_dir = tempdir()?;
+    let home = temp_dir.path();
+    let dotfiles = home.join("dotfiles");
+    let elsewhere = home.join("elsewhere");
+    fs::create_dir_all(&dotfiles)?;
+    fs::create_dir_all(&elsewhere)?;
+    let config_file = dotfiles.join("the-way.toml");
+    fs::write(
+        &config_file,
+        "theme = 'base16-ocean.dark'\n\
+        db_dir = 'the_way_db'\n\
+        themes_dir = 'themes'",
+    )?;
+    // Relative themes_dir follows the configuration file, not the working directory or home
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .env("HOME", home)
+        .current_dir(&elsewhere)
+        .args(["config", "get", "--verbose"])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains(format!("themes dir: {}", dotfiles.join("themes").display()))
+                .and(predicate::str::contains(format!(
+                    "db dir: {}",
+                    home.join("the_way_db").display()
+                ))),
+        );
+    assert!(dotfiles.join("themes").exists());
+    assert!(!elsewhere.join("themes").exists());
+
+    // Also when the configuration file is given relative to the working directory
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("HOME", home)
+        .env_remove("THE_WAY_CONFIG")
+        .current_dir(home)
+        .args([
+            "--config",
+            "dotfiles/the-way.toml",
+            "config",
+            "get",
+            "--verbose",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "themes dir: {}",
+            dotfiles.join("themes").display()
+        )));
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn view_oneline() -> color_eyre::Result<()> {
+    let contents =
+        r#"{"description":"list files","language":"bash","tags":["fs"],"code":"ls -la"}"#;
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["view", "1", "--oneline"])
+        .assert()
+        .success()
+        .stdout("#1 list files (bash)\n");
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn partial_config() -> color_eyre::Result<()> {
+    let temp_dir = tempdir()?;
+    let home = temp_dir.path();
+    let config_file = home.join("the-way.toml");
+    fs::write(&config_file, "theme = 'base16-mocha.dark'")?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .env("HOME", home)
+        .env_remove("XDG_DATA_HOME")
+        .args(["config", "get", "--verbose"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "db dir: {}",
+            home.join(".local/share/the-way/the_way_db").display()
+        )));
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .env("HOME", home)
+        .env_remove("XDG_DATA_HOME")
+        .args(["themes", "get"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("base16-mocha.dark"));
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .env("HOME", home)
+        .env_remove("XDG_DATA_HOME")
+        .args(["sync", "--url"])
+        .assert()
+        .failure();
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn tags_languages_counts() -> color_eyre::Result<()> {
+    let contents = [
+        r#"{"description":"first","language":"rust","tags":["cli","rust"],"code":"code"}"#,
+        r#"{"description":"second","language":"rust","tags":["rust","async"],"code":"code"}"#,
+        r#"{"description":"third","language":"python","tags":["cli"],"code":"code"}"#,
+        r#"{"description":"fourth","language":"python","tags":["rust"],"code":"code"}"#,
+        r#"{"description":"fifth","language":"sh","tags":["cli","rust"],"code":"code"}"#,
+    ]
+    .join("\n");
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+    // Most used first, alphabetically among equals
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["tags", "--plain"])
+        .assert()
+        .success()
+        .stdout("rust (4)\ncli (3)\nasync (1)\n");
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["languages", "--plain"])
+        .assert()
+        .success()
+        .stdout("python (2)\nrust (2)\nsh (1)\n");
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn tags_languages_json() -> color_eyre::Result<()> {
+    let contents = [
+        r#"{"description":"first","language":"rust","tags":["cli","rust"],"code":"code"}"#,
+        r#"{"description":"second","language":"rust","tags":["rust"],"code":"code"}"#,
+        r#"{"description":"third","language":"python","tags":["cli"],"code":"code"}"#,
+        r#"{"description":"fourth","language":"python","tags":["rust"],"code":"code"}"#,
+    ]
+    .join("\n");
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    let output = cmd
+        .env("THE_WAY_CONFIG", &config_file)
+        .args(["tags", "--json"])
+        .output()?;
+    assert!(output.status.success());
+    let tags: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(
+        tags,
+        serde_json::json!([{"name": "rust", "count": 3}, {"name": "cli", "count": 2}])
+    );
+
+    // Filters apply as usual
+    let mut cmd = Command::cargo_bin("the-way")?;
+    let output = cmd
+        .env("THE_WAY_CONFIG", &config_file)
+        .args(["languages", "--json", "--tags", "cli"])
+        .output()?;
+    assert!(output.status.success());
+    let languages: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(
+        languages,
+        serde_json::json!([{"name": "python", "count": 1}, {"name": "rust", "count": 1}])
+    );
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn export_yaml_toml() -> color_eyre::Result<()> {
+    let contents = [
+        r#"{"description":"first","language":"rust","tags":["tag1","tag2"],"code":"fn main() {\n    println!(\"hi\");\n}\n"}"#,
+        r#"{"description":"second","language":"sh","code":"echo 'quoted: yes'"}"#,
+    ]
+    .join("\n");
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    let output = cmd
+        .env("THE_WAY_CONFIG", &config_file)
+        .args(["export", "--format", "yaml"])
+        .output()?;
+    assert!(output.status.success());
+    let yaml_snippets: Vec<serde_json::Value> = serde_yaml::from_slice(&output.stdout)?;
+    assert_eq!(yaml_snippets.len(), 2);
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    let output = cmd
+        .env("THE_WAY_CONFIG", &config_file)
+        .args(["export", "--format", "toml"])
+        .output()?;
+    assert!(output.status.success());
+    let toml_snippets: toml::Value = toml::from_slice(&output.stdout)?;
+    assert_eq!(toml_snippets["snippets"].as_array().map(Vec::len), Some(2));
+    assert_eq!(
+        toml_snippets["snippets"][1]["description"].as_str(),
+        Some("second")
+    );
+
+    // YAML output survives a round trip through import
+    let (temp_dir_2, config_file_2) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file_2)
+        .arg("import")
+        .write_stdin(serde_json::to_string(&yaml_snippets)?)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Imported 2 snippets"));
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file_2)
+        .args(["view", "1"])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("println!(\"hi\");").and(predicate::str::contains("tag2")),
+        );
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file_2)
+        .args(["view", "2"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("echo 'quoted: yes'"));
+    drop(config_file);
+    drop(config_file_2);
+    temp_dir.close()?;
+    temp_dir_2.close()?;
+    Ok(())
+}
+
+#[test]
+fn import_yaml_toml() -> color_eyre::Result<()> {
+    let contents = [
+        r#"{"description":"first","language":"rust","tags":["tag1","tag2"],"code":"fn main() {\n    println!(\"hi\");\n}\n"}"#,
+        r#"{"description":"second","language":"sh","code":"echo 'quoted: yes'"}"#,
+    ]
+    .join("\n");
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+    let yaml_file = temp_dir.path().join("snippets.yml");
+    let toml_file = temp_dir.path().join("snippets.toml");
+    for (format, file) in [("yaml", &yaml_file), ("toml", &toml_file)] {
+        let mut cmd = Command::cargo_bin("the-way")?;
+        cmd.env("THE_WAY_CONFIG", &config_file)
+            .args(["export", "--format", format])
+            .arg(file)
+            .assert()
+            .success();
+    }
+
+    // Formats are taken from file extensions
+    let (temp_dir_2, config_file_2) = setup_the_way()?;
+    for file in [&yaml_file, &toml_file] {
+        let mut cmd = Command::cargo_bin("the-way")?;
+        cmd.env("THE_WAY_CONFIG", &config_file_2)
+            .arg("import")
+            .arg(file)
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("Imported 2 snippets"));
+    }
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file_2)
+        .args(["view", "3"])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains("println!(\"hi\");").and(predicate::str::contains("tag2")),
+        );
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file_2)
+        .args(["view", "2"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("echo 'quoted: yes'"));
+
+    // YAML on stdin is read after failing as JSON, TOML needs --format
+    let (temp_dir_3, config_file_3) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file_3)
+        .arg("import")
+        .write_stdin(fs::read(&yaml_file)?)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Imported 2 snippets"));
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file_3)
+        .arg("import")
+        .write_stdin(fs::read(&toml_file)?)
+        .assert()
+        .failure();
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file_3)
+        .args(["import", "--format", "toml"])
+        .write_stdin(fs::read(&toml_file)?)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Imported 2 snippets"));
+    drop(config_file);
+    drop(config_file_2);
+    drop(config_file_3);
+    temp_dir.close()?;
+    temp_dir_2.close()?;
+    temp_dir_3.close()?;
+    Ok(())
+}
+
+#[test]
+fn search_copy_keys() -> color_eyre::Result<()> {
+    let contents = r#"{"description":"test description","language":"rust","code":"code"}"#;
+    let (temp_dir, config_file) = setup_the_way()?;
+    let copied_file = temp_dir.path().join("copied.txt");
+    let mut config_contents = fs::read_to_string(&config_file)?;
+    config_contents.push_str(&format!(
+        "\ncopy_cmd = 'cp {{file}} {}'",
+        copied_file.to_string_lossy()
+    ));
+    fs::write(&config_file, config_contents)?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+
+    let mut p = spawn_bash()?;
+    p.send_line(format!(
+        "export THE_WAY_CONFIG={}",
+        config_file.to_string_lossy()
+    ))?;
+    let executable = env!("CARGO_BIN_EXE_the-way");
+    let output_file = temp_dir.path().join("output.txt");
+    p.expect_prompt()?;
+    // ctrl-o prints even without --stdout
+    p.send_line(format!(
+        "{executable} cp > {}",
+        output_file.to_string_lossy()
+    ))?;
+    p.expect("ctrl-y to copy, ctrl-o to print")?;
+    p.expect("test description")?;
+    p.send("\x0f")?;
+    p.expect_prompt()?;
+    assert_eq!(fs::read_to_string(&output_file)?, "code\n");
+    assert!(!copied_file.exists());
+
+    // ctrl-y copies even with --stdout
+    p.send_line(format!(
+        "{executable} cp --stdout > {}",
+        output_file.to_string_lossy()
+    ))?;
+    p.expect("test description")?;
+    p.send("\x19")?;
+    p.expect_prompt()?;
+    assert_eq!(fs::read_to_string(&output_file)?, "");
+    assert_eq!(fs::read_to_string(&copied_file)?, "code");
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn move_snippet() -> color_eyre::Result<()> {
+    let contents = [
+        r#"{"description":"first","language":"rust","tags":["tag1","tag2"],"code":"fn first() {}","date":"2020-01-02T03:04:05Z","updated":"2021-06-07T08:09:10Z"}"#,
+        r#"{"description":"second","language":"python","tags":["tag2"],"code":"second = 2"}"#,
+    ]
+    .join("\n");
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+
+    // Can't move onto an existing snippet
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["move", "1", "2"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("already written a snippet at 2"));
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["move", "1", "0"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "invalid value '0' for '<TO>': snippet indices start at 1",
+        ));
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["move", "1", "5"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Snippet #1 moved to #5"));
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["view", "1"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "You haven't written that snippet: 1",
+        ));
+    let mut cmd = Command::cargo_bin("the-way")?;
+    let output = cmd
+        .env("THE_WAY_CONFIG", &config_file)
+        .args(["export", "--tags", "tag1"])
+        .output()?;
+    assert!(output.status.success());
+    let snippet: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(snippet["index"], 5);
+    assert_eq!(snippet["description"], "first");
+    assert_eq!(snippet["code"], "fn first() {}");
+    assert_eq!(snippet["tags"], serde_json::json!(["tag1", "tag2"]));
+    assert_eq!(snippet["date"], "2020-01-02T03:04:05Z");
+    assert_eq!(snippet["updated"], "2021-06-07T08:09:10Z");
+
+    // Language and tag listings follow the move, and new snippets go after it
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["list", "--porcelain", "--languages", "rust"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("5\t"));
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(r#"{"description":"third","language":"rust","code":"third"}"#)
+        .assert()
+        .success();
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["view", "6", "--oneline"])
+        .assert()
+        .success()
+        .stdout("#6 third (rust)\n");
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+fn pick_tags_interactive(config_file: &Path) -> color_eyre::Result<()> {
+    let mut p = spawn_bash()?;
+    p.send_line(format!(
+        "export THE_WAY_CONFIG={}",
+        config_file.to_string_lossy()
+    ))?;
+    let executable = env!("CARGO_BIN_EXE_the-way");
+    p.expect_prompt()?;
+    p.send_line(format!("{executable} edit 1 --pick-tags"))?;
+    p.expect("Description")?;
+    p.send_line("")?;
+    p.expect("Language")?;
+    p.send_line("")?;
+    // Accept the snippet's own tags as checked, then add a new one
+    p.expect("Tags (space to toggle")?;
+    p.send("\r")?;
+    p.expect("New tags")?;
+    p.send_line("tag4")?;
+    p.expect("Date")?;
+    p.send_line("")?;
+    p.expect("Edit snippet")?;
+    p.send_line("")?;
+    p.expect("Snippet #1 changed")?;
+    p.expect_prompt()?;
+    Ok(())
+}
+
+#[test]
+fn pick_tags() -> color_eyre::Result<()> {
+    let contents = [
+        r#"{"description":"first","language":"rust","tags":["tag1","tag2"],"code":"code"}"#,
+        r#"{"description":"second","language":"rust","tags":["tag3"],"code":"code"}"#,
+    ]
+    .join("\n");
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+    assert!(pick_tags_interactive(&config_file).is_ok());
+    let mut cmd = Command::cargo_bin("the-way")?;
+    let output = cmd
+        .env("THE_WAY_CONFIG", &config_file)
+        .args(["export", "--tags", "tag1"])
+        .output()?;
+    assert!(output.status.success());
+    let snippet: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(snippet["tags"], serde_json::json!(["tag1", "tag2", "tag4"]));
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn reindex() -> color_eyre::Result<()> {
+    let contents = [
+        r#"{"description":"first","language":"rust","tags":["tag1"],"code":"code"}"#,
+        r#"{"description":"second","language":"python","tags":["tag1"],"code":"code"}"#,
+        r#"{"description":"third","language":"sh","tags":["tag2"],"code":"code"}"#,
+    ]
+    .join("\n");
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["del", "2", "--force"])
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("reindex")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Reindexed 1 snippets"));
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["list", "--porcelain"])
+        .assert()
+        .success()
+        .stdout("1\trust\ttag1\tfirst\n2\tsh\ttag2\tthird\n");
+    // Language and tag trees point to the new indices
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["list", "--porcelain", "--languages", "sh", "--tags", "tag2"])
+        .assert()
+        .success()
+        .stdout("2\tsh\ttag2\tthird\n");
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["list", "--porcelain", "--tags", "tag1"])
+        .assert()
+        .success()
+        .stdout("1\trust\ttag1\tfirst\n");
+
+    // New snippets continue after the last one
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(r#"{"description":"fourth","language":"rust","code":"code"}"#)
+        .assert()
+        .success();
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["view", "3", "--oneline"])
+        .assert()
+        .success()
+        .stdout("#3 fourth (rust)\n");
+
+    // The synced Gist has to be updated afterwards
+    let mut config_contents = fs::read_to_string(&config_file)?;
+    config_contents.push_str("\ngist_id = 'abc'\n");
+    fs::write(&config_file, config_contents)?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["del", "1", "--force"])
+        .assert()
+        .success();
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("reindex")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Reindexed 2 snippets"))
+        .stderr(predicate::str::contains(
+            "Warning: the synced Gist still has the old indices",
+        ));
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn import_skips_invalid_utf8() -> color_eyre::Result<()> {
+    let (temp_dir, config_file) = setup_the_way()?;
+    let file = temp_dir.path().join("snippets.json");
+    let mut contents = Vec::new();
+    contents.extend_from_slice(br#"{"description":"first","language":"rust","code":"code"}"#);
+    contents.extend_from_slice(
+        b"\n{\"description\":\"bad\",\"language\":\"rust\",\"code\":\"\xff\xfe\"}\n",
+    );
+    // Pretty-printed, so its lines aren't records
+    contents.extend_from_slice(
+        b"{\n  \"description\": \"third\",\n  \"language\": \"rust\",\n  \"code\": \"code\"\n}\n",
+    );
+    contents.extend_from_slice(
+        b"{\n  \"description\": \"fourth\",\n  \"language\": \"rust\",\n  \"code\": \"\xe2\x28\"\n}",
+    );
+    fs::write(&file, contents)?;
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Imported 2 snippets, skipped 2 (not UTF-8 text: record 2, record 4)",
+        ));
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["list", "--porcelain"])
+        .assert()
+        .success()
+        .stdout("1\trust\t\tfirst\n2\trust\t\tthird\n");
+
+    // YAML isn't split into records
+    let file = temp_dir.path().join("snippets.yaml");
+    fs::write(
+        &file,
+        b"- description: bad\n  language: rust\n  code: \"\xff\"\n",
+    )?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .arg(&file)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid utf-8"));
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn import_zip() -> color_eyre::Result<()> {
+    let (temp_dir, config_file) = setup_the_way()?;
+    let zip_file = temp_dir.path().join("scripts.zip");
+    let mut zip = zip::ZipWriter::new(fs::File::create(&zip_file)?);
+    let options = zip::write::SimpleFileOptions::default();
+    zip.add_directory("scripts/", options)?;
+    zip.start_file("scripts/hello.py", options)?;
+    io::Write::write_all(&mut zip, b"print('hello')\n")?;
+    zip.start_file("backup", options)?;
+    io::Write::write_all(&mut zip, b"#!/bin/bash\ncp -r ~/notes /backup\n")?;
+    zip.start_file("logo.png", options)?;
+    io::Write::write_all(&mut zip, &[0x89, b'P', b'N', b'G', 0xff, 0xfe])?;
+    // Compresses to a few KiB but is too large to read
+    zip.start_file("huge.txt", options)?;
+    io::Write::write_all(&mut zip, &vec![b'a'; 17 * 1024 * 1024])?;
+    zip.finish()?;
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .arg(&zip_file)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Imported 2 snippets, skipped 2 (not UTF-8 text or too large: logo.png, huge.txt)",
+        ));
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["list", "--porcelain"])
+        .assert()
+        .success()
+        .stdout("1\tpython\t\tscripts/hello.py\n2\tsh\t\tbackup\n");
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["cp", "1", "--stdout"])
+        .assert()
+        .success()
+        .stdout("print('hello')\n\n");
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn list_count() -> color_eyre::Result<()> {
+    // Dates out of index order, so the most recent ones aren't simply the last ones
+    let contents = [
+        ("first", "2021-01-01"),
+        ("second", "2023-01-01"),
+        ("third", "2020-01-01"),
+        ("fourth", "2022-01-01"),
+        ("fifth", "2019-01-01"),
+    ]
+    .iter()
+    .map(|(description, date)| {
+        format!(
+            r#"{{"description":"{description}","language":"rust","code":"code","date":"{date}T00:00:00Z"}}"#
+        )
+    })
+    .collect::<Vec<_>>()
+    .join("\n");
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["list", "--porcelain", "-n", "2"])
+        .assert()
+        .success()
+        .stdout("2\trust\t\tsecond\n4\trust\t\tfourth\n");
+    let mut cmd = Command::cargo_bin("the-way")?;
+    let output = cmd
+        .env("THE_WAY_CONFIG", &config_file)
+        .args(["--plain", "list", "--count", "3"])
+        .output()?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout)?;
+    assert_eq!(stdout.matches("\ncode").count(), 3);
+    assert!(stdout.contains("first") && !stdout.contains("third") && !stdout.contains("fifth"));
+
+    // Applies after the other filters
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["list", "--porcelain", "-n", "1", "--before", "4"])
+        .assert()
+        .success()
+        .stdout("2\trust\t\tsecond\n");
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn color_flags_after_subcommand() -> color_eyre::Result<()> {
+    let contents = r#"{"description":"test description","language":"rust","code":"fn main() {}"}"#;
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["view", "1", "--colorize"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\x1b["));
+    for args in [["view", "1", "--plain"], ["-p", "view", "1"]] {
+        let mut cmd = Command::cargo_bin("the-way")?;
+        cmd.env("THE_WAY_CONFIG", &config_file)
+            .args(args)
+            .assert()
+            .success()
+            .stdout(
+                predicate::str::contains("fn main() {}")
+                    .and(predicate::str::contains("\x1b[").not()),
+            );
+    }
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["view", "1", "--plain", "--colorize"])
+        .assert()
+        .failure();
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn list_sort() -> color_eyre::Result<()> {
+    let contents = [
+        ("first", "python", "2021-01-01"),
+        ("second", "rust", "2023-01-01"),
+        ("third", "python", "2021-01-01"),
+        ("fourth", "bash", "2022-01-01"),
+    ]
+    .iter()
+    .map(|(description, language, date)| {
+        format!(
+            r#"{{"description":"{description}","language":"{language}","code":"code","date":"{date}T00:00:00Z"}}"#
+        )
+    })
+    .collect::<Vec<_>>()
+    .join("\n");
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("import")
+        .write_stdin(contents)
+        .assert()
+        .success();
+
+    let list = |args: &[&str]| -> color_eyre::Result<Vec<String>> {
+        let mut cmd = Command::cargo_bin("the-way")?;
+        let output = cmd
+            .env("THE_WAY_CONFIG", &config_file)
+            .args(["list", "--porcelain"])
+            .args(args)
+            .output()?;
+        assert!(output.status.success());
+        Ok(String::from_utf8(output.stdout)?
+            .lines()
+            .map(|line| line.rsplit('\t').next().unwrap_or_default().to_owned())
+            .collect())
+    };
+    assert_eq!(list(&[])?, ["first", "second", "third", "fourth"]);
+    assert_eq!(
+        list(&["--reverse"])?,
+        ["fourth", "third", "second", "first"]
+    );
+    // Snippets with the same date stay in index order
+    assert_eq!(
+        list(&["--sort", "date", "--reverse"])?,
+        ["second", "fourth", "first", "third"]
+    );
+    assert_eq!(
+        list(&["--sort", "date"])?,
+        ["first", "third", "fourth", "second"]
+    );
+    assert_eq!(
+        list(&["--sort", "language"])?,
+        ["fourth", "first", "third", "second"]
+    );
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}
+
+#[test]
+fn import_gist_file() -> color_eyre::Result<()> {
+    let (temp_dir, config_file) = setup_the_way()?;
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["import", "--gist-file", "tests/data/gist.json"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Imported 2 snippets"));
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .arg("list")
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::contains(
+                "Hello World Examples - aa5a315d61ae9438b18d - hello_world.rb",
+            )
+            .and(predicate::str::contains(
+                "Hello World Examples - aa5a315d61ae9438b18d - hello_world.py",
+            ))
+            .and(predicate::str::contains("print(\"Hello World!\")"))
+            .and(predicate::str::contains("gist")),
+        );
+
+    // Files that aren't Gist API responses are rejected
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args(["import", "--gist-file", "tests/data/snippets.json"])
+        .assert()
+        .failure();
+    // and --gist-file can't be mixed with other sources
+    let mut cmd = Command::cargo_bin("the-way")?;
+    cmd.env("THE_WAY_CONFIG", &config_file)
+        .args([
+            "import",
+            "tests/data/snippets.json",
+            "--gist-file",
+            "tests/data/gist.json",
+        ])
+        .assert()
+        .failure();
+    drop(config_file);
+    temp_dir.close()?;
+    Ok(())
+}